@@ -0,0 +1,106 @@
+use std::time::Instant;
+
+use arcnova_chain::blockchain::chain::ChainManager;
+#[cfg(unix)]
+use arcnova_chain::p2p::admin::{self, AdminResponse};
+use arcnova_chain::p2p::{
+    self,
+    admin::AdminHandle,
+    banlist::BanList,
+    config::{NodeCommand, NodeConfig},
+    node_key::NodeKey,
+    peers::PeerBook,
+    pipeline, sync, ChainHandle, ServiceFlags, INBOUND_QUEUE_SIZE,
+};
+use arcnova_chain::storage::{ChainStore, Store, StoreConfig};
+use tokio::sync::mpsc;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = match NodeCommand::from_args(std::env::args().skip(1))? {
+        NodeCommand::Run(config) => config,
+        NodeCommand::ShowPeerId(config) => {
+            std::fs::create_dir_all(config.network_dir())?;
+            let key = NodeKey::load_or_generate(config.node_key_path())?;
+            println!("{}", p2p::node_peer_id(&key));
+            return Ok(());
+        }
+        NodeCommand::RotateKey(config) => {
+            std::fs::create_dir_all(config.network_dir())?;
+            let key = NodeKey::rotate(config.node_key_path())?;
+            println!("New PeerId: {}", p2p::node_peer_id(&key));
+            return Ok(());
+        }
+        #[cfg(unix)]
+        NodeCommand::Admin(config, request) => {
+            let response = admin::admin_request(config.admin_socket_path(), &request).await?;
+            return print_admin_response(response);
+        }
+        #[cfg(not(unix))]
+        NodeCommand::Admin(..) => return Err("the admin socket needs a Unix platform".into()),
+    };
+    run_node(config).await
+}
+
+#[cfg(unix)]
+fn print_admin_response(response: AdminResponse) -> Result<(), Box<dyn std::error::Error>> {
+    match response {
+        AdminResponse::Bans(bans) => {
+            for (peer, entry) in bans {
+                println!("{}\tuntil {}\t{}", peer, entry.until, entry.reason);
+            }
+        }
+        AdminResponse::Banned => println!("Banned"),
+        AdminResponse::Unbanned(true) => println!("Unbanned"),
+        AdminResponse::Unbanned(false) => println!("Peer was not banned"),
+        AdminResponse::Failed(e) => return Err(e.into()),
+    }
+    Ok(())
+}
+
+async fn run_node(config: NodeConfig) -> Result<(), Box<dyn std::error::Error>> {
+    println!("🚀 ArcNova Chain Node Starting...");
+    let params = config.network.params();
+    let dir = config.network_dir();
+
+    // Identity and chain state
+    std::fs::create_dir_all(&dir)?;
+    let key = NodeKey::load_or_generate(config.node_key_path())?;
+    let store_config = StoreConfig { prune_depth: config.prune_depth, ..StoreConfig::default() };
+    let mut store = ChainStore::open_with_config(dir.join("chain"), store_config)?;
+    params.init_store(&mut store)?;
+    let services = ServiceFlags::for_node(store.is_pruned()?);
+    let tip = Store::tip(&store)?;
+    let locator = sync::block_locator(&store);
+
+    // Known peers: configured static peers plus addresses saved last run
+    let mut peers = PeerBook::open(dir.join("peers.json"), Instant::now())?;
+    for addr in &config.peers {
+        peers.add_static(addr.clone(), Instant::now());
+    }
+    let bans = BanList::open(dir.join("bans.json"))?;
+    // Operators reach the admin API through the control socket
+    let (admin_handle, admin_rx) = AdminHandle::channel();
+    #[cfg(unix)]
+    {
+        let admin_socket = admin::bind_admin_socket(config.admin_socket_path())?;
+        tokio::spawn(admin::serve_admin_socket(admin_socket, admin_handle));
+    }
+    #[cfg(not(unix))]
+    drop(admin_handle);
+
+    let (command_tx, command_rx) = mpsc::channel(INBOUND_QUEUE_SIZE);
+    let (event_tx, event_rx) = mpsc::channel(INBOUND_QUEUE_SIZE);
+    let chain_task = tokio::spawn(pipeline::run_chain_manager(
+        ChainManager::new(store),
+        command_rx,
+        event_tx,
+    ));
+
+    let swarm = p2p::start_p2p_node(&config, &key, services).await?;
+    let chain = ChainHandle { commands: command_tx, events: event_rx, tip, locator };
+    p2p::run_p2p_event_loop(swarm, params, chain, peers, bans, admin_rx).await;
+
+    chain_task.await?;
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+
+use bech32::primitives::decode::CheckedHrpstring;
+use bech32::{Bech32m, Hrp};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::params::NetworkParams;
+
+/// Version of addresses paying to the hash of an ed25519 public key, the
+/// only kind outputs may pay to so far
+pub const ADDRESS_VERSION: u8 = 0;
+
+/// Bytes of public key hash in an address
+pub const ADDRESS_HASH_SIZE: usize = 20;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AddressError {
+    #[error("invalid encoding: {0}")]
+    Encoding(String),
+
+    #[error("address has prefix {found}, expected {expected}")]
+    WrongPrefix { expected: String, found: String },
+
+    #[error("address payload of {0} bytes")]
+    InvalidLength(usize),
+}
+
+/// Where an output's value goes: a version and the hash of a public key.
+///
+/// Shown to people Bech32m-encoded, with the network's address prefix as
+/// human-readable part and the version as first payload byte, e.g.
+/// `arc1qq…` on mainnet. The checksum catches typos and the prefix keeps
+/// coins from being sent to another network's address.
+///
+/// Only `ADDRESS_VERSION` is spendable today. Other versions decode, so
+/// that newer addresses can at least be read, but the builder refuses to
+/// pay them and validation rejects transactions that do.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address {
+    version: u8,
+    hash: [u8; ADDRESS_HASH_SIZE],
+}
+
+impl Address {
+    pub const fn new(version: u8, hash: [u8; ADDRESS_HASH_SIZE]) -> Self {
+        Address { version, hash }
+    }
+
+    /// Address of a 32-byte ed25519 public key, or `None` for any other length
+    pub fn from_public_key(public_key: &[u8]) -> Option<Self> {
+        if public_key.len() != 32 {
+            return None;
+        }
+
+        let digest = Sha256::digest(public_key);
+        let mut hash = [0u8; ADDRESS_HASH_SIZE];
+        hash.copy_from_slice(&digest[..ADDRESS_HASH_SIZE]);
+        Some(Address::new(ADDRESS_VERSION, hash))
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn hash(&self) -> &[u8; ADDRESS_HASH_SIZE] {
+        &self.hash
+    }
+
+    /// Whether outputs may pay to this address
+    pub fn is_supported(&self) -> bool {
+        self.version == ADDRESS_VERSION
+    }
+
+    /// `[version | hash]`
+    pub fn to_bytes(&self) -> [u8; ADDRESS_HASH_SIZE + 1] {
+        let mut bytes = [0u8; ADDRESS_HASH_SIZE + 1];
+        bytes[0] = self.version;
+        bytes[1..].copy_from_slice(&self.hash);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AddressError> {
+        if bytes.len() != ADDRESS_HASH_SIZE + 1 {
+            return Err(AddressError::InvalidLength(bytes.len()));
+        }
+        let mut hash = [0u8; ADDRESS_HASH_SIZE];
+        hash.copy_from_slice(&bytes[1..]);
+        Ok(Address::new(bytes[0], hash))
+    }
+
+    /// Bech32m encoding with `params`' address prefix
+    pub fn encode(&self, params: &NetworkParams) -> String {
+        let hrp = Hrp::parse(params.address_prefix).expect("address prefixes are valid");
+        bech32::encode::<Bech32m>(hrp, &self.to_bytes()).expect("addresses fit in a bech32 string")
+    }
+
+    /// Parse an address shown by `encode`, refusing other networks'
+    pub fn decode(encoded: &str, params: &NetworkParams) -> Result<Self, AddressError> {
+        let checked = CheckedHrpstring::new::<Bech32m>(encoded)
+            .map_err(|e| AddressError::Encoding(e.to_string()))?;
+
+        let prefix = checked.hrp().to_lowercase();
+        if prefix != params.address_prefix {
+            return Err(AddressError::WrongPrefix {
+                expected: params.address_prefix.to_string(),
+                found: prefix,
+            });
+        }
+        Address::from_bytes(&checked.byte_iter().collect::<Vec<u8>>())
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Address(v{}, {})", self.version, hex::encode(self.hash))
+    }
+}
+
+// Hex of `to_bytes` in human-readable formats such as keystore JSON,
+// `(version, hash)` on the wire and on disk
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&hex::encode(self.to_bytes()))
+        } else {
+            (self.version, self.hash).serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let encoded = String::deserialize(deserializer)?;
+            let bytes = hex::decode(&encoded).map_err(serde::de::Error::custom)?;
+            Address::from_bytes(&bytes).map_err(serde::de::Error::custom)
+        } else {
+            let (version, hash) = <(u8, [u8; ADDRESS_HASH_SIZE])>::deserialize(deserializer)?;
+            Ok(Address::new(version, hash))
+        }
+    }
+}
@@ -11,3 +11,39 @@ pub struct Block {
     pub nonce: u64,
     pub hash: Hash,
 }
+
+/// A block without its transactions, as exchanged during header-first sync
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub prev_hash: Hash,
+    pub timestamp: u128,
+    pub nonce: u64,
+    pub hash: Hash,
+}
+
+impl Block {
+    /// Hash of the header fields and the transaction ids, which in turn
+    /// commit to each transaction's contents
+    pub fn calculate_hash(&self) -> Hash {
+        let txids: Vec<&Hash> = self.transactions.iter().map(|tx| &tx.id).collect();
+        Hash::hash(&(self.index, &self.prev_hash, self.timestamp, txids, self.nonce))
+    }
+
+    /// Whether `hash` commits to this block's contents: it matches the
+    /// header fields and transaction ids, and every id matches its transaction
+    pub fn is_consistent(&self) -> bool {
+        self.hash == self.calculate_hash()
+            && self.transactions.iter().all(|tx| tx.has_valid_id(self.index))
+    }
+
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            index: self.index,
+            prev_hash: self.prev_hash.clone(),
+            timestamp: self.timestamp,
+            nonce: self.nonce,
+            hash: self.hash.clone(),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use super::block::Block;
+use super::fees::FeeEstimator;
+use super::mempool::Mempool;
+use super::state::State;
+use super::transaction::Transaction;
+use super::utxo::UtxoSet;
+use crate::storage::Store;
+
+/// Outcome of validating an item received from the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Valid and applied; safe to relay
+    Accept,
+    /// Invalid; drop it and hold the sender responsible
+    Reject,
+    /// Not invalid but not useful right now (duplicate, orphan, missing inputs)
+    Ignore,
+}
+
+/// Owns the chain state and mempool and decides what to do with incoming
+/// blocks and transactions
+pub struct ChainManager<S: Store = UtxoSet> {
+    pub state: State<S>,
+    pub mempool: Mempool,
+    /// Learns fee rates from every connected block
+    pub fees: FeeEstimator,
+}
+
+impl<S: Store> ChainManager<S> {
+    pub fn new(store: S) -> Self {
+        ChainManager {
+            state: State { utxo_set: store },
+            mempool: Mempool::default(),
+            fees: FeeEstimator::default(),
+        }
+    }
+
+    /// Validate `block` and connect it if it extends the current tip
+    pub fn process_block(&mut self, block: Block) -> Verdict {
+        let tip = match self.state.utxo_set.tip() {
+            Ok(tip) => tip,
+            Err(_) => return Verdict::Ignore,
+        };
+
+        // 1. Must build on our tip; anything else is a duplicate or an orphan
+        let expected_index = match &tip {
+            Some((_, hash)) if *hash == block.hash => return Verdict::Ignore,
+            Some((height, hash)) if *hash == block.prev_hash => height + 1,
+            Some(_) => return Verdict::Ignore,
+            None => 0,
+        };
+
+        if block.index != expected_index {
+            return Verdict::Reject;
+        }
+
+        // 2. Hash and transactions must be valid against the current UTXO set
+        if !self.state.validate_block(&block) {
+            return Verdict::Reject;
+        }
+
+        // 3. Persist and drop now-confirmed or conflicting pool transactions
+        let undo = match self.state.utxo_set.connect_block(&block) {
+            Ok(undo) => undo,
+            Err(_) => return Verdict::Ignore,
+        };
+        self.mempool.remove_for_block(&block);
+        self.fees.add_block(&block, &undo);
+
+        Verdict::Accept
+    }
+
+    /// Validate `tx` and add it to the mempool
+    pub fn process_transaction(&mut self, tx: Transaction) -> Verdict {
+        // Ids key the mempool and relay, so they must match the contents
+        if tx.is_coinbase() || tx.id != tx.unsigned_hash() {
+            return Verdict::Reject;
+        }
+        if self.mempool.contains(&tx.id) || self.mempool.conflicts(&tx) {
+            return Verdict::Ignore;
+        }
+
+        // Unknown inputs may just mean we have not seen the parent yet
+        for input in &tx.inputs {
+            match self.state.utxo_set.get_utxo(&(input.txid.clone(), input.index)) {
+                Ok(Some(_)) => {}
+                _ => return Verdict::Ignore,
+            }
+        }
+
+        if !self.state.validate_transaction(&tx) {
+            return Verdict::Reject;
+        }
+
+        self.mempool.insert(tx);
+        Verdict::Accept
+    }
+}
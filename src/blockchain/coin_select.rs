@@ -0,0 +1,247 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use super::transaction::TxOutput;
+use super::utxo::OutPoint;
+
+/// Search steps after which branch-and-bound gives up
+pub const DEFAULT_MAX_TRIES: usize = 100_000;
+
+/// Inputs chosen to fund a transaction
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    pub inputs: Vec<(OutPoint, TxOutput)>,
+    pub total: u64,
+}
+
+impl Selection {
+    fn from_indexes(candidates: &[(OutPoint, TxOutput)], indexes: &[usize]) -> Self {
+        let inputs: Vec<_> = indexes.iter().map(|&i| candidates[i].clone()).collect();
+        let total = saturating_sum(inputs.iter().map(|(_, output)| output.value));
+        Selection { inputs, total }
+    }
+
+    /// What is left over after paying `target`
+    pub fn change(&self, target: u64) -> u64 {
+        self.total.saturating_sub(target)
+    }
+}
+
+/// A strategy for picking which outputs to spend.
+///
+/// Implementations only depend on the candidates themselves, never on the
+/// order they are passed in, so the same inputs always give the same
+/// selection.
+pub trait CoinSelector {
+    /// Choose candidates worth at least `target`; `None` if all of them
+    /// together are not enough
+    fn select(&self, candidates: &[(OutPoint, TxOutput)], target: u64) -> Option<Selection>;
+}
+
+// Totals above `u64::MAX` cannot be spent anyway; they only need to compare
+// as at least any target
+fn saturating_sum(values: impl IntoIterator<Item = u64>) -> u64 {
+    values.into_iter().fold(0, u64::saturating_add)
+}
+
+// Candidate indexes by value, largest first; ties broken by outpoint
+fn by_value_desc(candidates: &[(OutPoint, TxOutput)]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&candidates[a], &candidates[b]);
+        b.1.value.cmp(&a.1.value).then_with(|| a.0.cmp(&b.0))
+    });
+    order
+}
+
+fn take_until(candidates: &[(OutPoint, TxOutput)], order: &[usize], target: u64) -> Option<Selection> {
+    let mut total = 0u64;
+    let mut taken = Vec::new();
+    for &i in order {
+        if total >= target {
+            break;
+        }
+        total = total.saturating_add(candidates[i].1.value);
+        taken.push(i);
+    }
+    (total >= target).then(|| Selection::from_indexes(candidates, &taken))
+}
+
+/// Spend the biggest outputs first: the fewest inputs, the most change
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LargestFirst;
+
+impl CoinSelector for LargestFirst {
+    fn select(&self, candidates: &[(OutPoint, TxOutput)], target: u64) -> Option<Selection> {
+        take_until(candidates, &by_value_desc(candidates), target)
+    }
+}
+
+/// Spend the smallest outputs first, consolidating dust at the cost of
+/// larger transactions
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmallestFirst;
+
+impl CoinSelector for SmallestFirst {
+    fn select(&self, candidates: &[(OutPoint, TxOutput)], target: u64) -> Option<Selection> {
+        let mut order: Vec<usize> = (0..candidates.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (&candidates[a], &candidates[b]);
+            a.1.value.cmp(&b.1.value).then_with(|| a.0.cmp(&b.0))
+        });
+        take_until(candidates, &order, target)
+    }
+}
+
+/// Look for a combination that needs no change output: its total is at
+/// least the target and at most `cost_of_change` above it (the excess goes
+/// to the fee). Among those, the one with the least excess wins.
+///
+/// Falls back to `LargestFirst` when no such combination is found within
+/// `max_tries` search steps.
+#[derive(Debug, Clone, Copy)]
+pub struct BranchAndBound {
+    pub cost_of_change: u64,
+    pub max_tries: usize,
+}
+
+impl BranchAndBound {
+    pub fn new(cost_of_change: u64) -> Self {
+        BranchAndBound { cost_of_change, max_tries: DEFAULT_MAX_TRIES }
+    }
+
+    /// The changeless combination, if there is one
+    pub fn select_changeless(&self, candidates: &[(OutPoint, TxOutput)], target: u64) -> Option<Selection> {
+        let order = by_value_desc(candidates);
+        let values: Vec<u64> = order.iter().map(|&i| candidates[i].1.value).collect();
+
+        // What the values from each position on add up to
+        let mut remaining = vec![0u64; values.len() + 1];
+        for i in (0..values.len()).rev() {
+            remaining[i] = remaining[i + 1].saturating_add(values[i]);
+        }
+
+        let mut search = Search {
+            values: &values,
+            remaining: &remaining,
+            target,
+            upper: target.saturating_add(self.cost_of_change),
+            tries: self.max_tries,
+            selected: Vec::new(),
+            best: None,
+        };
+        search.run(0, 0);
+
+        let (_, best) = search.best?;
+        let indexes: Vec<usize> = best.into_iter().map(|i| order[i]).collect();
+        Some(Selection::from_indexes(candidates, &indexes))
+    }
+}
+
+impl Default for BranchAndBound {
+    fn default() -> Self {
+        BranchAndBound::new(0)
+    }
+}
+
+impl CoinSelector for BranchAndBound {
+    fn select(&self, candidates: &[(OutPoint, TxOutput)], target: u64) -> Option<Selection> {
+        self.select_changeless(candidates, target)
+            .or_else(|| LargestFirst.select(candidates, target))
+    }
+}
+
+// Depth-first search over include/exclude of each value, largest first
+struct Search<'a> {
+    values: &'a [u64],
+    /// Sum of `values[i..]` at `i`, saturating
+    remaining: &'a [u64],
+    target: u64,
+    upper: u64,
+    tries: usize,
+    selected: Vec<usize>,
+    /// (excess over the target, positions in `values`)
+    best: Option<(u64, Vec<usize>)>,
+}
+
+impl Search<'_> {
+    fn run(&mut self, next: usize, current: u64) {
+        if self.tries == 0 || current > self.upper {
+            return;
+        }
+        self.tries -= 1;
+
+        if current >= self.target {
+            // Adding more would only raise the excess
+            let excess = current - self.target;
+            if self.best.as_ref().is_none_or(|(best, _)| excess < *best) {
+                self.best = Some((excess, self.selected.clone()));
+            }
+            return;
+        }
+        if next == self.values.len() || current.saturating_add(self.remaining[next]) < self.target {
+            return;
+        }
+
+        self.selected.push(next);
+        self.run(next + 1, current.saturating_add(self.values[next]));
+        self.selected.pop();
+
+        if !matches!(self.best, Some((0, _))) {
+            self.run(next + 1, current);
+        }
+    }
+}
+
+/// Cardano's random-improve: pick random outputs until the target is met,
+/// then keep adding random ones while that brings the total closer to twice
+/// the target (never above three times). The change ends up about the size
+/// of the payment, which keeps future selections easy.
+///
+/// The randomness comes from `seed`, so a given seed and set of candidates
+/// always give the same selection.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomImprove {
+    pub seed: u64,
+}
+
+impl RandomImprove {
+    pub fn new(seed: u64) -> Self {
+        RandomImprove { seed }
+    }
+}
+
+impl CoinSelector for RandomImprove {
+    fn select(&self, candidates: &[(OutPoint, TxOutput)], target: u64) -> Option<Selection> {
+        let mut order: Vec<usize> = (0..candidates.len()).collect();
+        order.sort_by(|&a, &b| candidates[a].0.cmp(&candidates[b].0));
+        order.shuffle(&mut StdRng::seed_from_u64(self.seed));
+
+        let mut taken = Vec::new();
+        let mut total = 0u64;
+        let mut rest = order.into_iter();
+        for i in rest.by_ref() {
+            taken.push(i);
+            total = total.saturating_add(candidates[i].1.value);
+            if total >= target {
+                break;
+            }
+        }
+        if total < target {
+            return None;
+        }
+
+        let ideal = target.saturating_mul(2);
+        let max = target.saturating_mul(3);
+        for i in rest {
+            let with = total.saturating_add(candidates[i].1.value);
+            if with <= max && ideal.abs_diff(with) < ideal.abs_diff(total) {
+                taken.push(i);
+                total = with;
+            }
+        }
+
+        Some(Selection::from_indexes(candidates, &taken))
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::block::Block;
+use super::transaction::TxOutput;
+use super::utxo::{BlockUndo, OutPoint};
+
+/// Lowest fee rate ever recommended, per byte
+pub const MIN_FEE_RATE: u64 = 1;
+
+/// Recent blocks the estimator learns from
+pub const DEFAULT_FEE_HISTORY: usize = 50;
+
+/// Share of recent windows in which an estimate must have been enough
+const SUCCESS_PERCENT: usize = 85;
+
+/// Fee rates (per byte) of a connected block's non-coinbase transactions.
+///
+/// `undo` is what `connect_block` returned for it; outputs created and
+/// spent within the block are taken from the block itself.
+pub fn block_fee_rates(block: &Block, undo: &BlockUndo) -> Vec<u64> {
+    let mut prevouts: HashMap<OutPoint, &TxOutput> =
+        undo.spent.iter().map(|(outpoint, output)| (outpoint.clone(), output)).collect();
+    let mut rates = Vec::new();
+
+    for tx in &block.transactions {
+        if !tx.is_coinbase() {
+            let input: u64 = tx
+                .inputs
+                .iter()
+                .filter_map(|input| prevouts.get(&(input.txid.clone(), input.index)))
+                .map(|output| output.value)
+                .sum();
+            let output: u64 = tx.outputs.iter().map(|output| output.value).sum();
+            rates.push(input.saturating_sub(output) / tx.size().max(1));
+        }
+        for (index, output) in tx.outputs.iter().enumerate() {
+            prevouts.insert((tx.id.clone(), index as u32), output);
+        }
+    }
+
+    rates
+}
+
+/// Recommends fee rates from what recently confirmed transactions paid.
+///
+/// Each block is summarised by the lowest rate it included; a block with no
+/// fee-paying transactions had room for anything and counts as
+/// `MIN_FEE_RATE`. To confirm within `n` blocks, a transaction must beat the
+/// cheapest of `n` consecutive blocks, so `estimate(n)` is the rate that
+/// would have done that in 85% of the recent windows of `n` blocks.
+pub struct FeeEstimator {
+    history: usize,
+    /// (height, lowest included rate), oldest first
+    blocks: VecDeque<(u64, u64)>,
+}
+
+impl Default for FeeEstimator {
+    fn default() -> Self {
+        FeeEstimator::new(DEFAULT_FEE_HISTORY)
+    }
+}
+
+impl FeeEstimator {
+    pub fn new(history: usize) -> Self {
+        FeeEstimator { history: history.max(1), blocks: VecDeque::new() }
+    }
+
+    /// Blocks currently taken into account
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Learn from a connected block and the undo data it produced
+    pub fn add_block(&mut self, block: &Block, undo: &BlockUndo) {
+        let lowest = block_fee_rates(block, undo).into_iter().min().unwrap_or(MIN_FEE_RATE);
+        self.add_rate(block.index, lowest);
+    }
+
+    /// Record the lowest rate included at `height` directly
+    pub fn add_rate(&mut self, height: u64, lowest: u64) {
+        // A block at an existing height replaced the old one
+        while matches!(self.blocks.back(), Some((last, _)) if *last >= height) {
+            self.blocks.pop_back();
+        }
+        self.blocks.push_back((height, lowest.max(MIN_FEE_RATE)));
+        if self.blocks.len() > self.history {
+            self.blocks.pop_front();
+        }
+    }
+
+    /// Forget the block at `height` after it was disconnected
+    pub fn remove_block(&mut self, height: u64) {
+        if matches!(self.blocks.back(), Some((last, _)) if *last == height) {
+            self.blocks.pop_back();
+        }
+    }
+
+    /// Recommended rate to confirm within `target` blocks; `None` until
+    /// enough blocks have been seen
+    pub fn estimate(&self, target: usize) -> Option<u64> {
+        let target = target.max(1);
+        if self.blocks.len() < target {
+            return None;
+        }
+
+        let lowest: Vec<u64> = self.blocks.iter().map(|(_, rate)| *rate).collect();
+        let mut windows: Vec<u64> = lowest
+            .windows(target)
+            .map(|window| *window.iter().min().expect("windows are never empty"))
+            .collect();
+        windows.sort_unstable();
+
+        let rank = (windows.len() * SUCCESS_PERCENT).div_ceil(100).max(1);
+        Some(windows[rank - 1])
+    }
+}
@@ -0,0 +1,211 @@
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha512;
+use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+pub use bip39::Mnemonic;
+
+use super::address::Address;
+use super::transaction::TxOutput;
+use super::utxo::OutPoint;
+use super::wallet::Wallet;
+use crate::storage::{StorageError, Store};
+
+/// Marks a child index as hardened; SLIP-0010 ed25519 only has those
+pub const HARDENED: u32 = 0x8000_0000;
+
+/// Coin type in our `m/44'/coin'/account'/0'/index'` paths
+pub const COIN_TYPE: u32 = 9333;
+
+/// Unused addresses in a row after which a rescan stops looking
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+#[derive(Debug, Error)]
+pub enum HdError {
+    #[error("invalid mnemonic: {0}")]
+    Mnemonic(#[from] bip39::Error),
+
+    #[error("unsupported mnemonic length: {0} words")]
+    WordCount(usize),
+
+    #[error("invalid derivation path: {0}")]
+    InvalidPath(String),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+/// A fresh random mnemonic of 12, 15, 18, 21 or 24 words
+pub fn generate_mnemonic(words: usize) -> Result<Mnemonic, HdError> {
+    if !matches!(words, 12 | 15 | 18 | 21 | 24) {
+        return Err(HdError::WordCount(words));
+    }
+
+    let mut entropy = Zeroizing::new([0u8; 32]);
+    let len = words / 3 * 4;
+    OsRng.fill_bytes(&mut entropy[..len]);
+    Ok(Mnemonic::from_entropy(&entropy[..len])?)
+}
+
+/// Check a mnemonic typed in by the user, including its checksum
+pub fn parse_mnemonic(phrase: &str) -> Result<Mnemonic, HdError> {
+    Ok(Mnemonic::parse(phrase)?)
+}
+
+/// A SLIP-0010 ed25519 private key with its chain code
+#[derive(Clone, PartialEq, Eq)]
+pub struct ExtendedKey {
+    key: [u8; 32],
+    chain_code: [u8; 32],
+}
+
+// I = HMAC-SHA512(key, data); the left half is the key, the right the chain code
+fn hmac_split(key: &[u8], parts: &[&[u8]]) -> ExtendedKey {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC takes keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    let mut out = mac.finalize().into_bytes();
+
+    let mut extended = ExtendedKey { key: [0u8; 32], chain_code: [0u8; 32] };
+    extended.key.copy_from_slice(&out[..32]);
+    extended.chain_code.copy_from_slice(&out[32..]);
+    out.zeroize();
+    extended
+}
+
+impl ExtendedKey {
+    /// The master key for a BIP39 seed
+    pub fn master(seed: &[u8]) -> Self {
+        hmac_split(b"ed25519 seed", &[seed])
+    }
+
+    /// Derive hardened child `index` (the hardened bit is added if missing)
+    pub fn child(&self, index: u32) -> Self {
+        let index = index | HARDENED;
+        hmac_split(&self.chain_code, &[&[0u8], &self.key, &index.to_be_bytes()])
+    }
+
+    /// Derive along a path like `m/44'/9333'/0'`; every step must be hardened
+    pub fn derive_path(&self, path: &str) -> Result<Self, HdError> {
+        let invalid = || HdError::InvalidPath(path.to_string());
+        let mut steps = path.split('/');
+        if steps.next() != Some("m") {
+            return Err(invalid());
+        }
+
+        let mut key = self.clone();
+        for step in steps {
+            let index = step
+                .strip_suffix('\'')
+                .or_else(|| step.strip_suffix('h'))
+                .and_then(|n| n.parse::<u32>().ok())
+                .filter(|n| *n < HARDENED)
+                .ok_or_else(invalid)?;
+            key = key.child(index);
+        }
+        Ok(key)
+    }
+
+    pub fn secret(&self) -> [u8; 32] {
+        self.key
+    }
+
+    pub fn chain_code(&self) -> [u8; 32] {
+        self.chain_code
+    }
+
+    pub fn to_wallet(&self) -> Wallet {
+        Wallet::from_secret(self.key)
+    }
+}
+
+impl Drop for ExtendedKey {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        self.chain_code.zeroize();
+    }
+}
+
+// Never print the key
+impl std::fmt::Debug for ExtendedKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ExtendedKey(..)")
+    }
+}
+
+/// Outputs found by `HdWallet::rescan`
+#[derive(Debug, Clone, Default)]
+pub struct ScanResult {
+    /// Indexes of the addresses holding unspent outputs
+    pub used: Vec<u32>,
+    /// First index after the last used address; where new addresses go
+    pub next_index: u32,
+    /// Unspent outputs with the index of the address they pay
+    pub unspent: Vec<(u32, OutPoint, TxOutput)>,
+}
+
+impl ScanResult {
+    pub fn balance(&self) -> u64 {
+        self.unspent.iter().map(|(_, _, output)| output.value).sum()
+    }
+}
+
+/// Many addresses from one seed, so a single mnemonic backs up all of them.
+///
+/// Address `i` of an account uses the key at `m/44'/9333'/account'/0'/i'`.
+pub struct HdWallet {
+    account: ExtendedKey,
+}
+
+impl HdWallet {
+    /// Restore from a mnemonic and its optional passphrase ("" for none)
+    pub fn from_mnemonic(mnemonic: &Mnemonic, passphrase: &str, account: u32) -> Self {
+        let seed = Zeroizing::new(mnemonic.to_seed(passphrase));
+        HdWallet::from_seed(&seed[..], account)
+    }
+
+    pub fn from_seed(seed: &[u8], account: u32) -> Self {
+        let account = ExtendedKey::master(seed)
+            .child(44)
+            .child(COIN_TYPE)
+            .child(account)
+            .child(0);
+        HdWallet { account }
+    }
+
+    /// Key for address `index`
+    pub fn derive(&self, index: u32) -> Wallet {
+        self.account.child(index).to_wallet()
+    }
+
+    pub fn address(&self, index: u32) -> Address {
+        self.derive(index).address()
+    }
+
+    /// Find this wallet's unspent outputs in `store`, deriving addresses
+    /// until `gap_limit` in a row hold nothing.
+    ///
+    /// Only unspent outputs are visible, so an address whose outputs were
+    /// all spent counts as unused.
+    pub fn rescan<S: Store>(&self, store: &S, gap_limit: u32) -> Result<ScanResult, HdError> {
+        let mut result = ScanResult::default();
+        let mut index = 0;
+
+        while index < result.next_index.saturating_add(gap_limit) && index < HARDENED {
+            let found = store.find_unspent(&self.address(index))?;
+            if !found.is_empty() {
+                result.used.push(index);
+                result.next_index = index + 1;
+                result
+                    .unspent
+                    .extend(found.into_iter().map(|(outpoint, output)| (index, outpoint, output)));
+            }
+            index += 1;
+        }
+
+        Ok(result)
+    }
+}
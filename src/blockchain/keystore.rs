@@ -0,0 +1,263 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use super::address::Address;
+use super::wallet::Wallet;
+use crate::storage::write_private;
+
+/// Current keystore file format
+pub const KEYSTORE_VERSION: u32 = 2;
+
+const KDF_ALGORITHM: &str = "argon2id";
+const CIPHER_ALGORITHM: &str = "xchacha20poly1305";
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 24;
+
+// Refuse files that would make unlocking take unreasonable memory or time
+const MAX_MEMORY_KIB: u32 = 4 * 1024 * 1024;
+const MAX_ITERATIONS: u32 = 64;
+const MAX_PARALLELISM: u32 = 16;
+
+#[derive(Debug, Error)]
+pub enum KeystoreError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("malformed keystore: {0}")]
+    Malformed(String),
+
+    #[error("unsupported keystore version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("unsupported key derivation: {0}")]
+    UnsupportedKdf(String),
+
+    #[error("wrong passphrase or corrupted keystore")]
+    Decryption,
+}
+
+/// Argon2id cost settings, stored in the file so they can be raised later
+/// without breaking existing keystores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams { memory_kib: 64 * 1024, iterations: 3, parallelism: 1 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct KdfHeader {
+    algorithm: String,
+    #[serde(flatten)]
+    params: KdfParams,
+    salt: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CipherHeader {
+    algorithm: String,
+    nonce: String,
+}
+
+/// A wallet secret key encrypted with a passphrase.
+///
+/// The key is derived from the passphrase with Argon2id and the secret is
+/// sealed with XChaCha20-Poly1305. The file is JSON; everything except the
+/// ciphertext is readable without the passphrase, and all of it is
+/// authenticated:
+///
+/// ```text
+/// { "version": 2, "address": "…",
+///   "kdf": { "algorithm": "argon2id", "memory_kib": 65536, "iterations": 3, "parallelism": 1, "salt": "…" },
+///   "cipher": { "algorithm": "xchacha20poly1305", "nonce": "…" },
+///   "ciphertext": "…" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    /// Address of the wallet, so keystores can be told apart while locked
+    pub address: Address,
+    kdf: KdfHeader,
+    cipher: CipherHeader,
+    ciphertext: String,
+}
+
+#[derive(Deserialize)]
+struct Versioned {
+    version: u32,
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn decode_hex(field: &str, value: &str) -> Result<Vec<u8>, KeystoreError> {
+    hex::decode(value).map_err(|_| KeystoreError::Malformed(format!("{} is not hex", field)))
+}
+
+impl Keystore {
+    /// Encrypt `wallet` with the default key derivation cost
+    pub fn create(wallet: &Wallet, passphrase: &str) -> Result<Self, KeystoreError> {
+        Keystore::create_with(wallet, passphrase, KdfParams::default())
+    }
+
+    pub fn create_with(wallet: &Wallet, passphrase: &str, params: KdfParams) -> Result<Self, KeystoreError> {
+        let mut keystore = Keystore {
+            version: KEYSTORE_VERSION,
+            address: wallet.address(),
+            kdf: KdfHeader {
+                algorithm: KDF_ALGORITHM.into(),
+                params,
+                salt: hex::encode(random_bytes::<SALT_SIZE>()),
+            },
+            cipher: CipherHeader {
+                algorithm: CIPHER_ALGORITHM.into(),
+                nonce: hex::encode(random_bytes::<NONCE_SIZE>()),
+            },
+            ciphertext: String::new(),
+        };
+
+        let key = keystore.derive_key(passphrase)?;
+        let secret = Zeroizing::new(wallet.secret_bytes());
+        let payload = Payload { msg: &secret[..], aad: &keystore.associated_data() };
+        let ciphertext = cipher(&key)
+            .encrypt(&keystore.nonce()?, payload)
+            .map_err(|_| KeystoreError::Decryption)?;
+
+        keystore.ciphertext = hex::encode(ciphertext);
+        Ok(keystore)
+    }
+
+    /// Decrypt the wallet
+    pub fn unlock(&self, passphrase: &str) -> Result<Wallet, KeystoreError> {
+        self.check_header()?;
+
+        let key = self.derive_key(passphrase)?;
+        let nonce = self.nonce()?;
+        let ciphertext = decode_hex("ciphertext", &self.ciphertext)?;
+        let payload = Payload { msg: &ciphertext[..], aad: &self.associated_data() };
+
+        let secret = Zeroizing::new(
+            cipher(&key)
+                .decrypt(&nonce, payload)
+                .map_err(|_| KeystoreError::Decryption)?,
+        );
+        let secret: [u8; 32] = secret[..]
+            .try_into()
+            .map_err(|_| KeystoreError::Malformed("secret has the wrong length".into()))?;
+
+        let wallet = Wallet::from_secret(secret);
+        if wallet.address() != self.address {
+            return Err(KeystoreError::Decryption);
+        }
+        Ok(wallet)
+    }
+
+    /// Re-encrypt under a new passphrase, with a fresh salt and nonce
+    pub fn change_passphrase(&mut self, old: &str, new: &str) -> Result<(), KeystoreError> {
+        let wallet = self.unlock(old)?;
+        *self = Keystore::create_with(&wallet, new, self.kdf.params)?;
+        Ok(())
+    }
+
+    pub fn kdf_params(&self) -> KdfParams {
+        self.kdf.params
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, KeystoreError> {
+        let bytes = fs::read(path)?;
+
+        // Check the version first, so files in an older layout are reported
+        // as such rather than as malformed
+        let Versioned { version } =
+            serde_json::from_slice(&bytes).map_err(|e| KeystoreError::Malformed(e.to_string()))?;
+        if version != KEYSTORE_VERSION {
+            return Err(KeystoreError::UnsupportedVersion(version));
+        }
+
+        let keystore: Keystore =
+            serde_json::from_slice(&bytes).map_err(|e| KeystoreError::Malformed(e.to_string()))?;
+        keystore.check_header()?;
+        Ok(keystore)
+    }
+
+    /// Write the keystore to `path`, readable only by its owner
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), KeystoreError> {
+        let path = path.as_ref();
+        let bytes = serde_json::to_vec_pretty(self).map_err(|e| KeystoreError::Malformed(e.to_string()))?;
+
+        let tmp = path.with_extension("tmp");
+        write_private(&tmp, &bytes)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn check_header(&self) -> Result<(), KeystoreError> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(KeystoreError::UnsupportedVersion(self.version));
+        }
+        if self.kdf.algorithm != KDF_ALGORITHM {
+            return Err(KeystoreError::UnsupportedKdf(self.kdf.algorithm.clone()));
+        }
+        if self.cipher.algorithm != CIPHER_ALGORITHM {
+            return Err(KeystoreError::Malformed(format!("unknown cipher {}", self.cipher.algorithm)));
+        }
+
+        let KdfParams { memory_kib, iterations, parallelism } = self.kdf.params;
+        if memory_kib > MAX_MEMORY_KIB || iterations > MAX_ITERATIONS || parallelism > MAX_PARALLELISM {
+            return Err(KeystoreError::UnsupportedKdf(format!(
+                "cost too high (m={}, t={}, p={})",
+                memory_kib, iterations, parallelism
+            )));
+        }
+        Ok(())
+    }
+
+    fn nonce(&self) -> Result<XNonce, KeystoreError> {
+        let nonce: [u8; NONCE_SIZE] = decode_hex("nonce", &self.cipher.nonce)?
+            .try_into()
+            .map_err(|_| KeystoreError::Malformed("nonce has the wrong length".into()))?;
+        Ok(XNonce::from(nonce))
+    }
+
+    fn derive_key(&self, passphrase: &str) -> Result<Zeroizing<[u8; 32]>, KeystoreError> {
+        let KdfParams { memory_kib, iterations, parallelism } = self.kdf.params;
+        let params = Params::new(memory_kib, iterations, parallelism, Some(32))
+            .map_err(|e| KeystoreError::UnsupportedKdf(e.to_string()))?;
+        let salt = decode_hex("salt", &self.kdf.salt)?;
+
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key[..])
+            .map_err(|e| KeystoreError::UnsupportedKdf(e.to_string()))?;
+        Ok(key)
+    }
+
+    // Binds the readable header to the ciphertext, so it cannot be edited
+    fn associated_data(&self) -> Vec<u8> {
+        serde_json::to_vec(&(self.version, &self.address, &self.kdf, &self.cipher.algorithm))
+            .expect("keystore headers always serialize")
+    }
+}
+
+fn cipher(key: &[u8; 32]) -> XChaCha20Poly1305 {
+    XChaCha20Poly1305::new(key.into())
+}
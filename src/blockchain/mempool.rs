@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use super::block::Block;
+use super::transaction::Transaction;
+use super::utxo::OutPoint;
+use crate::crypto::hash::Hash;
+
+/// Validated transactions waiting to be included in a block.
+///
+/// Tracks which outpoints pool transactions spend so conflicting
+/// transactions are rejected instead of both being kept.
+#[derive(Default)]
+pub struct Mempool {
+    txs: HashMap<Hash, Transaction>,
+    spent: HashMap<OutPoint, Hash>,
+}
+
+impl Mempool {
+    pub fn contains(&self, txid: &Hash) -> bool {
+        self.txs.contains_key(txid)
+    }
+
+    pub fn get(&self, txid: &Hash) -> Option<&Transaction> {
+        self.txs.get(txid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.txs.values()
+    }
+
+    /// Whether `tx` spends an output already spent by a pool transaction
+    pub fn conflicts(&self, tx: &Transaction) -> bool {
+        tx.inputs
+            .iter()
+            .any(|input| self.spent.contains_key(&(input.txid.clone(), input.index)))
+    }
+
+    /// Add an already validated, non-conflicting transaction
+    pub fn insert(&mut self, tx: Transaction) {
+        for input in &tx.inputs {
+            self.spent.insert((input.txid.clone(), input.index), tx.id.clone());
+        }
+        self.txs.insert(tx.id.clone(), tx);
+    }
+
+    pub fn remove(&mut self, txid: &Hash) -> Option<Transaction> {
+        let tx = self.txs.remove(txid)?;
+        for input in &tx.inputs {
+            self.spent.remove(&(input.txid.clone(), input.index));
+        }
+        Some(tx)
+    }
+
+    /// Drop transactions included in `block` and any that conflict with it
+    pub fn remove_for_block(&mut self, block: &Block) {
+        for tx in &block.transactions {
+            self.remove(&tx.id);
+
+            for input in &tx.inputs {
+                if let Some(txid) = self.spent.get(&(input.txid.clone(), input.index)).cloned() {
+                    self.remove(&txid);
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,15 @@
+pub mod address;
 pub mod block;
+pub mod chain;
+pub mod coin_select;
+pub mod fees;
+pub mod hd;
+pub mod keystore;
+pub mod mempool;
+pub mod multiwallet;
+pub mod params;
+pub mod psbt;
+pub mod signer;
 pub mod state;
 pub mod transaction;
 pub mod tx_builder;
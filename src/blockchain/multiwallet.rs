@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+
+use super::address::Address;
+use super::block::Block;
+use super::hd::HdWallet;
+use super::signer::Signer;
+use super::transaction::{Transaction, TxOutput};
+use super::utxo::OutPoint;
+use super::wallet::{address_from_public_key_bytes, Wallet};
+use crate::crypto::hash::Hash;
+
+/// An output paying one of the wallet's addresses
+#[derive(Debug, Clone)]
+pub struct WalletUtxo {
+    pub output: TxOutput,
+    /// Height of the block that created it; `None` while unconfirmed
+    pub height: Option<u64>,
+}
+
+/// What one transaction did to the wallet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub txid: Hash,
+    /// `None` while unconfirmed
+    pub height: Option<u64>,
+    /// Paid to our addresses
+    pub received: u64,
+    /// Spent from our outputs
+    pub sent: u64,
+}
+
+/// Confirmed funds exclude outputs spent by our own unconfirmed
+/// transactions; pending funds are unconfirmed outputs paying us (incoming
+/// payments and our own change).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Balance {
+    pub confirmed: u64,
+    pub pending: u64,
+}
+
+impl Balance {
+    pub fn total(&self) -> u64 {
+        self.confirmed + self.pending
+    }
+}
+
+// A key we can sign with, here or through another signer, or only watch
+enum Key {
+    Full(Wallet),
+    External(Box<dyn Signer>),
+    WatchOnly(Vec<u8>),
+}
+
+// Outputs of ours spent by a connected block, to restore on disconnect
+struct BlockUndo {
+    spent: Vec<(OutPoint, WalletUtxo)>,
+}
+
+/// A wallet holding many keys that follows the chain itself.
+///
+/// Feed it every connected and disconnected block (and our own transactions
+/// once broadcast, via `add_pending`); it keeps the unspent outputs of its
+/// addresses and a per-transaction history, so balances never need a
+/// per-address query against the UTXO set.
+///
+/// Keys can also be added by public key alone. Such watch-only addresses
+/// are tracked like any other, but spending from them needs an unsigned
+/// transaction (`TxBuilder::build_wallet_psbt`) signed elsewhere.
+#[derive(Default)]
+pub struct MultiWallet {
+    keys: BTreeMap<Address, Key>,
+    utxos: BTreeMap<OutPoint, WalletUtxo>,
+    /// Unconfirmed transactions touching the wallet
+    pending: BTreeMap<Hash, Transaction>,
+    /// Outputs spent by `pending` transactions
+    pending_spent: BTreeMap<OutPoint, Hash>,
+    history: Vec<HistoryEntry>,
+    undo: BTreeMap<Hash, BlockUndo>,
+}
+
+impl MultiWallet {
+    pub fn new() -> Self {
+        MultiWallet::default()
+    }
+
+    /// Add a key; returns its address
+    pub fn add_key(&mut self, wallet: Wallet) -> Address {
+        let address = wallet.address();
+        self.keys.insert(address, Key::Full(wallet));
+        address
+    }
+
+    /// Add a key held by an external signer; returns its address, or `None`
+    /// if the signer's public key is malformed
+    pub fn add_signer(&mut self, signer: Box<dyn Signer>) -> Option<Address> {
+        let address = signer.address()?;
+        self.keys.insert(address, Key::External(signer));
+        Some(address)
+    }
+
+    /// Watch the address of `public_key` without its secret key; returns
+    /// the address, or `None` if the key is malformed. A key we already
+    /// hold in full stays that way.
+    pub fn add_public_key(&mut self, public_key: &[u8]) -> Option<Address> {
+        let address = address_from_public_key_bytes(public_key)?;
+        self.keys
+            .entry(address)
+            .or_insert_with(|| Key::WatchOnly(public_key.to_vec()));
+        Some(address)
+    }
+
+    /// A wallet watching the addresses of `public_keys`; `None` if any of
+    /// them is malformed
+    pub fn watch_only<'a>(public_keys: impl IntoIterator<Item = &'a [u8]>) -> Option<Self> {
+        let mut wallet = MultiWallet::new();
+        for public_key in public_keys {
+            wallet.add_public_key(public_key)?;
+        }
+        Some(wallet)
+    }
+
+    /// Add a fresh random key
+    pub fn generate_key(&mut self) -> Address {
+        self.add_key(Wallet::new())
+    }
+
+    /// Add the keys for addresses `start..end` of an HD wallet
+    pub fn add_hd_keys(&mut self, hd: &HdWallet, range: std::ops::Range<u32>) {
+        for index in range {
+            self.add_key(hd.derive(index));
+        }
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = &Address> {
+        self.keys.keys()
+    }
+
+    pub fn is_mine(&self, address: &Address) -> bool {
+        self.keys.contains_key(address)
+    }
+
+    /// Signer for one of our addresses; `None` if watch-only
+    pub fn key(&self, address: &Address) -> Option<&dyn Signer> {
+        match self.keys.get(address)? {
+            Key::Full(wallet) => Some(wallet),
+            Key::External(signer) => Some(signer.as_ref()),
+            Key::WatchOnly(_) => None,
+        }
+    }
+
+    pub fn public_key(&self, address: &Address) -> Option<Vec<u8>> {
+        match self.keys.get(address)? {
+            Key::Full(wallet) => Some(wallet.public_key_bytes()),
+            Key::External(signer) => Some(signer.public_key()),
+            Key::WatchOnly(public_key) => Some(public_key.clone()),
+        }
+    }
+
+    pub fn is_watch_only(&self, address: &Address) -> bool {
+        matches!(self.keys.get(address), Some(Key::WatchOnly(_)))
+    }
+
+    pub fn utxo(&self, outpoint: &OutPoint) -> Option<&WalletUtxo> {
+        self.utxos.get(outpoint)
+    }
+
+    pub fn balance(&self) -> Balance {
+        let mut balance = Balance::default();
+        for (outpoint, utxo) in &self.utxos {
+            // Spent by one of our unconfirmed transactions, whose outputs
+            // count instead
+            if self.pending_spent.contains_key(outpoint) {
+                continue;
+            }
+            match utxo.height {
+                Some(_) => balance.confirmed += utxo.output.value,
+                None => balance.pending += utxo.output.value,
+            }
+        }
+        balance
+    }
+
+    /// Confirmed outputs not already spent by one of our unconfirmed
+    /// transactions, in outpoint order
+    pub fn spendable(&self) -> Vec<(OutPoint, TxOutput)> {
+        self.utxos
+            .iter()
+            .filter(|(outpoint, utxo)| utxo.height.is_some() && !self.pending_spent.contains_key(*outpoint))
+            .map(|(outpoint, utxo)| (outpoint.clone(), utxo.output.clone()))
+            .collect()
+    }
+
+    /// Transactions that touched the wallet, oldest first
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
+    pub fn is_pending(&self, txid: &Hash) -> bool {
+        self.pending.contains_key(txid)
+    }
+
+    /// Record a transaction of ours (or paying us) that is not in a block
+    /// yet. Its inputs stop being spendable; its outputs to us count as
+    /// pending. Returns false if it does not concern the wallet.
+    pub fn add_pending(&mut self, tx: Transaction) -> bool {
+        if self.pending.contains_key(&tx.id) {
+            return true;
+        }
+        let (received, sent) = self.amounts(&tx);
+        if received == 0 && sent == 0 {
+            return false;
+        }
+
+        for input in &tx.inputs {
+            self.pending_spent.insert((input.txid.clone(), input.index), tx.id.clone());
+        }
+        for (index, output) in tx.outputs.iter().enumerate() {
+            if self.is_mine(&output.address) {
+                let utxo = WalletUtxo { output: output.clone(), height: None };
+                self.utxos.insert((tx.id.clone(), index as u32), utxo);
+            }
+        }
+
+        self.history.push(HistoryEntry { txid: tx.id.clone(), height: None, received, sent });
+        self.pending.insert(tx.id.clone(), tx);
+        true
+    }
+
+    /// Forget an unconfirmed transaction that will not confirm (e.g. it
+    /// was evicted or conflicted), along with its unconfirmed descendants
+    pub fn abandon(&mut self, txid: &Hash) {
+        let tx = match self.pending.remove(txid) {
+            Some(tx) => tx,
+            None => return,
+        };
+        self.pending_spent.retain(|_, spender| spender != txid);
+        for index in 0..tx.outputs.len() {
+            self.utxos.remove(&(txid.clone(), index as u32));
+        }
+        self.history.retain(|entry| entry.txid != *txid || entry.height.is_some());
+
+        // Transactions spending its outputs cannot confirm either
+        let children: Vec<Hash> = self
+            .pending_spent
+            .iter()
+            .filter(|((parent, _), _)| parent == txid)
+            .map(|(_, child)| child.clone())
+            .collect();
+        for child in children {
+            self.abandon(&child);
+        }
+    }
+
+    pub fn connect_block(&mut self, block: &Block) {
+        let mut undo = BlockUndo { spent: Vec::new() };
+
+        for tx in &block.transactions {
+            let (received, sent) = self.amounts(tx);
+
+            // A pending transaction of ours is now confirmed; conflicting
+            // ones never will be
+            self.pending.remove(&tx.id);
+            for input in &tx.inputs {
+                let outpoint = (input.txid.clone(), input.index);
+                if let Some(spender) = self.pending_spent.get(&outpoint).cloned() {
+                    if spender != tx.id {
+                        self.abandon(&spender);
+                    }
+                }
+                self.pending_spent.remove(&outpoint);
+                if let Some(utxo) = self.utxos.remove(&outpoint) {
+                    undo.spent.push((outpoint, utxo));
+                }
+            }
+
+            for (index, output) in tx.outputs.iter().enumerate() {
+                if self.is_mine(&output.address) {
+                    let utxo = WalletUtxo { output: output.clone(), height: Some(block.index) };
+                    self.utxos.insert((tx.id.clone(), index as u32), utxo);
+                }
+            }
+
+            if received > 0 || sent > 0 {
+                match self.history.iter_mut().find(|entry| entry.txid == tx.id) {
+                    Some(entry) => entry.height = Some(block.index),
+                    None => self.history.push(HistoryEntry {
+                        txid: tx.id.clone(),
+                        height: Some(block.index),
+                        received,
+                        sent,
+                    }),
+                }
+            }
+        }
+
+        self.undo.insert(block.hash.clone(), undo);
+    }
+
+    /// Undo `connect_block` for the current tip. Its transactions that
+    /// concern us become pending again (the coinbase is dropped).
+    pub fn disconnect_block(&mut self, block: &Block) {
+        let undo = self.undo.remove(&block.hash).unwrap_or(BlockUndo { spent: Vec::new() });
+
+        for tx in block.transactions.iter().rev() {
+            for index in 0..tx.outputs.len() {
+                self.utxos.remove(&(tx.id.clone(), index as u32));
+            }
+            self.history.retain(|entry| entry.txid != tx.id);
+        }
+        for (outpoint, utxo) in undo.spent {
+            self.utxos.insert(outpoint, utxo);
+        }
+
+        for tx in block.transactions.iter().filter(|tx| !tx.is_coinbase()) {
+            self.add_pending(tx.clone());
+        }
+    }
+
+    // Value paid to us and value spent from our outputs by `tx`
+    fn amounts(&self, tx: &Transaction) -> (u64, u64) {
+        let received = tx
+            .outputs
+            .iter()
+            .filter(|output| self.is_mine(&output.address))
+            .map(|output| output.value)
+            .sum();
+        let sent = tx
+            .inputs
+            .iter()
+            .filter_map(|input| self.utxos.get(&(input.txid.clone(), input.index)))
+            .map(|utxo| utxo.output.value)
+            .sum();
+        (received, sent)
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use super::address::{Address, AddressError, ADDRESS_HASH_SIZE, ADDRESS_VERSION};
+use super::block::Block;
+use super::transaction::{Transaction, TxOutput};
+use crate::crypto::hash::Hash;
+use crate::storage::{StorageError, Store};
+
+#[derive(Debug, Error)]
+pub enum NetworkError {
+    #[error("unknown network: {0}")]
+    UnknownNetwork(String),
+
+    #[error("address is for another network: {0}")]
+    WrongNetwork(String),
+
+    #[error("invalid address: {0}")]
+    InvalidAddress(String),
+
+    #[error("chain data belongs to another network (genesis {0:?})")]
+    GenesisMismatch(Hash),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+/// The chains a node can run on. Nodes only talk to peers on the same one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Testnet,
+    /// Local test chains, e.g. for integration tests
+    Regtest,
+}
+
+impl Network {
+    pub fn params(self) -> &'static NetworkParams {
+        match self {
+            Network::Mainnet => &MAINNET,
+            Network::Testnet => &TESTNET,
+            Network::Regtest => &REGTEST,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        self.params().name
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Network {
+    type Err = NetworkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" | "main" => Ok(Network::Mainnet),
+            "testnet" | "test" => Ok(Network::Testnet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(NetworkError::UnknownNetwork(s.to_string())),
+        }
+    }
+}
+
+/// Constants that tell one network apart from another
+#[derive(Debug, PartialEq, Eq)]
+pub struct NetworkParams {
+    pub network: Network,
+    pub name: &'static str,
+    /// Prefixed to every sync protocol frame
+    pub magic: [u8; 4],
+    /// Port listened on unless configured otherwise
+    pub default_port: u16,
+    /// Human-readable prefix of addresses on this network
+    pub address_prefix: &'static str,
+    // Fixes the genesis block (and so its hash)
+    genesis_timestamp: u128,
+}
+
+pub const MAINNET: NetworkParams = NetworkParams {
+    network: Network::Mainnet,
+    name: "mainnet",
+    magic: [0xa7, 0xc0, 0x4e, 0x01],
+    default_port: 7333,
+    address_prefix: "arc",
+    genesis_timestamp: 1_700_000_000_000,
+};
+
+pub const TESTNET: NetworkParams = NetworkParams {
+    network: Network::Testnet,
+    name: "testnet",
+    magic: [0xa7, 0xc0, 0x4e, 0x02],
+    default_port: 17333,
+    address_prefix: "tarc",
+    genesis_timestamp: 1_700_000_000_001,
+};
+
+pub const REGTEST: NetworkParams = NetworkParams {
+    network: Network::Regtest,
+    name: "regtest",
+    magic: [0xa7, 0xc0, 0x4e, 0x03],
+    default_port: 27333,
+    address_prefix: "rarc",
+    genesis_timestamp: 0,
+};
+
+// Genesis pays nothing to an address no key hashes to
+const GENESIS_ADDRESS: Address = Address::new(ADDRESS_VERSION, [0u8; ADDRESS_HASH_SIZE]);
+
+impl NetworkParams {
+    /// The first block of this network's chain, the same on every node.
+    ///
+    /// It is built and hashed like any other block; the timestamp and a
+    /// nonce taken from the magic tell the networks' genesis blocks apart.
+    pub fn genesis_block(&self) -> Block {
+        let coinbase = Transaction::coinbase(0, vec![TxOutput { value: 0, address: GENESIS_ADDRESS }]);
+        let mut block = Block {
+            index: 0,
+            prev_hash: Hash([0u8; 32]),
+            timestamp: self.genesis_timestamp,
+            transactions: vec![coinbase],
+            nonce: u32::from_be_bytes(self.magic).into(),
+            hash: Hash([0u8; 32]),
+        };
+        block.hash = block.calculate_hash();
+        block
+    }
+
+    pub fn genesis_hash(&self) -> Hash {
+        self.genesis_block().hash
+    }
+
+    /// Start an empty store at this network's genesis block, or check that a
+    /// store already holding a chain is on this network.
+    ///
+    /// Stores restored from a snapshot may lack the genesis header; those
+    /// are trusted.
+    pub fn init_store<S: Store>(&self, store: &mut S) -> Result<(), NetworkError> {
+        if store.tip()?.is_none() {
+            store.connect_block(&self.genesis_block())?;
+            return Ok(());
+        }
+
+        match store.get_header_at_height(0)? {
+            Some(header) if header.hash != self.genesis_hash() => {
+                Err(NetworkError::GenesisMismatch(header.hash))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Identify protocol version; peers announcing another one are on
+    /// another network
+    pub fn identify_protocol(&self) -> String {
+        format!("/arcnova/{}/1.0.0", self.name)
+    }
+
+    pub fn sync_protocol(&self) -> String {
+        format!("/arcnova/{}/sync/1.0.0", self.name)
+    }
+
+    /// Gossip topic for one of the base topic names, e.g. `arcnova-blocks/testnet`
+    pub fn topic(&self, base: &str) -> String {
+        format!("{}/{}", base, self.name)
+    }
+
+    /// Show an address Bech32m-encoded with this network's prefix
+    pub fn encode_address(&self, address: &Address) -> String {
+        address.encode(self)
+    }
+
+    /// Parse an address shown by `encode_address`, refusing other networks'
+    pub fn decode_address(&self, encoded: &str) -> Result<Address, NetworkError> {
+        Address::decode(encoded, self).map_err(|e| match e {
+            AddressError::WrongPrefix { .. } => NetworkError::WrongNetwork(encoded.to_string()),
+            _ => NetworkError::InvalidAddress(encoded.to_string()),
+        })
+    }
+}
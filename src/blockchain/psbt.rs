@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::hd::HdWallet;
+use super::signer::{Signer, SignerError};
+use super::transaction::{Transaction, TxInput, TxOutput};
+use super::utxo::OutPoint;
+use super::wallet::address_from_public_key_bytes;
+use crate::crypto::hash::Hash;
+use crate::crypto::keys::verify_signature;
+use crate::encoding::{decode, DecodeError};
+use crate::storage::{StorageError, Store};
+
+/// Current partially signed transaction format
+pub const PSBT_VERSION: u32 = 1;
+
+/// Largest encoded PSBT `Psbt::from_bytes` accepts
+pub const MAX_PSBT_SIZE: u64 = 2 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum PsbtError {
+    #[error("no input {0}")]
+    NoInput(usize),
+
+    #[error("input {0} has no previous output")]
+    MissingPreviousOutput(usize),
+
+    #[error("input {0} has no public key")]
+    MissingPublicKey(usize),
+
+    #[error("public key of input {0} does not match the output it spends")]
+    KeyMismatch(usize),
+
+    #[error("input {0} is not signed")]
+    NotSigned(usize),
+
+    #[error("invalid signature on input {0}")]
+    InvalidSignature(usize),
+
+    #[error("input {0} is already signed; its metadata cannot change")]
+    AlreadySigned(usize),
+
+    #[error("conflicting data for input {0}")]
+    Conflict(usize),
+
+    #[error("not the same transaction")]
+    DifferentTransaction,
+
+    #[error("{inputs} inputs for {outpoints} outpoints")]
+    InputCountMismatch { outpoints: usize, inputs: usize },
+
+    #[error("unsupported version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error(transparent)]
+    Malformed(#[from] DecodeError),
+
+    #[error(transparent)]
+    Signer(#[from] SignerError),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+/// What a signer needs to know about one input
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PsbtInput {
+    /// The output being spent: its value and the address the key must match
+    pub previous_output: Option<TxOutput>,
+    pub public_key: Option<Vec<u8>>,
+    /// Address index of the key when it comes from an HD wallet
+    pub hd_index: Option<u32>,
+    pub signature: Option<Vec<u8>>,
+}
+
+/// A transaction on its way from a coordinator, which knows what to spend,
+/// to the signers that hold the keys.
+///
+/// `create` fixes the inputs and outputs, `update` adds what signers need
+/// (previous outputs and public keys), `sign` adds signatures, `combine`
+/// merges copies signed in different places and `finalize` produces the
+/// transaction. The whole container serializes, so each step can run on a
+/// different machine.
+///
+/// The transaction id covers every input's public key, so signing can only
+/// start once all public keys are known.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Psbt {
+    pub version: u32,
+    /// Which outputs are spent, in input order
+    pub outpoints: Vec<OutPoint>,
+    pub outputs: Vec<TxOutput>,
+    pub inputs: Vec<PsbtInput>,
+}
+
+impl Psbt {
+    pub fn create(outpoints: Vec<OutPoint>, outputs: Vec<TxOutput>) -> Self {
+        let inputs = vec![PsbtInput::default(); outpoints.len()];
+        Psbt { version: PSBT_VERSION, outpoints, outputs, inputs }
+    }
+
+    /// Decode a PSBT from an untrusted source, such as a file from
+    /// another machine
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PsbtError> {
+        let psbt: Psbt = decode(bytes, MAX_PSBT_SIZE)?;
+        if psbt.version != PSBT_VERSION {
+            return Err(PsbtError::UnsupportedVersion(psbt.version));
+        }
+        psbt.check_inputs()?;
+        Ok(psbt)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("PSBTs always serialize")
+    }
+
+    /// Fill in missing previous outputs from the UTXO set
+    pub fn update<S: Store>(&mut self, store: &S) -> Result<(), PsbtError> {
+        self.check_inputs()?;
+        for (outpoint, input) in self.outpoints.iter().zip(&mut self.inputs) {
+            if input.previous_output.is_none() {
+                input.previous_output = store.get_utxo(outpoint)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the public key (and HD index, if any) that will sign input
+    /// `index`; it must match the address of the output being spent
+    pub fn set_key(&mut self, index: usize, public_key: &[u8], hd_index: Option<u32>) -> Result<(), PsbtError> {
+        let input = self.inputs.get_mut(index).ok_or(PsbtError::NoInput(index))?;
+        let previous = input.previous_output.as_ref().ok_or(PsbtError::MissingPreviousOutput(index))?;
+        if address_from_public_key_bytes(public_key) != Some(previous.address) {
+            return Err(PsbtError::KeyMismatch(index));
+        }
+        if input.signature.is_some() && input.public_key.as_deref() != Some(public_key) {
+            return Err(PsbtError::AlreadySigned(index));
+        }
+
+        input.public_key = Some(public_key.to_vec());
+        input.hd_index = hd_index.or(input.hd_index);
+        Ok(())
+    }
+
+    /// Sum of the previous outputs minus the outputs, once all are known
+    pub fn fee(&self) -> Option<u64> {
+        let mut input = 0u64;
+        for psbt_input in &self.inputs {
+            input = input.checked_add(psbt_input.previous_output.as_ref()?.value)?;
+        }
+        let output = self
+            .outputs
+            .iter()
+            .try_fold(0u64, |sum, output| sum.checked_add(output.value))?;
+        input.checked_sub(output)
+    }
+
+    /// The transaction id every input signs
+    pub fn signing_digest(&self) -> Result<Hash, PsbtError> {
+        Ok(self.unsigned_tx()?.unsigned_hash())
+    }
+
+    /// Sign every input whose key is `signer`'s; returns how many were signed
+    pub fn sign(&mut self, signer: &dyn Signer) -> Result<usize, PsbtError> {
+        let public_key = signer.public_key();
+        let digest = self.signing_digest()?;
+
+        let mut signature = None;
+        let mut signed = 0;
+        for input in &mut self.inputs {
+            if input.public_key.as_deref() == Some(&public_key[..]) {
+                // Every input signs the same digest, so ask the signer once
+                if signature.is_none() {
+                    signature = Some(signer.sign_digest(&digest)?);
+                }
+                input.signature = signature.clone();
+                signed += 1;
+            }
+        }
+        Ok(signed)
+    }
+
+    /// Sign every input with an HD index using that key of `hd`
+    pub fn sign_hd(&mut self, hd: &HdWallet) -> Result<usize, PsbtError> {
+        let digest = self.signing_digest()?;
+
+        let mut signed = 0;
+        for input in &mut self.inputs {
+            let key = match input.hd_index {
+                Some(index) => hd.derive(index),
+                None => continue,
+            };
+            if input.public_key.as_deref() == Some(&key.public_key_bytes()[..]) {
+                input.signature = Some(key.sign_digest(&digest)?);
+                signed += 1;
+            }
+        }
+        Ok(signed)
+    }
+
+    /// Merge what another copy of the same transaction knows
+    pub fn combine(&mut self, other: &Psbt) -> Result<(), PsbtError> {
+        if self.outpoints != other.outpoints || self.outputs != other.outputs {
+            return Err(PsbtError::DifferentTransaction);
+        }
+        self.check_inputs()?;
+        other.check_inputs()?;
+
+        for (index, (ours, theirs)) in self.inputs.iter_mut().zip(&other.inputs).enumerate() {
+            merge(&mut ours.previous_output, &theirs.previous_output, index)?;
+            merge(&mut ours.public_key, &theirs.public_key, index)?;
+            merge(&mut ours.hd_index, &theirs.hd_index, index)?;
+            merge(&mut ours.signature, &theirs.signature, index)?;
+        }
+        Ok(())
+    }
+
+    /// Check every signature and produce the signed transaction
+    pub fn finalize(&self) -> Result<Transaction, PsbtError> {
+        let mut tx = self.unsigned_tx()?;
+        tx.id = tx.unsigned_hash();
+
+        for (index, (input, tx_input)) in self.inputs.iter().zip(&mut tx.inputs).enumerate() {
+            let signature = input.signature.as_ref().ok_or(PsbtError::NotSigned(index))?;
+            if !verify_signature(&tx_input.public_key, signature, &tx.id.0) {
+                return Err(PsbtError::InvalidSignature(index));
+            }
+            tx_input.signature = signature.clone();
+        }
+        Ok(tx)
+    }
+
+    // Every outpoint needs its input data and vice versa
+    fn check_inputs(&self) -> Result<(), PsbtError> {
+        if self.inputs.len() != self.outpoints.len() {
+            return Err(PsbtError::InputCountMismatch {
+                outpoints: self.outpoints.len(),
+                inputs: self.inputs.len(),
+            });
+        }
+        Ok(())
+    }
+
+    // The transaction with every public key in place and no signatures, as
+    // `TxBuilder` hashes it
+    fn unsigned_tx(&self) -> Result<Transaction, PsbtError> {
+        self.check_inputs()?;
+        let mut inputs = Vec::with_capacity(self.inputs.len());
+        for (index, ((txid, vout), input)) in self.outpoints.iter().zip(&self.inputs).enumerate() {
+            let public_key = input.public_key.clone().ok_or(PsbtError::MissingPublicKey(index))?;
+            inputs.push(TxInput {
+                txid: txid.clone(),
+                index: *vout,
+                signature: Vec::new(),
+                public_key,
+            });
+        }
+
+        Ok(Transaction {
+            id: Hash([0u8; 32]),
+            inputs,
+            outputs: self.outputs.clone(),
+        })
+    }
+}
+
+fn merge<T: Clone + PartialEq>(ours: &mut Option<T>, theirs: &Option<T>, index: usize) -> Result<(), PsbtError> {
+    match (ours.as_ref(), theirs) {
+        (_, None) => Ok(()),
+        (None, Some(value)) => {
+            *ours = Some(value.clone());
+            Ok(())
+        }
+        (Some(a), Some(b)) if a == b => Ok(()),
+        _ => Err(PsbtError::Conflict(index)),
+    }
+}
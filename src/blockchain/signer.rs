@@ -0,0 +1,181 @@
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::address::Address;
+use super::wallet::{address_from_public_key_bytes, Wallet};
+use crate::crypto::hash::Hash;
+use crate::crypto::keys::{sign_message, verify_signature};
+
+/// Largest request or response on a signer connection
+const MAX_FRAME: usize = 4096;
+
+#[derive(Debug, Error)]
+pub enum SignerError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("encoding error: {0}")]
+    Encoding(#[from] bincode::Error),
+
+    #[error("signer refused: {0}")]
+    Refused(String),
+
+    #[error("signer protocol error: {0}")]
+    Protocol(String),
+}
+
+/// Something that holds a secret key and signs transaction ids with it.
+///
+/// The in-memory `Wallet` is one; `RemoteSigner` forwards to another
+/// process, so the key never has to live in the process building
+/// transactions.
+pub trait Signer: Send + Sync {
+    /// The 32-byte ed25519 public key
+    fn public_key(&self) -> Vec<u8>;
+
+    /// Sign a transaction id
+    fn sign_digest(&self, digest: &Hash) -> Result<Vec<u8>, SignerError>;
+
+    /// Address of the key, or `None` if the public key is malformed
+    fn address(&self) -> Option<Address> {
+        address_from_public_key_bytes(&self.public_key())
+    }
+}
+
+impl Signer for Wallet {
+    fn public_key(&self) -> Vec<u8> {
+        self.public_key_bytes()
+    }
+
+    fn sign_digest(&self, digest: &Hash) -> Result<Vec<u8>, SignerError> {
+        Ok(sign_message(&self.keypair, &digest.0))
+    }
+
+    fn address(&self) -> Option<Address> {
+        Some(Wallet::address(self))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum SignerRequest {
+    PublicKey,
+    Sign(Hash),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum SignerResponse {
+    PublicKey(Vec<u8>),
+    Signature(Vec<u8>),
+    Refused(String),
+}
+
+// Frames are `[u32 LE length][bincode payload]`
+fn write_frame<W: Write, T: Serialize>(stream: &mut W, message: &T) -> Result<(), SignerError> {
+    let payload = bincode::serialize(message)?;
+    stream.write_all(&(payload.len() as u32).to_le_bytes())?;
+    stream.write_all(&payload)?;
+    stream.flush()?;
+    Ok(())
+}
+
+// `None` on a clean end of stream
+fn read_frame<R: Read, T: for<'de> Deserialize<'de>>(stream: &mut R) -> Result<Option<T>, SignerError> {
+    let mut len = [0u8; 4];
+    match stream.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME {
+        return Err(SignerError::Protocol(format!("frame of {} bytes", len)));
+    }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload)?;
+    Ok(Some(bincode::deserialize(&payload)?))
+}
+
+/// Answer requests from one `RemoteSigner` connection with `signer` until
+/// the other side hangs up.
+///
+/// Run this in the process that holds the key, e.g. for every connection
+/// accepted on a Unix socket only the node's user can open.
+pub fn serve_signer<S: Read + Write>(stream: &mut S, signer: &dyn Signer) -> Result<(), SignerError> {
+    while let Some(request) = read_frame(stream)? {
+        let response = match request {
+            SignerRequest::PublicKey => SignerResponse::PublicKey(signer.public_key()),
+            SignerRequest::Sign(digest) => match signer.sign_digest(&digest) {
+                Ok(signature) => SignerResponse::Signature(signature),
+                Err(e) => SignerResponse::Refused(e.to_string()),
+            },
+        };
+        write_frame(stream, &response)?;
+    }
+    Ok(())
+}
+
+/// A signer in another process, reached over a stream such as a Unix
+/// socket served by `serve_signer`.
+///
+/// Signatures are checked against the public key before they are returned,
+/// so a misbehaving signer cannot slip an invalid one into a transaction.
+pub struct RemoteSigner<S> {
+    stream: Mutex<S>,
+    public_key: Vec<u8>,
+}
+
+impl<S: Read + Write + Send> RemoteSigner<S> {
+    /// Wrap a connected stream and fetch the signer's public key
+    pub fn new(mut stream: S) -> Result<Self, SignerError> {
+        write_frame(&mut stream, &SignerRequest::PublicKey)?;
+        let public_key = match read_frame(&mut stream)? {
+            Some(SignerResponse::PublicKey(key)) if key.len() == 32 => key,
+            other => return Err(unexpected(other)),
+        };
+        Ok(RemoteSigner { stream: Mutex::new(stream), public_key })
+    }
+}
+
+#[cfg(unix)]
+impl RemoteSigner<std::os::unix::net::UnixStream> {
+    /// Connect to a signer listening on the Unix socket at `path`
+    pub fn connect(path: impl AsRef<std::path::Path>) -> Result<Self, SignerError> {
+        RemoteSigner::new(std::os::unix::net::UnixStream::connect(path)?)
+    }
+}
+
+impl<S: Read + Write + Send> Signer for RemoteSigner<S> {
+    fn public_key(&self) -> Vec<u8> {
+        self.public_key.clone()
+    }
+
+    fn sign_digest(&self, digest: &Hash) -> Result<Vec<u8>, SignerError> {
+        let mut stream = self
+            .stream
+            .lock()
+            .map_err(|_| SignerError::Protocol("connection poisoned".into()))?;
+        write_frame(&mut *stream, &SignerRequest::Sign(digest.clone()))?;
+
+        match read_frame(&mut *stream)? {
+            Some(SignerResponse::Signature(signature)) => {
+                if !verify_signature(&self.public_key, &signature, &digest.0) {
+                    return Err(SignerError::Protocol("invalid signature".into()));
+                }
+                Ok(signature)
+            }
+            Some(SignerResponse::Refused(reason)) => Err(SignerError::Refused(reason)),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+fn unexpected(response: Option<SignerResponse>) -> SignerError {
+    match response {
+        None => SignerError::Protocol("signer hung up".into()),
+        Some(response) => SignerError::Protocol(format!("unexpected response {:?}", response)),
+    }
+}
@@ -1,60 +1,165 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use super::{transaction::Transaction, utxo::UtxoSet, wallet::address_from_public_key_bytes};
+use super::{
+    block::Block,
+    transaction::{Transaction, TxOutput},
+    utxo::{OutPoint, UtxoSet},
+    wallet::address_from_public_key_bytes,
+};
+use crate::crypto::hash::Hash;
 use crate::crypto::keys::verify_signature;
+use crate::storage::Store;
 
-pub struct State {
-    pub utxo_set: UtxoSet,
+/// New coins a block's coinbase may pay out on top of the fees it collects
+pub const BLOCK_REWARD: u64 = 50_000;
+
+/// Chain state backed by any `Store`; defaults to the in-memory `UtxoSet`
+pub struct State<S: Store = UtxoSet> {
+    pub utxo_set: S,
 }
 
-impl State {
+impl<S: Store> State<S> {
+    /// Validate a transaction on its own, e.g. for the mempool. Coinbases
+    /// are only valid at the head of a block, see `validate_block`.
     pub fn validate_transaction(&self, tx: &Transaction) -> bool {
-        // Coinbase always valid
-        if tx.is_coinbase() {
-            return true;
+        !tx.is_coinbase() && self.transaction_fee(tx, &HashMap::new()).is_some()
+    }
+
+    /// Fee paid by the non-coinbase `tx` if it is valid against the store
+    /// plus `pending` outputs that are not stored yet (e.g. created earlier
+    /// in the same block)
+    fn transaction_fee(
+        &self,
+        tx: &Transaction,
+        pending: &HashMap<OutPoint, TxOutput>,
+    ) -> Option<u64> {
+        // Outputs may only pay to address versions we know how to spend
+        if !tx.outputs.iter().all(|output| output.address.is_supported()) {
+            return None;
+        }
+
+        // The id is what inputs sign, so it must be the one the contents hash to
+        if tx.id != tx.unsigned_hash() {
+            return None;
         }
 
-        let mut input_sum = 0;
-        let mut output_sum = 0;
+        let mut input_sum: u64 = 0;
 
         // Track inputs used in this transaction to prevent double spends
         let mut seen_inputs = HashSet::new();
 
         // 1. Validate signatures + ownership
         for input in &tx.inputs {
-            if !seen_inputs.insert((input.txid.clone(), input.index)) {
-                return false;
+            let key = (input.txid.clone(), input.index);
+            if !seen_inputs.insert(key.clone()) {
+                return None;
             }
 
-            let utxo = match self.utxo_set.utxos.get(&(input.txid, input.index)) {
-                Some(u) => u,
-                None => return false,
+            let utxo = match pending.get(&key) {
+                Some(u) => u.clone(),
+                None => match self.utxo_set.get_utxo(&key) {
+                    Ok(Some(u)) => u,
+                    _ => return None,
+                },
             };
 
             // Ensure the provided public key matches the address of the UTXO
-            let derived_address = match address_from_public_key_bytes(&input.public_key) {
-                Some(addr) => addr,
-                None => return false,
-            };
+            let derived_address = address_from_public_key_bytes(&input.public_key)?;
 
             if derived_address != utxo.address {
-                return false;
+                return None;
             }
 
             if !verify_signature(&input.public_key, &input.signature, &tx.id.0) {
-                return false;
+                return None;
             }
 
             // 2. Sum inputs
-            input_sum += utxo.value;
+            input_sum = input_sum.checked_add(utxo.value)?;
         }
 
         // 3. Sum outputs
-        for o in &tx.outputs {
-            output_sum += o.value;
-        }
+        let output_sum = output_sum(tx)?;
 
         // 4. Prevent inflation
-        input_sum >= output_sum
+        input_sum.checked_sub(output_sum)
     }
-}
\ No newline at end of file
+
+    /// Check `block` against the current state:
+    /// - its hash and transaction ids match its contents
+    /// - exactly one coinbase, in first position, paying at most
+    ///   `BLOCK_REWARD` plus the block's fees (genesis excepted)
+    /// - every other transaction valid, possibly spending outputs created
+    ///   earlier in the block
+    /// - no output spent twice within the block
+    /// - no transaction id seen twice, in the block or in the UTXO set
+    ///
+    /// Linkage to the tip is checked by the caller.
+    pub fn validate_block(&self, block: &Block) -> bool {
+        if !block.is_consistent() {
+            return false;
+        }
+
+        let coinbase = match block.transactions.first() {
+            Some(tx) if tx.is_coinbase() => tx,
+            _ => return false,
+        };
+        if !coinbase.outputs.iter().all(|output| output.address.is_supported()) {
+            return false;
+        }
+
+        let mut pending: HashMap<OutPoint, TxOutput> = HashMap::new();
+        let mut spent: HashSet<OutPoint> = HashSet::new();
+        let mut txids: HashSet<&Hash> = HashSet::new();
+        let mut fees: u64 = 0;
+
+        for (i, tx) in block.transactions.iter().enumerate() {
+            if i > 0 && tx.is_coinbase() {
+                return false;
+            }
+            // Connecting a repeated id would overwrite the earlier outputs
+            if !txids.insert(&tx.id) || self.has_outputs_of(tx) {
+                return false;
+            }
+
+            for input in &tx.inputs {
+                if !spent.insert((input.txid.clone(), input.index)) {
+                    return false;
+                }
+            }
+
+            if i > 0 {
+                match self.transaction_fee(tx, &pending).and_then(|fee| fees.checked_add(fee)) {
+                    Some(total) => fees = total,
+                    None => return false,
+                }
+            }
+
+            for (index, output) in tx.outputs.iter().enumerate() {
+                pending.insert((tx.id.clone(), index as u32), output.clone());
+            }
+        }
+
+        // Genesis is fixed by the network, not earned
+        if block.index > 0 {
+            match output_sum(coinbase) {
+                Some(paid) if paid <= BLOCK_REWARD.saturating_add(fees) => {}
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    // Whether any output of `tx`'s id is already unspent in the store
+    fn has_outputs_of(&self, tx: &Transaction) -> bool {
+        (0..tx.outputs.len() as u32).any(|index| {
+            !matches!(self.utxo_set.get_utxo(&(tx.id.clone(), index)), Ok(None))
+        })
+    }
+}
+
+// Total value paid by `tx`, or `None` if it overflows
+fn output_sum(tx: &Transaction) -> Option<u64> {
+    tx.outputs.iter().try_fold(0u64, |sum, output| sum.checked_add(output.value))
+}
@@ -1,4 +1,5 @@
 use serde::{Serialize, Deserialize};
+use super::address::Address;
 use crate::crypto::hash::Hash;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,10 +10,10 @@ pub struct TxInput {
     pub public_key: Vec<u8>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TxOutput {
     pub value: u64,      // amount
-    pub address: String, // receiver address (pubkey hash)
+    pub address: Address, // receiver address (pubkey hash)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,7 +28,44 @@ impl Transaction {
         Hash::hash(self)
     }
 
+    /// The id of every non-coinbase transaction, and what its inputs sign:
+    /// the hash of the transaction with a zero id and no signatures
+    pub fn unsigned_hash(&self) -> Hash {
+        let mut unsigned = self.clone();
+        unsigned.id = Hash([0u8; 32]);
+        for input in unsigned.inputs.iter_mut() {
+            input.signature = Vec::new();
+        }
+        unsigned.calculate_hash()
+    }
+
+    /// Coinbase of the block at `height` paying `outputs`. Its id commits to
+    /// the height, so identical payouts in different blocks get distinct ids.
+    pub fn coinbase(height: u64, outputs: Vec<TxOutput>) -> Self {
+        Transaction {
+            id: Hash::hash(&(height, &outputs)),
+            inputs: vec![],
+            outputs,
+        }
+    }
+
+    /// Whether `id` matches the contents; coinbase ids also depend on the
+    /// `height` of their block
+    pub fn has_valid_id(&self, height: u64) -> bool {
+        let expected = if self.is_coinbase() {
+            Hash::hash(&(height, &self.outputs))
+        } else {
+            self.unsigned_hash()
+        };
+        self.id == expected
+    }
+
     pub fn is_coinbase(&self) -> bool {
         self.inputs.is_empty()
     }
+
+    /// Encoded size in bytes, the basis for fees
+    pub fn size(&self) -> u64 {
+        bincode::serialized_size(self).expect("transactions always serialize")
+    }
 }
\ No newline at end of file
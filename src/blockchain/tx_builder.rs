@@ -1,10 +1,22 @@
 use thiserror::Error;
+use crate::blockchain::address::Address;
+use crate::blockchain::coin_select::{CoinSelector, LargestFirst, Selection};
+use crate::blockchain::multiwallet::MultiWallet;
+use crate::blockchain::psbt::{Psbt, PsbtError};
+use crate::blockchain::signer::{Signer, SignerError};
 use crate::blockchain::transaction::{Transaction, TxInput, TxOutput};
-use crate::blockchain::utxo::UtxoSet;
+use crate::blockchain::utxo::{OutPoint, UtxoSet};
 use crate::blockchain::state::State;
-use crate::blockchain::wallet::Wallet;
+use crate::blockchain::wallet::address_from_public_key_bytes;
 use crate::crypto::hash::Hash;
-use crate::crypto::keys::sign_message;
+use crate::storage::Store;
+
+/// Outputs worth less than this cost more to spend than they hold; smaller
+/// change is left to the fee instead
+pub const DUST_LIMIT: u64 = 500;
+
+/// Size of an ed25519 signature
+const SIGNATURE_SIZE: usize = 64;
 
 #[derive(Debug, Error)]
 pub enum TxBuildError {
@@ -14,62 +26,84 @@ pub enum TxBuildError {
     #[error("no UTXOs available for this address")]
     NoUtxos,
 
+    #[error("cannot pay to {0:?}: unsupported address version")]
+    InvalidAddress(Address),
+
+    #[error("output of {value} to {address:?} is below the dust limit")]
+    DustOutput { address: Address, value: u64 },
+
+    #[error("invalid public key")]
+    InvalidPublicKey,
+
+    #[error("no recipients")]
+    NoRecipients,
+
+    #[error("amounts and fee add up to more than a u64 can hold")]
+    Overflow,
+
+    #[error("signing failed: {0}")]
+    Signer(#[from] SignerError),
+
     #[error("internal error: {0}")]
     Internal(String),
 }
 
-pub struct TxBuilder<'a> {
-    pub state: &'a State,
+pub struct TxBuilder<'a, S: Store = UtxoSet> {
+    pub state: &'a State<S>,
 }
 
-impl<'a> TxBuilder<'a> {
-    pub fn new(state: &'a State) -> Self {
+impl<'a, S: Store> TxBuilder<'a, S> {
+    pub fn new(state: &'a State<S>) -> Self {
         TxBuilder { state }
     }
 
     /// Create a standard payment transaction:
-    /// - from `wallet`
+    /// - from the key of `signer` (a `Wallet`, or any other `Signer`)
     /// - to `to_address`
     /// - amount `amount`
     /// - with fee `fee`
+    ///
+    /// Inputs are picked largest first; see `build_payment_tx_with` to
+    /// choose the strategy, or `build_payment_tx_at_rate` to pay a fee rate
+    /// instead of a fixed fee.
     pub fn build_payment_tx(
         &self,
-        wallet: &Wallet,
-        to_address: &str,
+        signer: &dyn Signer,
+        to_address: &Address,
         amount: u64,
         fee: u64,
     ) -> Result<Transaction, TxBuildError> {
-        let from_address = wallet.address();
-        let utxos = self.state.utxo_set.find_unspent(&from_address);
+        self.build_payment_tx_with(signer, to_address, amount, fee, &LargestFirst)
+    }
+
+    /// `build_payment_tx` with inputs picked by `selector`
+    pub fn build_payment_tx_with(
+        &self,
+        signer: &dyn Signer,
+        to_address: &Address,
+        amount: u64,
+        fee: u64,
+        selector: &dyn CoinSelector,
+    ) -> Result<Transaction, TxBuildError> {
+        check_address(to_address)?;
+        let from_address = signer.address().ok_or(TxBuildError::InvalidPublicKey)?;
+        let utxos = self
+            .state
+            .utxo_set
+            .find_unspent(&from_address)
+            .map_err(|e| TxBuildError::Internal(e.to_string()))?;
 
         if utxos.is_empty() {
             return Err(TxBuildError::NoUtxos);
         }
 
-        let target = amount + fee;
-
-        // 1. Select UTXOs (simple greedy strategy)
-        let mut selected = Vec::new();
-        let mut total_in = 0u64;
+        let target = amount.checked_add(fee).ok_or(TxBuildError::Overflow)?;
 
-        for (key, out) in utxos {
-            selected.push((key, out.clone()));
-            total_in += out.value;
-
-            if total_in >= target {
-                break;
-            }
-        }
-
-        if total_in < target {
-            return Err(TxBuildError::InsufficientFunds {
-                required: target,
-                available: total_in,
-            });
-        }
+        // 1. Select UTXOs
+        let Selection { inputs: selected, total: total_in } = select(selector, &utxos, target)?;
 
         // 2. Build inputs
-        let public_key_bytes = wallet.public_key_bytes();
+        let public_key_bytes = signer.public_key();
         let mut inputs = Vec::new();
 
         for ((txid, index), _output) in &selected {
@@ -87,7 +121,7 @@ impl<'a> TxBuilder<'a> {
         // recipient
         outputs.push(TxOutput {
             value: amount,
-            address: to_address.to_string(),
+            address: *to_address,
         });
 
         // change (if any)
@@ -107,16 +141,374 @@ impl<'a> TxBuilder<'a> {
         };
 
         // 5. Compute tx id (hash of tx skeleton)
-        let txid = tx.calculate_hash();
+        let txid = tx.unsigned_hash();
         tx.id = txid.clone();
 
         // 6. Sign each input with the wallet key
         //    (Sign over the txid; could also sign serialized tx data)
+        let sig = signer.sign_digest(&tx.id)?;
+        for input in tx.inputs.iter_mut() {
+            input.signature = sig.clone();
+        }
+
+        Ok(tx)
+    }
+
+    /// Create a payment from any of the keys of `wallet`:
+    /// - spends only outputs the wallet considers spendable (confirmed and
+    ///   not already spent by one of its unconfirmed transactions) that are
+    ///   still unspent in the chain state
+    /// - sends change to `change_address`, which should be one of ours
+    ///
+    /// Pass the result to `MultiWallet::add_pending` once broadcast.
+    pub fn build_wallet_payment(
+        &self,
+        wallet: &MultiWallet,
+        to_address: &Address,
+        amount: u64,
+        fee: u64,
+        change_address: &Address,
+    ) -> Result<Transaction, TxBuildError> {
+        self.build_wallet_payment_with(wallet, to_address, amount, fee, change_address, &LargestFirst)
+    }
+
+    /// `build_wallet_payment` with inputs picked by `selector`
+    pub fn build_wallet_payment_with(
+        &self,
+        wallet: &MultiWallet,
+        to_address: &Address,
+        amount: u64,
+        fee: u64,
+        change_address: &Address,
+        selector: &dyn CoinSelector,
+    ) -> Result<Transaction, TxBuildError> {
+        let utxos = self.wallet_utxos(wallet, true)?;
+        let payment = TxOutput {
+            value: amount,
+            address: *to_address,
+        };
+        let mut psbt = self.wallet_psbt(wallet, utxos, payment, fee, change_address, selector)?;
+
+        // Sign with the key of each address spent from, once per address
+        // since `Psbt::sign` covers every input of that key
+        let mut addresses: Vec<Address> = Vec::new();
+        for input in &psbt.inputs {
+            let address = input.previous_output.as_ref().expect("set by wallet_psbt").address;
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+        for address in &addresses {
+            let key = wallet
+                .key(address)
+                .ok_or_else(|| TxBuildError::Internal(format!("no key for {:?}", address)))?;
+            psbt.sign(key).map_err(|e| match e {
+                PsbtError::Signer(e) => TxBuildError::Signer(e),
+                e => TxBuildError::Internal(e.to_string()),
+            })?;
+        }
+
+        psbt.finalize().map_err(|e| TxBuildError::Internal(e.to_string()))
+    }
+
+    /// Like `build_wallet_payment_with`, but unsigned, so it can also spend
+    /// from watch-only addresses (which `build_wallet_payment` skips).
+    /// Every input carries its previous output and public key; sign the
+    /// result with `Psbt::sign` wherever the keys are.
+    pub fn build_wallet_psbt(
+        &self,
+        wallet: &MultiWallet,
+        to_address: &Address,
+        amount: u64,
+        fee: u64,
+        change_address: &Address,
+        selector: &dyn CoinSelector,
+    ) -> Result<Psbt, TxBuildError> {
+        let utxos = self.wallet_utxos(wallet, false)?;
+        let payment = TxOutput {
+            value: amount,
+            address: *to_address,
+        };
+        self.wallet_psbt(wallet, utxos, payment, fee, change_address, selector)
+    }
+
+    // Outputs `wallet` may spend that are still unspent in the chain state,
+    // only those it holds the secret key for if `signable`
+    fn wallet_utxos(&self, wallet: &MultiWallet, signable: bool) -> Result<Vec<(OutPoint, TxOutput)>, TxBuildError> {
+        let mut utxos = Vec::new();
+        for (outpoint, output) in wallet.spendable() {
+            if signable && wallet.key(&output.address).is_none() {
+                continue;
+            }
+            let unspent = self
+                .state
+                .utxo_set
+                .get_utxo(&outpoint)
+                .map_err(|e| TxBuildError::Internal(e.to_string()))?;
+            if unspent.is_some() {
+                utxos.push((outpoint, output));
+            }
+        }
+
+        if utxos.is_empty() {
+            return Err(TxBuildError::NoUtxos);
+        }
+        Ok(utxos)
+    }
+
+    fn wallet_psbt(
+        &self,
+        wallet: &MultiWallet,
+        utxos: Vec<(OutPoint, TxOutput)>,
+        payment: TxOutput,
+        fee: u64,
+        change_address: &Address,
+        selector: &dyn CoinSelector,
+    ) -> Result<Psbt, TxBuildError> {
+        check_address(&payment.address)?;
+        check_address(change_address)?;
+        let target = payment.value.checked_add(fee).ok_or(TxBuildError::Overflow)?;
+
+        // 1. Select UTXOs
+        let Selection { inputs: selected, total: total_in } = select(selector, &utxos, target)?;
+
+        // 2. Build outputs (recipient + change)
+        let mut outputs = vec![payment];
+
+        let change = total_in - target;
+        if change > 0 {
+            outputs.push(TxOutput {
+                value: change,
+                address: *change_address,
+            });
+        }
+
+        // 3. Describe each input for its signer
+        let outpoints = selected.iter().map(|(outpoint, _)| outpoint.clone()).collect();
+        let mut psbt = Psbt::create(outpoints, outputs);
+
+        for (index, (_, output)) in selected.into_iter().enumerate() {
+            let public_key = wallet
+                .public_key(&output.address)
+                .ok_or_else(|| TxBuildError::Internal(format!("no key for {:?}", output.address)))?;
+            psbt.inputs[index].previous_output = Some(output);
+            psbt.set_key(index, &public_key, None)
+                .map_err(|e| TxBuildError::Internal(e.to_string()))?;
+        }
+
+        Ok(psbt)
+    }
+
+    /// Pay `amount` to `to_address` with a fee of `fee_rate` per byte of
+    /// the signed transaction (see `FeeEstimator` for a rate), checked like
+    /// `build_batch_payment`
+    pub fn build_payment_tx_at_rate(
+        &self,
+        signer: &dyn Signer,
+        to_address: &Address,
+        amount: u64,
+        fee_rate: u64,
+        selector: &dyn CoinSelector,
+    ) -> Result<Transaction, TxBuildError> {
+        let payment = TxOutput {
+            value: amount,
+            address: *to_address,
+        };
+        self.build_batch_payment(signer, &[payment], fee_rate, selector)
+    }
+
+    /// Like `build_payment_tx_with`, but for a key held elsewhere: returns
+    /// an unsigned `Psbt` with previous outputs and the public key filled
+    /// in, ready to be signed by whoever holds the secret key
+    pub fn build_psbt(
+        &self,
+        public_key: &[u8],
+        to_address: &Address,
+        amount: u64,
+        fee: u64,
+        selector: &dyn CoinSelector,
+    ) -> Result<Psbt, TxBuildError> {
+        check_address(to_address)?;
+        let from_address = address_from_public_key_bytes(public_key).ok_or(TxBuildError::InvalidPublicKey)?;
+        let utxos = self
+            .state
+            .utxo_set
+            .find_unspent(&from_address)
+            .map_err(|e| TxBuildError::Internal(e.to_string()))?;
+
+        if utxos.is_empty() {
+            return Err(TxBuildError::NoUtxos);
+        }
+
+        let target = amount.checked_add(fee).ok_or(TxBuildError::Overflow)?;
+        let Selection { inputs: selected, total: total_in } = select(selector, &utxos, target)?;
+
+        let mut outputs = vec![TxOutput {
+            value: amount,
+            address: *to_address,
+        }];
+        let change = total_in - target;
+        if change > 0 {
+            outputs.push(TxOutput {
+                value: change,
+                address: from_address,
+            });
+        }
+
+        let outpoints = selected.iter().map(|(outpoint, _)| outpoint.clone()).collect();
+        let mut psbt = Psbt::create(outpoints, outputs);
+        for (index, (_, previous)) in selected.into_iter().enumerate() {
+            psbt.inputs[index].previous_output = Some(previous);
+            psbt.set_key(index, public_key, None)
+                .map_err(|e| TxBuildError::Internal(e.to_string()))?;
+        }
+        Ok(psbt)
+    }
+
+    /// Pay many recipients from the key of `signer` in one transaction:
+    /// - every recipient address must have a supported version and every
+    ///   amount be at least `DUST_LIMIT`
+    /// - the fee is `fee_rate` per byte of the signed transaction
+    /// - change goes back to the signer, unless it would be dust, in which
+    ///   case it is added to the fee
+    pub fn build_batch_payment(
+        &self,
+        signer: &dyn Signer,
+        payments: &[TxOutput],
+        fee_rate: u64,
+        selector: &dyn CoinSelector,
+    ) -> Result<Transaction, TxBuildError> {
+        if payments.is_empty() {
+            return Err(TxBuildError::NoRecipients);
+        }
+        for payment in payments {
+            check_address(&payment.address)?;
+            if payment.value < DUST_LIMIT {
+                return Err(TxBuildError::DustOutput {
+                    address: payment.address,
+                    value: payment.value,
+                });
+            }
+        }
+
+        let from_address = signer.address().ok_or(TxBuildError::InvalidPublicKey)?;
+        let utxos = self
+            .state
+            .utxo_set
+            .find_unspent(&from_address)
+            .map_err(|e| TxBuildError::Internal(e.to_string()))?;
+
+        if utxos.is_empty() {
+            return Err(TxBuildError::NoUtxos);
+        }
+
+        let amount = payments
+            .iter()
+            .try_fold(0u64, |sum, payment| sum.checked_add(payment.value))
+            .ok_or(TxBuildError::Overflow)?;
+        let mut outputs = payments.to_vec();
+        let change_output = TxOutput { value: 0, address: from_address };
+
+        // 1. Select for amount + fee, starting from the fee of the smallest
+        //    possible transaction. More inputs mean a bigger transaction and
+        //    a higher fee, so select again until the fee is covered; the fee
+        //    only grows, so this ends.
+        let mut fee = fee_at_rate(1, &outputs, fee_rate)?;
+        let (selected, change) = loop {
+            let target = amount.checked_add(fee).ok_or(TxBuildError::Overflow)?;
+            let Selection { inputs, total } = select(selector, &utxos, target)?;
+
+            let fee_without_change = fee_at_rate(inputs.len(), &outputs, fee_rate)?;
+            outputs.push(change_output.clone());
+            let fee_with_change = fee_at_rate(inputs.len(), &outputs, fee_rate)?;
+            outputs.pop();
+
+            // Totals that would overflow cannot be covered
+            let needed_with_change = amount
+                .checked_add(fee_with_change)
+                .and_then(|needed| needed.checked_add(DUST_LIMIT));
+            if needed_with_change.is_some_and(|needed| total >= needed) {
+                break (inputs, total - amount - fee_with_change);
+            }
+            if amount.checked_add(fee_without_change).is_some_and(|needed| total >= needed) {
+                break (inputs, 0);
+            }
+            fee = fee_without_change;
+        };
+
+        // 2. Build outputs (recipients + change above dust)
+        if change > 0 {
+            outputs.push(TxOutput { value: change, ..change_output });
+        }
+
+        // 3. Build inputs, compute the id and sign it
+        let public_key = signer.public_key();
+        let inputs = selected
+            .iter()
+            .map(|((txid, index), _)| TxInput {
+                txid: txid.clone(),
+                index: *index,
+                signature: Vec::new(),
+                public_key: public_key.clone(),
+            })
+            .collect();
+
+        let mut tx = Transaction {
+            id: Hash([0u8; 32]),
+            inputs,
+            outputs,
+        };
+        tx.id = tx.unsigned_hash();
+
+        let signature = signer.sign_digest(&tx.id)?;
         for input in tx.inputs.iter_mut() {
-            let sig = sign_message(&wallet.keypair, &tx.id.0);
-            input.signature = sig;
+            input.signature = signature.clone();
         }
 
         Ok(tx)
     }
-}
\ No newline at end of file
+}
+
+// Refuse to lock coins in an address no key can spend yet
+fn check_address(address: &Address) -> Result<(), TxBuildError> {
+    if !address.is_supported() {
+        return Err(TxBuildError::InvalidAddress(*address));
+    }
+    Ok(())
+}
+
+// Every transaction spends at least one input, even a zero-value one
+fn select(
+    selector: &dyn CoinSelector,
+    utxos: &[(OutPoint, TxOutput)],
+    target: u64,
+) -> Result<Selection, TxBuildError> {
+    selector.select(utxos, target.max(1)).ok_or_else(|| TxBuildError::InsufficientFunds {
+        required: target,
+        available: utxos.iter().fold(0, |sum, (_, output)| sum.saturating_add(output.value)),
+    })
+}
+
+// Fee for a signed transaction of that shape at `fee_rate` per byte
+fn fee_at_rate(inputs: usize, outputs: &[TxOutput], fee_rate: u64) -> Result<u64, TxBuildError> {
+    signed_size(inputs, outputs)
+        .checked_mul(fee_rate)
+        .ok_or(TxBuildError::Overflow)
+}
+
+// Size of a signed transaction with `inputs` inputs paying `outputs`. The
+// id, signatures and keys have fixed sizes, so placeholders give it exactly.
+fn signed_size(inputs: usize, outputs: &[TxOutput]) -> u64 {
+    let input = TxInput {
+        txid: Hash([0u8; 32]),
+        index: 0,
+        signature: vec![0u8; SIGNATURE_SIZE],
+        public_key: vec![0u8; 32],
+    };
+    Transaction {
+        id: Hash([0u8; 32]),
+        inputs: vec![input; inputs],
+        outputs: outputs.to_vec(),
+    }
+    .size()
+}
@@ -1,43 +1,182 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Bound;
+use serde::{Serialize, Deserialize};
 use crate::crypto::hash::Hash;
+use crate::crypto::multiset::MultisetHash;
+use super::address::Address;
+use super::block::Block;
 use super::transaction::{Transaction, TxOutput};
 
+/// A reference to a transaction output: (txid, output index)
+pub type OutPoint = (Hash, u32);
+
+/// Outputs spent by a block, in spend order, so the block can be disconnected
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlockUndo {
+    pub spent: Vec<(OutPoint, TxOutput)>,
+}
+
+/// Canonical encoding of one UTXO as a commitment element
+pub fn commitment_element(outpoint: &OutPoint, output: &TxOutput) -> Vec<u8> {
+    bincode::serialize(&(outpoint, output)).expect("UTXO entries always serialize")
+}
+
+/// In-memory UTXO set; also the in-memory `Store` backend, keeping connected
+/// blocks and their undo data alongside the outputs
+///
+/// Outputs are indexed by address so lookups only touch that address's
+/// outpoints. All mutations go through `insert`/`remove` to keep the index
+/// and the running set commitment in sync.
 #[derive(Default)]
 pub struct UtxoSet {
-    pub utxos: HashMap<(Hash, u32), TxOutput>,
+    utxos: HashMap<OutPoint, TxOutput>,
+    by_address: HashMap<Address, BTreeSet<OutPoint>>,
+    commitment: MultisetHash,
+    pub(crate) blocks: HashMap<Hash, (Block, BlockUndo)>,
+    pub(crate) chain: Vec<Hash>,
 }
 
 impl UtxoSet {
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&TxOutput> {
+        self.utxos.get(outpoint)
+    }
+
+    pub fn contains(&self, outpoint: &OutPoint) -> bool {
+        self.utxos.contains_key(outpoint)
+    }
+
+    pub fn len(&self) -> usize {
+        self.utxos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.utxos.is_empty()
+    }
+
+    /// Order-independent hash over every unspent output, updated incrementally
+    pub fn commitment(&self) -> Hash {
+        self.commitment.finalize()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&OutPoint, &TxOutput)> {
+        self.utxos.iter()
+    }
+
+    /// Add an unspent output, replacing any previous output at `outpoint`
+    pub fn insert(&mut self, outpoint: OutPoint, output: TxOutput) {
+        self.remove(&outpoint);
+
+        self.by_address
+            .entry(output.address)
+            .or_default()
+            .insert(outpoint.clone());
+        self.commitment.insert(&commitment_element(&outpoint, &output));
+        self.utxos.insert(outpoint, output);
+    }
+
+    pub fn remove(&mut self, outpoint: &OutPoint) -> Option<TxOutput> {
+        let output = self.utxos.remove(outpoint)?;
+        self.commitment.remove(&commitment_element(outpoint, &output));
+
+        if let Some(set) = self.by_address.get_mut(&output.address) {
+            set.remove(outpoint);
+            if set.is_empty() {
+                self.by_address.remove(&output.address);
+            }
+        }
+
+        Some(output)
+    }
+
     pub fn apply_transaction(&mut self, tx: &Transaction) {
         // Remove inputs
         for input in &tx.inputs {
-            self.utxos.remove(&(input.txid, input.index));
+            self.remove(&(input.txid.clone(), input.index));
         }
 
         // Add outputs
         for (i, output) in tx.outputs.iter().enumerate() {
-            self.utxos.insert((tx.id, i as u32), output.clone());
+            self.insert((tx.id.clone(), i as u32), output.clone());
         }
     }
 
-    pub fn apply_block(&mut self, block: &crate::blockchain::block::Block) {
+    /// Apply every transaction in `block`, returning the undo data needed to
+    /// revert it with `undo_block`
+    pub fn apply_block(&mut self, block: &Block) -> BlockUndo {
+        let mut undo = BlockUndo::default();
+        let mut created = Vec::new();
+
         for tx in &block.transactions {
+            for input in &tx.inputs {
+                let key = (input.txid.clone(), input.index);
+
+                // Outputs created and spent inside this block need no undo entry
+                if created.contains(&key) {
+                    continue;
+                }
+                if let Some(out) = self.utxos.get(&key) {
+                    undo.spent.push((key, out.clone()));
+                }
+            }
             self.apply_transaction(tx);
+            created.extend((0..tx.outputs.len() as u32).map(|i| (tx.id.clone(), i)));
+        }
+
+        undo
+    }
+
+    /// Revert `block`: drop the outputs it created and restore what it spent
+    pub fn undo_block(&mut self, block: &Block, undo: &BlockUndo) {
+        for tx in block.transactions.iter().rev() {
+            for i in 0..tx.outputs.len() {
+                self.remove(&(tx.id.clone(), i as u32));
+            }
+        }
+
+        for (key, out) in &undo.spent {
+            self.insert(key.clone(), out.clone());
         }
     }
 
-    pub fn find_unspent(&self, address: &str) -> Vec<((Hash, u32), TxOutput)> {
-        self.utxos
-            .iter()
-            .filter(|(_, out)| out.address == address)
-            .map(|(k, v)| (k.clone(), v.clone()))
+    /// Outpoints paying to `address`, in outpoint order
+    pub fn outpoints_for(&self, address: &Address) -> impl Iterator<Item = &OutPoint> {
+        self.by_address.get(address).into_iter().flatten()
+    }
+
+    pub fn find_unspent(&self, address: &Address) -> Vec<(OutPoint, TxOutput)> {
+        self.outpoints_for(address)
+            .map(|k| (k.clone(), self.utxos[k].clone()))
+            .collect()
+    }
+
+    /// Up to `limit` unspent outputs of `address`, starting after `after`
+    ///
+    /// Pass the last outpoint of the previous page to continue iterating.
+    pub fn find_unspent_page(
+        &self,
+        address: &Address,
+        after: Option<&OutPoint>,
+        limit: usize,
+    ) -> Vec<(OutPoint, TxOutput)> {
+        let set = match self.by_address.get(address) {
+            Some(set) => set,
+            None => return Vec::new(),
+        };
+
+        let start = match after {
+            Some(k) => Bound::Excluded(k.clone()),
+            None => Bound::Unbounded,
+        };
+
+        set.range((start, Bound::Unbounded))
+            .take(limit)
+            .map(|k| (k.clone(), self.utxos[k].clone()))
             .collect()
     }
 
-    pub fn available_balance(&self, address: &str) -> u64 {
-        self.find_unspent(address)
-            .iter()
-            .map(|(_, out)| out.value)
+    pub fn available_balance(&self, address: &Address) -> u64 {
+        self.outpoints_for(address)
+            .map(|k| self.utxos[k].value)
             .sum()
     }
 }
@@ -1,18 +1,14 @@
-use ed25519_dalek::Keypair;
+use ed25519_dalek::{Keypair, PublicKey, SecretKey};
 use rand::rngs::OsRng;
-use sha2::{Digest, Sha256};
+
+use super::address::Address;
 
 pub struct Wallet {
     pub keypair: Keypair,
 }
 
-pub fn address_from_public_key_bytes(public_key_bytes: &[u8]) -> Option<String> {
-    if public_key_bytes.len() != 32 {
-        return None;
-    }
-
-    let hash = Sha256::digest(public_key_bytes);
-    Some(hex::encode(&hash[0..20]))
+pub fn address_from_public_key_bytes(public_key_bytes: &[u8]) -> Option<Address> {
+    Address::from_public_key(public_key_bytes)
 }
 
 impl Wallet {
@@ -22,7 +18,18 @@ impl Wallet {
         Wallet { keypair }
     }
 
-    pub fn address(&self) -> String {
+    /// Rebuild a wallet from its 32-byte secret key
+    pub fn from_secret(secret: [u8; 32]) -> Self {
+        let secret = SecretKey::from_bytes(&secret).expect("any 32 bytes are a valid secret key");
+        let public = PublicKey::from(&secret);
+        Wallet { keypair: Keypair { secret, public } }
+    }
+
+    pub fn secret_bytes(&self) -> [u8; 32] {
+        self.keypair.secret.to_bytes()
+    }
+
+    pub fn address(&self) -> Address {
         address_from_public_key_bytes(self.keypair.public.as_bytes())
             .expect("public key length should always be valid")
     }
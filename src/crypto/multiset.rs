@@ -0,0 +1,51 @@
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::traits::Identity;
+use sha2::{Digest, Sha256, Sha512};
+
+use super::hash::Hash;
+
+/// Incremental multiset hash (ECMH over Ristretto255).
+///
+/// Each element is hashed to a curve point; the set hash is the sum of those
+/// points. Adding or removing an element is a single point addition, and the
+/// result is independent of insertion order, so a large set can be committed
+/// to without re-hashing it on every change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MultisetHash {
+    acc: RistrettoPoint,
+}
+
+impl Default for MultisetHash {
+    fn default() -> Self {
+        MultisetHash { acc: RistrettoPoint::identity() }
+    }
+}
+
+impl MultisetHash {
+    pub fn insert(&mut self, element: &[u8]) {
+        self.acc += RistrettoPoint::hash_from_bytes::<Sha512>(element);
+    }
+
+    pub fn remove(&mut self, element: &[u8]) {
+        self.acc -= RistrettoPoint::hash_from_bytes::<Sha512>(element);
+    }
+
+    /// 32-byte encoding of the accumulator, for persisting the running state
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.acc.compress().to_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8; 32]) -> Option<Self> {
+        CompressedRistretto(*bytes)
+            .decompress()
+            .map(|acc| MultisetHash { acc })
+    }
+
+    /// Final commitment over the set
+    pub fn finalize(&self) -> Hash {
+        let digest = Sha256::digest(self.to_bytes());
+        let mut out = [0u8; 32];
+        out.copy_from_slice(&digest);
+        Hash(out)
+    }
+}
@@ -0,0 +1,28 @@
+use bincode::Options;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("payload too large: {0} bytes")]
+    TooLarge(usize),
+
+    #[error("malformed payload: {0}")]
+    Malformed(#[from] bincode::Error),
+}
+
+// Same wire format as `bincode::serialize`, but bounded and strict
+fn options(limit: u64) -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_limit(limit)
+        .reject_trailing_bytes()
+}
+
+/// Decode bytes from an untrusted source, refusing anything over `limit`
+/// bytes or followed by trailing bytes
+pub fn decode<T: serde::de::DeserializeOwned>(data: &[u8], limit: u64) -> Result<T, DecodeError> {
+    if data.len() as u64 > limit {
+        return Err(DecodeError::TooLarge(data.len()));
+    }
+    Ok(options(limit).deserialize(data)?)
+}
@@ -1,2 +1,5 @@
 pub mod blockchain;
 pub mod crypto;
+pub mod encoding;
+pub mod p2p;
+pub mod storage;
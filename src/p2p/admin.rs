@@ -0,0 +1,209 @@
+use std::io;
+#[cfg(unix)]
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+#[cfg(unix)]
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+
+use super::banlist::{unix_now, BanEntry, BanList};
+
+/// Private directory holding the control socket, in the node's network
+/// directory
+pub const ADMIN_SOCKET_DIR: &str = "admin";
+
+/// Control socket in `ADMIN_SOCKET_DIR`
+pub const ADMIN_SOCKET_FILE: &str = "admin.sock";
+
+/// Longest request line accepted on the control socket
+#[cfg(unix)]
+const MAX_REQUEST_LINE: u64 = 4096;
+
+/// Requests the network loop accepts from operators
+#[derive(Debug)]
+pub enum AdminCommand {
+    ListBans { reply: oneshot::Sender<Vec<(String, BanEntry)>> },
+    /// Ban a peer (by PeerId string) and disconnect it
+    Ban { peer: String, duration: Duration, reply: oneshot::Sender<()> },
+    /// Reply is false if the peer was not banned
+    Unban { peer: String, reply: oneshot::Sender<bool> },
+}
+
+/// Client side of the admin API; cheap to clone
+#[derive(Debug, Clone)]
+pub struct AdminHandle {
+    commands: mpsc::Sender<AdminCommand>,
+}
+
+/// Operator requests sent over the control socket, one JSON line each
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdminRequest {
+    ListBans,
+    Ban { peer: String, duration: Duration },
+    Unban { peer: String },
+}
+
+/// Answer to an `AdminRequest`, one JSON line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdminResponse {
+    Bans(Vec<(String, BanEntry)>),
+    Banned,
+    /// False if the peer was not banned
+    Unbanned(bool),
+    Failed(String),
+}
+
+#[derive(Debug, Error)]
+pub enum AdminError {
+    #[error("network loop has stopped")]
+    Stopped,
+
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("malformed admin message: {0}")]
+    Malformed(#[from] serde_json::Error),
+}
+
+impl AdminHandle {
+    /// A handle plus the receiver to pass to the network loop
+    pub fn channel() -> (AdminHandle, mpsc::Receiver<AdminCommand>) {
+        let (commands, receiver) = mpsc::channel(16);
+        (AdminHandle { commands }, receiver)
+    }
+
+    async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> AdminCommand) -> Result<T, AdminError> {
+        let (reply, response) = oneshot::channel();
+        self.commands.send(command(reply)).await.map_err(|_| AdminError::Stopped)?;
+        response.await.map_err(|_| AdminError::Stopped)
+    }
+
+    pub async fn list_bans(&self) -> Result<Vec<(String, BanEntry)>, AdminError> {
+        self.request(|reply| AdminCommand::ListBans { reply }).await
+    }
+
+    pub async fn ban(&self, peer: &str, duration: Duration) -> Result<(), AdminError> {
+        let peer = peer.to_string();
+        self.request(|reply| AdminCommand::Ban { peer, duration, reply }).await
+    }
+
+    pub async fn unban(&self, peer: &str) -> Result<bool, AdminError> {
+        let peer = peer.to_string();
+        self.request(|reply| AdminCommand::Unban { peer, reply }).await
+    }
+
+    /// Carry out a request received on the control socket
+    pub async fn execute(&self, request: AdminRequest) -> AdminResponse {
+        let result = match request {
+            AdminRequest::ListBans => self.list_bans().await.map(AdminResponse::Bans),
+            AdminRequest::Ban { peer, duration } => {
+                self.ban(&peer, duration).await.map(|()| AdminResponse::Banned)
+            }
+            AdminRequest::Unban { peer } => self.unban(&peer).await.map(AdminResponse::Unbanned),
+        };
+        result.unwrap_or_else(|e| AdminResponse::Failed(e.to_string()))
+    }
+}
+
+/// Listen on the control socket at `path`, replacing one left behind by an
+/// earlier run. Only our own user may connect: the socket's directory is
+/// made private before binding, so the socket is never reachable by others,
+/// even before its own permissions are tightened.
+#[cfg(unix)]
+pub fn bind_admin_socket(path: impl AsRef<Path>) -> io::Result<UnixListener> {
+    use std::fs::{DirBuilder, Permissions};
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let path = path.as_ref();
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        // An existing directory keeps its mode; tighten it too
+        std::fs::set_permissions(dir, Permissions::from_mode(0o700))?;
+    }
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Answer control socket connections with `admin`, one request and one
+/// response per connection
+#[cfg(unix)]
+pub async fn serve_admin_socket(listener: UnixListener, admin: AdminHandle) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                println!("Admin socket error: {}", e);
+                continue;
+            }
+        };
+        let admin = admin.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_admin_connection(stream, &admin).await {
+                println!("Admin connection failed: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(unix)]
+async fn serve_admin_connection(mut stream: UnixStream, admin: &AdminHandle) -> Result<(), AdminError> {
+    let (reader, mut writer) = stream.split();
+    let mut line = String::new();
+    BufReader::new(reader.take(MAX_REQUEST_LINE)).read_line(&mut line).await?;
+
+    let response = match serde_json::from_str(&line) {
+        Ok(request) => admin.execute(request).await,
+        Err(e) => AdminResponse::Failed(format!("malformed request: {}", e)),
+    };
+    writer.write_all(&json_line(&response)?).await?;
+    Ok(())
+}
+
+/// Send `request` to the node whose control socket is at `path`
+#[cfg(unix)]
+pub async fn admin_request(path: impl AsRef<Path>, request: &AdminRequest) -> Result<AdminResponse, AdminError> {
+    let mut stream = UnixStream::connect(path).await?;
+    stream.write_all(&json_line(request)?).await?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).await?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(unix)]
+fn json_line<T: Serialize>(message: &T) -> Result<Vec<u8>, AdminError> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+/// Apply `command` to the ban list and answer it. Returns the peer to
+/// disconnect, if any.
+pub fn handle_admin_command(bans: &mut BanList, command: AdminCommand) -> Option<String> {
+    let now = unix_now();
+    match command {
+        AdminCommand::ListBans { reply } => {
+            let _ = reply.send(bans.list(now));
+            None
+        }
+        AdminCommand::Ban { peer, duration, reply } => {
+            bans.ban(&peer, duration, "banned by operator", now);
+            let _ = reply.send(());
+            Some(peer)
+        }
+        AdminCommand::Unban { peer, reply } => {
+            let _ = reply.send(bans.unban(&peer));
+            None
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Score at which a peer is disconnected and banned
+pub const BAN_THRESHOLD: u32 = 100;
+
+/// How long automatic bans last
+pub const DEFAULT_BAN_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Things a peer can do wrong, each adding to its misbehavior score
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Misbehavior {
+    InvalidBlock,
+    InvalidTransaction,
+    MalformedMessage,
+    OversizedMessage,
+    InvalidSyncResponse,
+}
+
+impl Misbehavior {
+    pub fn penalty(self) -> u32 {
+        match self {
+            Misbehavior::InvalidBlock => 100,
+            Misbehavior::InvalidTransaction => 10,
+            Misbehavior::MalformedMessage => 20,
+            Misbehavior::OversizedMessage => 50,
+            Misbehavior::InvalidSyncResponse => 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BanEntry {
+    /// Unix time (seconds) the ban ends
+    pub until: u64,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BanFile {
+    version: u32,
+    bans: BTreeMap<String, BanEntry>,
+}
+
+const BAN_FILE_VERSION: u32 = 1;
+
+/// Seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Misbehavior scores of peers and time-limited bans.
+///
+/// Peers are identified by their PeerId string. Scores live in memory and
+/// are kept across reconnects; bans are saved so they outlast restarts.
+/// Times are Unix seconds.
+pub struct BanList {
+    path: Option<PathBuf>,
+    scores: HashMap<String, u32>,
+    bans: BTreeMap<String, BanEntry>,
+    dirty: bool,
+}
+
+impl BanList {
+    /// An empty list that is never saved
+    pub fn in_memory() -> Self {
+        BanList { path: None, scores: HashMap::new(), bans: BTreeMap::new(), dirty: false }
+    }
+
+    /// Load bans from `path`; a missing file is an empty list
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut list = BanList { path: Some(path.clone()), ..BanList::in_memory() };
+
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(list),
+            Err(e) => return Err(e),
+        };
+        let file: BanFile = serde_json::from_slice(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if file.version != BAN_FILE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported ban file version {}", file.version),
+            ));
+        }
+
+        list.bans = file.bans;
+        Ok(list)
+    }
+
+    /// Write the bans if they changed since the last save
+    pub fn save(&mut self) -> io::Result<()> {
+        let path = match (&self.path, self.dirty) {
+            (Some(path), true) => path,
+            _ => return Ok(()),
+        };
+
+        let file = BanFile { version: BAN_FILE_VERSION, bans: self.bans.clone() };
+        let bytes = serde_json::to_vec_pretty(&file)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, path)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn score(&self, peer: &str) -> u32 {
+        self.scores.get(peer).copied().unwrap_or(0)
+    }
+
+    /// Add the penalty for `kind` to the peer's score, banning it once the
+    /// score reaches `BAN_THRESHOLD`. Returns true if the peer was banned.
+    pub fn misbehaving(&mut self, peer: &str, kind: Misbehavior, now: u64) -> bool {
+        let score = self.scores.entry(peer.to_string()).or_insert(0);
+        *score = score.saturating_add(kind.penalty());
+        if *score < BAN_THRESHOLD {
+            return false;
+        }
+
+        self.ban(peer, DEFAULT_BAN_DURATION, &format!("{:?}", kind), now);
+        true
+    }
+
+    /// Ban `peer` for `duration`, replacing any existing ban
+    pub fn ban(&mut self, peer: &str, duration: Duration, reason: &str, now: u64) {
+        self.scores.remove(peer);
+        self.bans.insert(
+            peer.to_string(),
+            BanEntry { until: now.saturating_add(duration.as_secs()), reason: reason.to_string() },
+        );
+        self.dirty = true;
+    }
+
+    /// Lift a ban; returns false if the peer was not banned
+    pub fn unban(&mut self, peer: &str) -> bool {
+        let removed = self.bans.remove(peer).is_some();
+        self.dirty |= removed;
+        removed
+    }
+
+    pub fn is_banned(&self, peer: &str, now: u64) -> bool {
+        self.bans.get(peer).is_some_and(|ban| ban.until > now)
+    }
+
+    /// Remove expired bans
+    pub fn expire(&mut self, now: u64) {
+        let before = self.bans.len();
+        self.bans.retain(|_, ban| ban.until > now);
+        self.dirty |= self.bans.len() != before;
+    }
+
+    /// Current bans, ordered by peer
+    pub fn list(&self, now: u64) -> Vec<(String, BanEntry)> {
+        self.bans
+            .iter()
+            .filter(|(_, ban)| ban.until > now)
+            .map(|(peer, ban)| (peer.clone(), ban.clone()))
+            .collect()
+    }
+}
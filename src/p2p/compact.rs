@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::blockchain::block::{Block, BlockHeader};
+use crate::blockchain::mempool::Mempool;
+use crate::blockchain::transaction::Transaction;
+use crate::crypto::hash::Hash;
+
+/// Truncated, salted transaction ID used in compact blocks
+pub type ShortId = [u8; 8];
+
+pub fn short_id(salt: u64, txid: &Hash) -> ShortId {
+    let digest = Hash::hash(&(salt, txid));
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&digest.0[..8]);
+    id
+}
+
+/// A block announced as its header plus short IDs of its transactions.
+///
+/// Peers rebuild the block from their mempool and only fetch what they are
+/// missing. Coinbase transactions are never in a mempool, so they are sent
+/// in full (`prefilled`, with their position in the block).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactBlock {
+    pub header: BlockHeader,
+    /// Per-announcement salt so short ID collisions cannot be precomputed
+    pub salt: u64,
+    pub short_ids: Vec<ShortId>,
+    pub prefilled: Vec<(u32, Transaction)>,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CompactError {
+    #[error("prefilled transaction index {0} is out of range or repeated")]
+    BadPrefilled(u32),
+
+    #[error("block contains duplicate short IDs")]
+    DuplicateShortIds,
+
+    #[error("expected {expected} missing transactions, got {actual}")]
+    WrongTransactionCount { expected: usize, actual: usize },
+
+    #[error("transaction {0:?} does not match its short ID")]
+    ShortIdMismatch(Hash),
+}
+
+impl CompactBlock {
+    pub fn from_block(block: &Block, salt: u64) -> Self {
+        let mut short_ids = Vec::new();
+        let mut prefilled = Vec::new();
+
+        for (i, tx) in block.transactions.iter().enumerate() {
+            if tx.is_coinbase() {
+                prefilled.push((i as u32, tx.clone()));
+            } else {
+                short_ids.push(short_id(salt, &tx.id));
+            }
+        }
+
+        CompactBlock { header: block.header(), salt, short_ids, prefilled }
+    }
+
+    pub fn tx_count(&self) -> usize {
+        self.short_ids.len() + self.prefilled.len()
+    }
+
+    /// Rebuild the block from `mempool`. Transactions that are not in the
+    /// pool, or whose short ID matches several pool transactions, are left
+    /// for the caller to fetch.
+    pub fn reconstruct(&self, mempool: &Mempool) -> Result<PartialBlock, CompactError> {
+        let count = self.tx_count();
+        let mut slots: Vec<Option<Transaction>> = vec![None; count];
+        let mut prefilled_slot = vec![false; count];
+
+        for (index, tx) in &self.prefilled {
+            let slot = *index as usize;
+            if slot >= count || prefilled_slot[slot] {
+                return Err(CompactError::BadPrefilled(*index));
+            }
+            prefilled_slot[slot] = true;
+            slots[slot] = Some(tx.clone());
+        }
+
+        // Short IDs fill the remaining slots in order
+        let mut wanted: HashMap<ShortId, usize> = HashMap::new();
+        let free = (0..count).filter(|slot| !prefilled_slot[*slot]);
+        for (short, slot) in self.short_ids.iter().zip(free) {
+            if wanted.insert(*short, slot).is_some() {
+                return Err(CompactError::DuplicateShortIds);
+            }
+        }
+
+        let mut ambiguous = Vec::new();
+        for tx in mempool.transactions() {
+            if let Some(&slot) = wanted.get(&short_id(self.salt, &tx.id)) {
+                if slots[slot].is_some() {
+                    ambiguous.push(slot);
+                }
+                slots[slot] = Some(tx.clone());
+            }
+        }
+        for slot in ambiguous {
+            slots[slot] = None;
+        }
+
+        Ok(PartialBlock { compact: self.clone(), slots })
+    }
+}
+
+/// A compact block being rebuilt
+#[derive(Debug, Clone)]
+pub struct PartialBlock {
+    compact: CompactBlock,
+    slots: Vec<Option<Transaction>>,
+}
+
+impl PartialBlock {
+    pub fn header(&self) -> &BlockHeader {
+        &self.compact.header
+    }
+
+    /// Positions of the transactions still needed, in block order
+    pub fn missing(&self) -> Vec<u32> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| tx.is_none())
+            .map(|(i, _)| i as u32)
+            .collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.slots.iter().all(Option::is_some)
+    }
+
+    /// Fill the missing positions with `transactions` (in `missing()` order)
+    pub fn fill(&mut self, transactions: Vec<Transaction>) -> Result<(), CompactError> {
+        let missing = self.missing();
+        if missing.len() != transactions.len() {
+            return Err(CompactError::WrongTransactionCount {
+                expected: missing.len(),
+                actual: transactions.len(),
+            });
+        }
+
+        let prefilled: Vec<u32> = self.compact.prefilled.iter().map(|(i, _)| *i).collect();
+        for (slot, tx) in missing.into_iter().zip(transactions) {
+            // Position among the short IDs = slot minus prefilled slots before it
+            let before = prefilled.iter().filter(|i| **i < slot).count();
+            let expected = self.compact.short_ids[slot as usize - before];
+            if short_id(self.compact.salt, &tx.id) != expected {
+                return Err(CompactError::ShortIdMismatch(tx.id));
+            }
+            self.slots[slot as usize] = Some(tx);
+        }
+        Ok(())
+    }
+
+    pub fn into_compact(self) -> CompactBlock {
+        self.compact
+    }
+
+    /// The full block, once complete
+    pub fn into_block(self) -> Option<Block> {
+        let transactions = self.slots.into_iter().collect::<Option<Vec<_>>>()?;
+        let header = self.compact.header;
+        Some(Block {
+            index: header.index,
+            prev_hash: header.prev_hash,
+            timestamp: header.timestamp,
+            transactions,
+            nonce: header.nonce,
+            hash: header.hash,
+        })
+    }
+}
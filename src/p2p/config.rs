@@ -0,0 +1,226 @@
+use std::iter::Peekable;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use super::admin::{AdminRequest, ADMIN_SOCKET_DIR, ADMIN_SOCKET_FILE};
+use super::banlist::DEFAULT_BAN_DURATION;
+use super::node_key::NODE_KEY_FILE;
+use crate::blockchain::params::Network;
+use crate::storage::MIN_PRUNE_DEPTH;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("missing value for {0}")]
+    MissingValue(String),
+
+    #[error("invalid value for {flag}: {value}")]
+    InvalidValue { flag: String, value: String },
+
+    #[error("unknown option: {0}")]
+    UnknownOption(String),
+}
+
+/// Network settings for a node, usually parsed from the command line:
+///
+/// ```text
+/// arcnova-node --port 3001 --peers node2:3002,node3:3003 --data-dir /app/data
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeConfig {
+    /// Which chain to join
+    pub network: Network,
+    /// Multiaddrs to listen on
+    pub listen: Vec<String>,
+    /// Static peers (as multiaddrs) that are always dialed and redialed
+    pub peers: Vec<String>,
+    /// Where the chain, known peers and node key are kept
+    pub data_dir: PathBuf,
+    /// Keep only this many recent blocks' raw data; `None` keeps everything
+    pub prune_depth: Option<u64>,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        let network = Network::default();
+        NodeConfig {
+            network,
+            listen: vec![listen_addr(network.params().default_port)],
+            peers: Vec::new(),
+            data_dir: PathBuf::from("data"),
+            prune_depth: None,
+        }
+    }
+}
+
+/// What the `arcnova-node` binary was asked to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeCommand {
+    /// Run the node (no subcommand)
+    Run(NodeConfig),
+    /// Print the node's PeerId, creating its key if needed
+    ShowPeerId(NodeConfig),
+    /// Replace the node key, giving the node a new PeerId
+    RotateKey(NodeConfig),
+    /// Send a request to the running node's control socket
+    Admin(NodeConfig, AdminRequest),
+}
+
+impl NodeCommand {
+    /// Parse an optional subcommand followed by options (without the
+    /// program name). The admin subcommands take their arguments before
+    /// the options: `bans`, `ban PEER [SECONDS]` and `unban PEER`.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, ConfigError> {
+        let mut args = args.into_iter().peekable();
+
+        let command = match args.peek().map(String::as_str) {
+            Some("peer-id") => NodeCommand::ShowPeerId,
+            Some("rotate-key") => NodeCommand::RotateKey,
+            Some("bans" | "ban" | "unban") => {
+                let request = admin_request(&mut args)?;
+                return Ok(NodeCommand::Admin(NodeConfig::from_args(args)?, request));
+            }
+            Some(other) if !other.starts_with("--") => {
+                return Err(ConfigError::UnknownOption(other.to_string()))
+            }
+            _ => return Ok(NodeCommand::Run(NodeConfig::from_args(args)?)),
+        };
+        args.next();
+        Ok(command(NodeConfig::from_args(args)?))
+    }
+}
+
+// An admin subcommand and its arguments
+fn admin_request<I: Iterator<Item = String>>(args: &mut Peekable<I>) -> Result<AdminRequest, ConfigError> {
+    let name = args.next().unwrap_or_default();
+    let mut argument = || args.next_if(|arg| !arg.starts_with("--"));
+
+    if name == "bans" {
+        return Ok(AdminRequest::ListBans);
+    }
+    let peer = argument().ok_or_else(|| ConfigError::MissingValue(name.clone()))?;
+    if name == "unban" {
+        return Ok(AdminRequest::Unban { peer });
+    }
+
+    let duration = match argument() {
+        Some(value) => match value.parse::<u64>() {
+            Ok(seconds) => Duration::from_secs(seconds),
+            Err(_) => return Err(ConfigError::InvalidValue { flag: name, value }),
+        },
+        None => DEFAULT_BAN_DURATION,
+    };
+    Ok(AdminRequest::Ban { peer, duration })
+}
+
+fn listen_addr(port: u16) -> String {
+    format!("/ip4/0.0.0.0/tcp/{}", port)
+}
+
+impl NodeConfig {
+    /// Where this network's chain, peers and key live: the data dir itself
+    /// for mainnet, a subdirectory named after the network otherwise
+    pub fn network_dir(&self) -> PathBuf {
+        match self.network {
+            Network::Mainnet => self.data_dir.clone(),
+            network => self.data_dir.join(network.name()),
+        }
+    }
+
+    /// Location of the node's identity key
+    pub fn node_key_path(&self) -> PathBuf {
+        self.network_dir().join(NODE_KEY_FILE)
+    }
+
+    /// Where the running node listens for admin requests
+    pub fn admin_socket_path(&self) -> PathBuf {
+        self.network_dir().join(ADMIN_SOCKET_DIR).join(ADMIN_SOCKET_FILE)
+    }
+
+    /// Parse options (without the program name).
+    ///
+    /// `--network` picks mainnet (the default), testnet or regtest.
+    /// `--port N` listens on all interfaces; `--listen ADDR` may be given
+    /// several times instead. Without either, the network's default port is
+    /// used. `--peers` takes a comma-separated list of `host:port` pairs or
+    /// multiaddrs. `--prune-depth N` turns on pruning, keeping the raw data
+    /// of the last `N` (at least `MIN_PRUNE_DEPTH`) blocks.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, ConfigError> {
+        let mut config = NodeConfig::default();
+        let mut listen = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or_else(|| ConfigError::MissingValue(flag.clone()));
+
+            match flag.as_str() {
+                "--port" => {
+                    let value = value()?;
+                    let port = value.parse::<u16>().map_err(|_| ConfigError::InvalidValue {
+                        flag: flag.clone(),
+                        value,
+                    })?;
+                    listen.push(listen_addr(port));
+                }
+                "--listen" => listen.push(value()?),
+                "--peers" => {
+                    for peer in value()?.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                        config.peers.push(peer_multiaddr(peer)?);
+                    }
+                }
+                "--data-dir" => config.data_dir = PathBuf::from(value()?),
+                "--network" => {
+                    let value = value()?;
+                    config.network = value.parse().map_err(|_| ConfigError::InvalidValue {
+                        flag: flag.clone(),
+                        value,
+                    })?;
+                }
+                "--prune-depth" => {
+                    let value = value()?;
+                    let depth = value
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|depth| *depth >= MIN_PRUNE_DEPTH)
+                        .ok_or_else(|| ConfigError::InvalidValue { flag: flag.clone(), value })?;
+                    config.prune_depth = Some(depth);
+                }
+                _ => return Err(ConfigError::UnknownOption(flag)),
+            }
+        }
+
+        if listen.is_empty() {
+            listen.push(listen_addr(config.network.params().default_port));
+        }
+        config.listen = listen;
+        Ok(config)
+    }
+}
+
+/// Turn `host:port` into a TCP multiaddr; multiaddrs are passed through.
+///
+/// Hostnames become `/dns4/...` so that names like Docker service names are
+/// resolved when dialing.
+pub fn peer_multiaddr(peer: &str) -> Result<String, ConfigError> {
+    if peer.starts_with('/') {
+        return Ok(peer.to_string());
+    }
+
+    let invalid = || ConfigError::InvalidValue {
+        flag: "--peers".into(),
+        value: peer.to_string(),
+    };
+
+    let (host, port) = peer.rsplit_once(':').ok_or_else(invalid)?;
+    let port: u16 = port.parse().map_err(|_| invalid())?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => Ok(format!("/ip4/{}/tcp/{}", ip, port)),
+        Ok(IpAddr::V6(ip)) => Ok(format!("/ip6/{}/tcp/{}", ip, port)),
+        Err(_) if !host.is_empty() => Ok(format!("/dns4/{}/tcp/{}", host, port)),
+        Err(_) => Err(invalid()),
+    }
+}
@@ -0,0 +1,62 @@
+use super::compact::CompactBlock;
+use crate::blockchain::block::Block;
+use crate::blockchain::transaction::Transaction;
+use crate::crypto::hash::Hash;
+use crate::encoding;
+
+pub use crate::encoding::DecodeError;
+
+// Gossip topic base names; each network subscribes to its own
+// `NetworkParams::topic` of these
+pub const BLOCK_TOPIC_STR: &str = "arcnova-blocks";
+pub const TX_TOPIC_STR: &str = "arcnova-transactions";
+pub const COMPACT_BLOCK_TOPIC_STR: &str = "arcnova-compact-blocks";
+
+/// Largest gossip payload we are willing to decode
+pub const MAX_MESSAGE_SIZE: u64 = 2 * 1024 * 1024;
+
+/// A decoded gossip payload
+#[derive(Debug, Clone)]
+pub enum GossipItem {
+    Block(Block),
+    Transaction(Transaction),
+    CompactBlock(CompactBlock),
+}
+
+fn decode<T: serde::de::DeserializeOwned>(data: &[u8]) -> Result<T, DecodeError> {
+    encoding::decode(data, MAX_MESSAGE_SIZE)
+}
+
+pub fn decode_block(data: &[u8]) -> Result<Block, DecodeError> {
+    decode(data)
+}
+
+pub fn decode_transaction(data: &[u8]) -> Result<Transaction, DecodeError> {
+    decode(data)
+}
+
+pub fn decode_compact_block(data: &[u8]) -> Result<CompactBlock, DecodeError> {
+    decode(data)
+}
+
+pub fn encode_block(block: &Block) -> Vec<u8> {
+    bincode::serialize(block).expect("blocks always serialize")
+}
+
+pub fn encode_transaction(tx: &Transaction) -> Vec<u8> {
+    bincode::serialize(tx).expect("transactions always serialize")
+}
+
+pub fn encode_compact_block(block: &CompactBlock) -> Vec<u8> {
+    bincode::serialize(block).expect("compact blocks always serialize")
+}
+
+/// Gossip message ID: a hash of the payload under its topic, so the same
+/// item is deduplicated whoever published it.
+///
+/// The strict wire format gives every item a single encoding, so hashing
+/// the bytes identifies the item without decoding it twice or trusting
+/// the hash it claims for itself.
+pub fn message_id(topic: &str, data: &[u8]) -> Vec<u8> {
+    [topic.as_bytes(), b":", &Hash::hash(&data).0].concat()
+}
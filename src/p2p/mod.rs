@@ -1,43 +1,111 @@
 // src/p2p/mod.rs
+pub mod admin;
+pub mod banlist;
+pub mod compact;
+pub mod config;
+pub mod messages;
+pub mod node_key;
+pub mod peers;
+pub mod pipeline;
+pub mod protocol;
+pub mod services;
+pub mod sim;
+pub mod sync;
+pub mod txrelay;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
 use libp2p::{
-    core::upgrade::Version,
+    core::{upgrade::Version, ConnectedPoint},
     futures::StreamExt,
     identity, 
-    PeerId, Swarm, 
-    swarm::{NetworkBehaviour, SwarmEvent},
+    multiaddr::Protocol,
+    Multiaddr, PeerId, Swarm, 
+    swarm::{DialError, NetworkBehaviour, SwarmEvent},
     identify, mdns, 
-    floodsub::{Floodsub, FloodsubEvent, Topic},
+    gossipsub::{self, IdentTopic, MessageAcceptance, MessageAuthenticity, MessageId, PeerScoreParams, PeerScoreThresholds, TopicScoreParams, ValidationMode},
+    request_response::{self, ProtocolSupport, ResponseChannel},
 };
+use rand::{rngs::StdRng, SeedableRng};
 use tokio::io;
+use tokio::sync::mpsc;
+
+use crate::blockchain::block::Block;
+use crate::blockchain::chain::Verdict;
+use crate::blockchain::params::NetworkParams;
+use crate::crypto::hash::Hash;
+use admin::{handle_admin_command, AdminCommand};
+use banlist::{unix_now, BanList, Misbehavior};
+use config::NodeConfig;
+use messages::{DecodeError, GossipItem};
+use node_key::NodeKey;
+use peers::PeerBook;
+use protocol::{SyncCodec, SyncProtocol};
+use sync::{SyncError, SyncManager, SyncRequest, SyncResponse};
+use txrelay::TxRelay;
+
+pub use pipeline::{ChainCommand, ChainEvent, Inbound, Validated};
+pub use services::ServiceFlags;
+
+/// Commands and events exchanged with the chain manager task
+pub type NetworkCommand = ChainCommand<PeerId, ResponseChannel<SyncResponse>>;
+pub type NetworkEvent = ChainEvent<PeerId, ResponseChannel<SyncResponse>>;
+
+/// The network loop's connection to the chain manager task
+pub struct ChainHandle {
+    pub commands: mpsc::Sender<NetworkCommand>,
+    pub events: mpsc::Receiver<NetworkEvent>,
+    /// Our chain tip when the loop starts; initial sync resumes from here
+    pub tip: Option<(u64, Hash)>,
+    /// `sync::block_locator` of the chain up to `tip`
+    pub locator: Vec<Hash>,
+}
 
-// Gossip topic constants
-pub const BLOCK_TOPIC_STR: &str = "arcnova-blocks";
-pub const TX_TOPIC_STR: &str = "arcnova-transactions";
+// How often stalled sync requests are checked for
+const SYNC_TICK: Duration = Duration::from_secs(5);
 
-// Helper functions for Topic creation
-pub fn block_topic() -> Topic { Topic::new(BLOCK_TOPIC_STR) }
-pub fn tx_topic() -> Topic { Topic::new(TX_TOPIC_STR) }
+// How often due peer dials are made and the peer book is saved
+const DIAL_TICK: Duration = Duration::from_secs(1);
+
+// How often transaction announcements are trickled out
+const RELAY_TICK: Duration = Duration::from_millis(500);
+
+// Decoded items waiting for the chain manager; more are dropped when full
+pub const INBOUND_QUEUE_SIZE: usize = 1024;
+
+pub use messages::{BLOCK_TOPIC_STR, COMPACT_BLOCK_TOPIC_STR, TX_TOPIC_STR};
+
+// Helper functions for Topic creation; topics are namespaced by network
+pub fn block_topic(params: &NetworkParams) -> IdentTopic { IdentTopic::new(params.topic(BLOCK_TOPIC_STR)) }
+pub fn tx_topic(params: &NetworkParams) -> IdentTopic { IdentTopic::new(params.topic(TX_TOPIC_STR)) }
+pub fn compact_block_topic(params: &NetworkParams) -> IdentTopic { IdentTopic::new(params.topic(COMPACT_BLOCK_TOPIC_STR)) }
+
+// Score lost per invalid message on a topic (squared by gossipsub)
+const INVALID_MESSAGE_WEIGHT: f64 = -10.0;
 
 // Custom Network Behavior combining protocols
 #[derive(NetworkBehaviour)]
 #[behaviour(out_event = "AppBehaviourEvent")]
 pub struct AppBehaviour {
     mdns: mdns::tokio::Behaviour,
-    floodsub: Floodsub, 
+    gossipsub: gossipsub::Behaviour,
     identify: identify::Behaviour,
+    sync: request_response::Behaviour<SyncCodec>,
 }
 
 // Unified event enum
 #[derive(Debug)]
 pub enum AppBehaviourEvent {
-    Floodsub(FloodsubEvent),
+    Gossipsub(gossipsub::Event),
     Mdns(mdns::Event),
     Identify(identify::Event),
+    Sync(request_response::Event<SyncRequest, SyncResponse>),
 }
 
 // Event conversion implementations
-impl From<FloodsubEvent> for AppBehaviourEvent {
-    fn from(event: FloodsubEvent) -> Self { AppBehaviourEvent::Floodsub(event) }
+impl From<gossipsub::Event> for AppBehaviourEvent {
+    fn from(event: gossipsub::Event) -> Self { AppBehaviourEvent::Gossipsub(event) }
 }
 impl From<mdns::Event> for AppBehaviourEvent {
     fn from(event: mdns::Event) -> Self { AppBehaviourEvent::Mdns(event) }
@@ -45,10 +113,27 @@ impl From<mdns::Event> for AppBehaviourEvent {
 impl From<identify::Event> for AppBehaviourEvent {
     fn from(event: identify::Event) -> Self { AppBehaviourEvent::Identify(event) }
 }
+impl From<request_response::Event<SyncRequest, SyncResponse>> for AppBehaviourEvent {
+    fn from(event: request_response::Event<SyncRequest, SyncResponse>) -> Self { AppBehaviourEvent::Sync(event) }
+}
+
+// The libp2p identity for a stored node key
+pub fn node_keypair(key: &NodeKey) -> identity::Keypair {
+    let secret = identity::ed25519::SecretKey::from_bytes(key.secret())
+        .expect("any 32 bytes are a valid ed25519 secret");
+    identity::Keypair::Ed25519(secret.into())
+}
+
+pub fn node_peer_id(key: &NodeKey) -> PeerId {
+    PeerId::from(node_keypair(key).public())
+}
 
 // Network initialization
-pub async fn start_p2p_node() -> Result<Swarm<AppBehaviour>, Box<dyn std::error::Error>> {
-    let local_key = identity::Keypair::generate_ed25519();
+// `key` is the node's persistent identity; `services` is advertised to peers
+// through identify (e.g. whether we are pruned)
+pub async fn start_p2p_node(config: &NodeConfig, key: &NodeKey, services: ServiceFlags) -> Result<Swarm<AppBehaviour>, Box<dyn std::error::Error>> {
+    let params = config.network.params();
+    let local_key = node_keypair(key);
     let local_peer_id = PeerId::from(local_key.public());
     
     let transport = libp2p::development_transport(local_key.clone()).await
@@ -57,56 +142,509 @@ pub async fn start_p2p_node() -> Result<Swarm<AppBehaviour>, Box<dyn std::error:
     let mdns = mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id)
         .expect("Failed mDNS");
     
-    let mut floodsub = Floodsub::new(local_peer_id);
-    
-    floodsub.subscribe(block_topic());
-    floodsub.subscribe(tx_topic());
+    let mut gossipsub = gossipsub::Behaviour::new(
+        MessageAuthenticity::Signed(local_key.clone()),
+        gossipsub_config()?,
+    )?;
+    let (score_params, thresholds) = peer_scoring(params);
+    gossipsub.with_peer_score(score_params, thresholds)?;
+
+    gossipsub.subscribe(&block_topic(params))?;
+    gossipsub.subscribe(&tx_topic(params))?;
+    gossipsub.subscribe(&compact_block_topic(params))?;
 
     let identify = identify::Behaviour::new(
-        identify::Config::new("/arcnova/1.0.0".to_owned(), local_key.public())
-            .with_protocol_version(Version::V1),
+        identify::Config::new(params.identify_protocol(), local_key.public())
+            .with_protocol_version(Version::V1)
+            .with_agent_version(services.agent_version()),
+    );
+
+    let sync = request_response::Behaviour::new(
+        SyncCodec::for_network(params),
+        std::iter::once((SyncProtocol::for_network(params), ProtocolSupport::Full)),
+        request_response::Config::default(),
     );
 
     let mut swarm = Swarm::new(
         transport, 
-        AppBehaviour { mdns, floodsub, identify }, 
+        AppBehaviour { mdns, gossipsub, identify, sync }, 
         local_peer_id
     );
 
-    swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?)?;
+    for addr in &config.listen {
+        swarm.listen_on(addr.parse()?)?;
+    }
 
-    println!("Local Peer ID: {}", local_peer_id);
+    println!("Local Peer ID: {} ({})", local_peer_id, params.name);
     
     Ok(swarm)
 }
 
+// Messages are held back until the chain manager has validated them, and
+// deduplicated by a hash of their payload
+fn gossipsub_config() -> Result<gossipsub::Config, &'static str> {
+    gossipsub::ConfigBuilder::default()
+        .validation_mode(ValidationMode::Strict)
+        .validate_messages()
+        .max_transmit_size(messages::MAX_MESSAGE_SIZE as usize)
+        .message_id_fn(|message: &gossipsub::Message| {
+            MessageId::from(messages::message_id(message.topic.as_str(), &message.data))
+        })
+        .build()
+}
+
+// Peers lose score for every message we reject; low scorers stop receiving
+// gossip from us and are eventually ignored altogether
+fn peer_scoring(network: &NetworkParams) -> (PeerScoreParams, PeerScoreThresholds) {
+    let mut params = PeerScoreParams::default();
+    for topic in [block_topic(network), tx_topic(network), compact_block_topic(network)] {
+        params.topics.insert(
+            topic.hash(),
+            TopicScoreParams {
+                invalid_message_deliveries_weight: INVALID_MESSAGE_WEIGHT,
+                // Small networks may be quiet for long stretches; do not
+                // penalise peers for not delivering enough
+                mesh_message_deliveries_weight: 0.0,
+                mesh_failure_penalty_weight: 0.0,
+                ..TopicScoreParams::default()
+            },
+        );
+    }
+
+    let thresholds = PeerScoreThresholds {
+        gossip_threshold: -10.0,
+        publish_threshold: -50.0,
+        graylist_threshold: -80.0,
+        ..PeerScoreThresholds::default()
+    };
+
+    (params, thresholds)
+}
+
 // Main network event loop
-pub async fn run_p2p_event_loop(mut swarm: Swarm<AppBehaviour>) {
+// Gossiped blocks/transactions are decoded here and sent to the chain manager
+// as commands; its verdicts come back as events. Initial block download runs
+// alongside over the sync protocol with every peer that advertises services.
+// Addresses in `peers` are dialed and redialed with backoff. Misbehaving
+// peers are scored and banned through `bans`, which operators can also
+// manage over the `admin` channel. Peers identifying as nodes of another
+// network than `params` are disconnected.
+pub async fn run_p2p_event_loop(
+    mut swarm: Swarm<AppBehaviour>,
+    params: &NetworkParams,
+    chain: ChainHandle,
+    mut peers: PeerBook,
+    mut bans: BanList,
+    mut admin: mpsc::Receiver<AdminCommand>,
+) {
+    let ChainHandle { commands, mut events, tip, locator } = chain;
+
+    // What each connected peer told us it can serve
+    let mut peer_services: HashMap<PeerId, ServiceFlags> = HashMap::new();
+
+    let mut sync: SyncManager<PeerId> = SyncManager::new(tip);
+    sync.set_chain_locator(locator);
+    // Downloaded blocks waiting to be handed to the chain manager, which
+    // gets one batch at a time
+    let mut ready: Vec<Block> = Vec::new();
+    let mut batch_in_progress = false;
+    let mut tick = tokio::time::interval(SYNC_TICK);
+    let mut dial_tick = tokio::time::interval(DIAL_TICK);
+
+    let mut tx_relay: TxRelay<PeerId> = TxRelay::default();
+    let mut rng = StdRng::from_entropy();
+    let mut relay_tick = tokio::time::interval(RELAY_TICK);
+
     loop {
         tokio::select! {
             event = swarm.select_next_some() => match event {
                 SwarmEvent::NewListenAddr { address, .. } => {
                     println!("Listening on: {:?}", address);
                 }
-                SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if bans.is_banned(&peer_id.to_string(), unix_now()) => {
+                    println!("Disconnecting banned peer {}", peer_id);
+                    let _ = swarm.disconnect_peer_id(peer_id);
+                }
+                SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
                     println!("Connection established with: {}", peer_id);
+                    if let ConnectedPoint::Dialer { address, .. } = endpoint {
+                        peers.connected(&address.to_string());
+                    }
+                }
+                SwarmEvent::OutgoingConnectionError { error: DialError::Transport(failed), .. } => {
+                    // Other dial errors are caught by the peer book's dial timeout
+                    for (address, _) in failed {
+                        peers.failed(&address.to_string(), Instant::now());
+                    }
                 }
                 SwarmEvent::Behaviour(AppBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
                     for (peer_id, addr) in list {
-                        swarm.behaviour_mut().floodsub.add_node_to_partial_view(peer_id);
+                        if !swarm.is_connected(&peer_id) && !bans.is_banned(&peer_id.to_string(), unix_now()) {
+                            let _ = swarm.dial(addr);
+                        }
                     }
                 }
-                SwarmEvent::Behaviour(AppBehaviourEvent::Floodsub(FloodsubEvent::Message(msg))) => {
-                    if msg.topic.to_string() == BLOCK_TOPIC_STR {
-                        // TODO: Deserialize and validate block
-                        println!("Received new BLOCK ({} bytes)", msg.data.len());
-                    } else if msg.topic.to_string() == TX_TOPIC_STR {
-                        // TODO: Deserialize and add transaction to mempool
-                        println!("Received new TRANSACTION ({} bytes)", msg.data.len());
+                SwarmEvent::Behaviour(AppBehaviourEvent::Identify(identify::Event::Received { peer_id, info }))
+                    if info.protocol_version != params.identify_protocol() =>
+                {
+                    println!("Disconnecting {}: on another network ({})", peer_id, info.protocol_version);
+                    let _ = swarm.disconnect_peer_id(peer_id);
+                }
+                SwarmEvent::Behaviour(AppBehaviourEvent::Identify(identify::Event::Received { peer_id, info })) => {
+                    // Only arcnova nodes advertise services; only they can serve sync
+                    if let Some(services) = ServiceFlags::from_agent_version(&info.agent_version) {
+                        if services.is_pruned() {
+                            println!("Peer {} is pruned (recent blocks only)", peer_id);
+                        }
+                        peer_services.insert(peer_id, services);
+                        for address in info.listen_addrs.into_iter().filter(is_shareable) {
+                            let address = address.with(Protocol::P2p(peer_id.into()));
+                            peers.learn(address.to_string(), Instant::now());
+                        }
+                        if !sync.has_peer(&peer_id) {
+                            let requests = sync.add_peer(peer_id, !services.is_pruned());
+                            send_sync_requests(&mut swarm, requests);
+                        }
+                        tx_relay.add_peer(peer_id, Instant::now(), &mut rng);
                     }
                 }
+                SwarmEvent::ConnectionClosed { peer_id, endpoint, num_established, .. } => {
+                    if let ConnectedPoint::Dialer { address, .. } = endpoint {
+                        peers.disconnected(&address.to_string(), Instant::now());
+                    }
+                    if num_established == 0 {
+                        peer_services.remove(&peer_id);
+                        tx_relay.remove_peer(&peer_id);
+                        let requests = sync.remove_peer(&peer_id);
+                        send_sync_requests(&mut swarm, requests);
+                    }
+                }
+                SwarmEvent::Behaviour(AppBehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                    propagation_source,
+                    message_id,
+                    message,
+                })) => {
+                    let decoded = if message.topic == block_topic(params).hash() {
+                        Some(messages::decode_block(&message.data).map(GossipItem::Block))
+                    } else if message.topic == tx_topic(params).hash() {
+                        Some(messages::decode_transaction(&message.data).map(GossipItem::Transaction))
+                    } else if message.topic == compact_block_topic(params).hash() {
+                        Some(messages::decode_compact_block(&message.data).map(GossipItem::CompactBlock))
+                    } else {
+                        None
+                    };
+
+                    // Anything not handed to the chain manager is settled here
+                    let acceptance = match decoded {
+                        Some(Ok(item)) => {
+                            let queued = commands.try_send(ChainCommand::Gossip(Inbound {
+                                source: propagation_source,
+                                id: message_id.0.clone(),
+                                item,
+                            }));
+                            if queued.is_err() {
+                                println!("Validation queue full, dropping message from {}", propagation_source);
+                                Some(MessageAcceptance::Ignore)
+                            } else {
+                                None
+                            }
+                        }
+                        Some(Err(e)) => {
+                            println!("Dropping malformed message from {}: {}", propagation_source, e);
+                            let kind = match e {
+                                DecodeError::TooLarge(_) => Misbehavior::OversizedMessage,
+                                DecodeError::Malformed(_) => Misbehavior::MalformedMessage,
+                            };
+                            punish(&mut swarm, &mut bans, propagation_source, kind);
+                            Some(MessageAcceptance::Reject)
+                        }
+                        None => Some(MessageAcceptance::Ignore),
+                    };
+
+                    if let Some(acceptance) = acceptance {
+                        let _ = swarm.behaviour_mut().gossipsub.report_message_validation_result(
+                            &message_id,
+                            &propagation_source,
+                            acceptance,
+                        );
+                    }
+                }
+                SwarmEvent::Behaviour(AppBehaviourEvent::Sync(event)) => {
+                    handle_sync_event(&mut swarm, &mut sync, &mut tx_relay, &mut bans, &commands, event);
+                    ready.extend(sync.drain_ready());
+                }
                 _ => {}
             },
+            Some(event) = events.recv() => match event {
+                ChainEvent::Validated(validated) => relay(&mut swarm, &mut tx_relay, &mut bans, validated),
+                ChainEvent::Served { channel, response } => {
+                    // Fails only if the requester has gone away
+                    let _ = swarm.behaviour_mut().sync.send_response(channel, response);
+                }
+                ChainEvent::MissingTransactions { source, block_hash, indexes } => {
+                    let request = SyncRequest::GetBlockTxn { block_hash, indexes };
+                    swarm.behaviour_mut().sync.send_request(&source, request);
+                }
+                ChainEvent::SyncProcessed { rejected, tip, locator } => {
+                    batch_in_progress = false;
+                    sync.set_chain_locator(locator);
+                    if let Some(hash) = rejected {
+                        println!("Synced block {} was rejected, restarting sync", hex::encode(&hash.0));
+                        ready.clear();
+                        let requests = sync.reset(tip);
+                        send_sync_requests(&mut swarm, requests);
+                    } else if sync.is_synced() && ready.is_empty() {
+                        println!("Initial sync complete at height {:?}", tip.map(|(height, _)| height));
+                    }
+                }
+            },
+            Some(command) = admin.recv() => {
+                if let Some(peer) = handle_admin_command(&mut bans, command) {
+                    if let Ok(peer) = peer.parse::<PeerId>() {
+                        let _ = swarm.disconnect_peer_id(peer);
+                    }
+                }
+            }
+            _ = relay_tick.tick() => {
+                let now = Instant::now();
+                for (peer, txids) in tx_relay.trickle(now, &mut rng) {
+                    swarm.behaviour_mut().sync.send_request(&peer, SyncRequest::TxInv { txids });
+                }
+                for (peer, txids) in tx_relay.timed_out(now) {
+                    swarm.behaviour_mut().sync.send_request(&peer, SyncRequest::GetTxs { txids });
+                }
+            }
+            _ = dial_tick.tick() => {
+                dial_due_peers(&mut swarm, &mut peers);
+                if let Err(e) = peers.save() {
+                    println!("Failed to save peer addresses: {}", e);
+                }
+                bans.expire(unix_now());
+                if let Err(e) = bans.save() {
+                    println!("Failed to save ban list: {}", e);
+                }
+            }
+            _ = tick.tick() => {
+                for peer in sync.timed_out(Instant::now()) {
+                    println!("Sync peer {} stalled, requesting its blocks elsewhere", peer);
+                    let requests = sync.remove_peer(&peer);
+                    send_sync_requests(&mut swarm, requests);
+                }
+            }
+        }
+
+        if !batch_in_progress && !ready.is_empty() {
+            let blocks = std::mem::take(&mut ready);
+            match commands.try_send(ChainCommand::Synced { blocks }) {
+                Ok(()) => batch_in_progress = true,
+                // Queue is busy with gossip; retry on the next event
+                Err(mpsc::error::TrySendError::Full(ChainCommand::Synced { blocks })) => ready = blocks,
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+// Add to a peer's misbehavior score, disconnecting it once banned
+fn punish(swarm: &mut Swarm<AppBehaviour>, bans: &mut BanList, peer: PeerId, kind: Misbehavior) {
+    if bans.misbehaving(&peer.to_string(), kind, unix_now()) {
+        println!("Banning peer {} ({:?})", peer, kind);
+        let _ = swarm.disconnect_peer_id(peer);
+    }
+}
+
+fn dial_due_peers(swarm: &mut Swarm<AppBehaviour>, peers: &mut PeerBook) {
+    let now = Instant::now();
+    for address in peers.due(now) {
+        let multiaddr: Multiaddr = match address.parse() {
+            Ok(multiaddr) => multiaddr,
+            Err(_) => {
+                peers.failed(&address, now);
+                continue;
+            }
+        };
+
+        // Learned addresses carry the peer ID; no need to dial a peer we
+        // are already connected to
+        if let Some(Protocol::P2p(hash)) = multiaddr.iter().last() {
+            if matches!(PeerId::from_multihash(hash), Ok(peer) if swarm.is_connected(&peer)) {
+                peers.connected(&address);
+                continue;
+            }
+        }
+
+        if let Err(e) = swarm.dial(multiaddr) {
+            println!("Failed to dial {}: {}", address, e);
+            peers.failed(&address, now);
+        }
+    }
+}
+
+// Addresses worth passing on to other nodes: not loopback or unspecified
+fn is_shareable(address: &Multiaddr) -> bool {
+    address.iter().all(|protocol| match protocol {
+        Protocol::Ip4(ip) => !ip.is_loopback() && !ip.is_unspecified(),
+        Protocol::Ip6(ip) => !ip.is_loopback() && !ip.is_unspecified(),
+        _ => true,
+    })
+}
+
+fn send_sync_requests(swarm: &mut Swarm<AppBehaviour>, requests: Vec<(PeerId, SyncRequest)>) {
+    for (peer, request) in requests {
+        swarm.behaviour_mut().sync.send_request(&peer, request);
+    }
+}
+
+fn handle_sync_event(
+    swarm: &mut Swarm<AppBehaviour>,
+    sync: &mut SyncManager<PeerId>,
+    tx_relay: &mut TxRelay<PeerId>,
+    bans: &mut BanList,
+    commands: &mpsc::Sender<NetworkCommand>,
+    event: request_response::Event<SyncRequest, SyncResponse>,
+) {
+    match event {
+        request_response::Event::Message { peer, message } => match message {
+            // Announcements are answered here; we fetch what we have not seen
+            request_response::Message::Request { request: SyncRequest::TxInv { txids }, channel, .. } => {
+                let _ = swarm.behaviour_mut().sync.send_response(channel, SyncResponse::Ack);
+                let wanted = tx_relay.on_inv(&peer, txids, Instant::now());
+                if !wanted.is_empty() {
+                    swarm.behaviour_mut().sync.send_request(&peer, SyncRequest::GetTxs { txids: wanted });
+                }
+            }
+            request_response::Message::Request { request, channel, .. } => {
+                // Peers may only fetch transactions we announced to them
+                let request = match request {
+                    SyncRequest::GetTxs { txids } => SyncRequest::GetTxs {
+                        txids: tx_relay.on_getdata(&peer, txids, Instant::now()),
+                    },
+                    request => request,
+                };
+                if commands.try_send(ChainCommand::Serve { request, channel }).is_err() {
+                    println!("Validation queue full, dropping sync request from {}", peer);
+                }
+            }
+            request_response::Message::Response { response, .. } => {
+                let result = match response {
+                    SyncResponse::Headers(headers) => sync.on_headers(&peer, headers),
+                    SyncResponse::Blocks(blocks) => sync.on_blocks(&peer, blocks),
+                    SyncResponse::BlockTxn { block_hash, transactions } => {
+                        let command = ChainCommand::BlockTxn { source: peer, block_hash, transactions };
+                        if commands.try_send(command).is_err() {
+                            println!("Validation queue full, dropping block transactions from {}", peer);
+                        }
+                        return;
+                    }
+                    SyncResponse::Txs(transactions) => {
+                        for tx in transactions {
+                            // Unrequested transactions are dropped
+                            if !tx_relay.on_tx(&peer, &tx.id) {
+                                continue;
+                            }
+                            let inbound = Inbound { source: peer, id: Vec::new(), item: GossipItem::Transaction(tx) };
+                            if commands.try_send(ChainCommand::Gossip(inbound)).is_err() {
+                                println!("Validation queue full, dropping transaction from {}", peer);
+                            }
+                        }
+                        return;
+                    }
+                    SyncResponse::Ack => return,
+                };
+                let requests = match result {
+                    Ok(requests) => requests,
+                    Err(e) => {
+                        println!("Dropping sync peer {}: {}", peer, e);
+                        // Headers that do not connect only show the peer is on
+                        // another branch than our locator describes
+                        if e != SyncError::UnconnectedHeaders {
+                            punish(swarm, bans, peer, Misbehavior::InvalidSyncResponse);
+                        }
+                        sync.remove_peer(&peer)
+                    }
+                };
+                send_sync_requests(swarm, requests);
+            }
+        },
+        request_response::Event::OutboundFailure { peer, error, .. } => {
+            println!("Sync request to {} failed: {}", peer, error);
+            let requests = sync.remove_peer(&peer);
+            send_sync_requests(swarm, requests);
         }
+        request_response::Event::InboundFailure { .. } | request_response::Event::ResponseSent { .. } => {}
     }
-}
\ No newline at end of file
+}
+
+// Announce a block we produced as a compact block; peers fetch any
+// transactions missing from their mempools over the sync protocol
+pub fn publish_block(
+    swarm: &mut Swarm<AppBehaviour>,
+    params: &NetworkParams,
+    block: &Block,
+) -> Result<(), gossipsub::PublishError> {
+    let compact = compact::CompactBlock::from_block(block, rand::random());
+    swarm
+        .behaviour_mut()
+        .gossipsub
+        .publish(compact_block_topic(params), messages::encode_compact_block(&compact))
+        .map(|_| ())
+}
+
+// Act on the chain manager's verdict.
+// Gossipsub holds each message until it is reported here: accepted blocks are
+// forwarded to the mesh, rejected items count against the peer's score and
+// ignored ones are dropped without penalty. Accepted transactions are
+// announced through `TxRelay` instead of being forwarded.
+fn relay(
+    swarm: &mut Swarm<AppBehaviour>,
+    tx_relay: &mut TxRelay<PeerId>,
+    bans: &mut BanList,
+    validated: Validated<PeerId>,
+) {
+    let (kind, misbehavior) = match &validated.item {
+        GossipItem::Block(_) | GossipItem::CompactBlock(_) => ("block", Misbehavior::InvalidBlock),
+        GossipItem::Transaction(_) => ("transaction", Misbehavior::InvalidTransaction),
+    };
+
+    let is_transaction = match &validated.item {
+        GossipItem::Transaction(tx) if validated.verdict == Verdict::Accept => {
+            tx_relay.announce(tx.id.clone());
+            true
+        }
+        GossipItem::Transaction(tx) => {
+            tx_relay.mark_seen(tx.id.clone());
+            true
+        }
+        _ => false,
+    };
+
+    let acceptance = match validated.verdict {
+        // Transactions spread by announcement rather than by flooding
+        Verdict::Accept if is_transaction => {
+            println!("Accepted {} from {}", kind, validated.source);
+            MessageAcceptance::Ignore
+        }
+        Verdict::Accept => {
+            println!("Accepted {} from {}", kind, validated.source);
+            MessageAcceptance::Accept
+        }
+        Verdict::Reject => {
+            println!("Rejected invalid {} from {}", kind, validated.source);
+            punish(swarm, bans, validated.source, misbehavior);
+            MessageAcceptance::Reject
+        }
+        Verdict::Ignore => MessageAcceptance::Ignore,
+    };
+
+    // Items fetched directly have no gossip message to report on
+    if validated.id.is_empty() {
+        return;
+    }
+
+    // Fails only if the message already expired from the gossip cache
+    let _ = swarm.behaviour_mut().gossipsub.report_message_validation_result(
+        &MessageId::new(&validated.id),
+        &validated.source,
+        acceptance,
+    );
+}
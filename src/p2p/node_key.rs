@@ -0,0 +1,88 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::storage::write_private;
+
+/// File in the data directory holding the node's identity key
+pub const NODE_KEY_FILE: &str = "node_key";
+
+/// Secret of the node's ed25519 network identity, from which its PeerId is
+/// derived. Kept in the data directory so the PeerId survives restarts.
+///
+/// Stored as 64 hex characters; the file is only readable by its owner.
+#[derive(Clone, PartialEq, Eq)]
+pub struct NodeKey {
+    secret: [u8; 32],
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid node key: {}", msg))
+}
+
+impl NodeKey {
+    pub fn generate() -> Self {
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        NodeKey { secret }
+    }
+
+    pub fn secret(&self) -> [u8; 32] {
+        self.secret
+    }
+
+    /// Read the key at `path`; `None` if there is none yet
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Option<Self>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let bytes = hex::decode(text.trim()).map_err(|_| invalid("not hex"))?;
+        let secret: [u8; 32] = bytes.try_into().map_err(|_| invalid("wrong length"))?;
+        Ok(Some(NodeKey { secret }))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        write_private(&tmp, format!("{}\n", hex::encode(self.secret)).as_bytes())?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Load the key at `path`, creating and saving a new one on first start
+    pub fn load_or_generate(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        if let Some(key) = NodeKey::load(path)? {
+            return Ok(key);
+        }
+
+        let key = NodeKey::generate();
+        key.save(path)?;
+        Ok(key)
+    }
+
+    /// Replace the key at `path` with a new one. The previous key is kept
+    /// next to it with an `.old` extension.
+    pub fn rotate(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        if let Some(old) = NodeKey::load(path)? {
+            old.save(path.with_extension("old"))?;
+        }
+
+        let key = NodeKey::generate();
+        key.save(path)?;
+        Ok(key)
+    }
+}
+
+// Never print the secret
+impl std::fmt::Debug for NodeKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("NodeKey(..)")
+    }
+}
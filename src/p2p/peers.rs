@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Delay before the first redial; doubles with every failure
+pub const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+pub const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// A dial that has not succeeded or failed by then counts as failed
+pub const DIAL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Learned addresses are forgotten after this many failures in a row;
+/// static peers are retried forever
+pub const MAX_LEARNED_FAILURES: u32 = 8;
+
+/// Most learned addresses kept
+pub const MAX_KNOWN_ADDRESSES: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DialState {
+    Idle { next_dial: Instant },
+    Dialing { since: Instant },
+    Connected,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    is_static: bool,
+    failures: u32,
+    state: DialState,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PeerFile {
+    version: u32,
+    addresses: Vec<String>,
+}
+
+const PEER_FILE_VERSION: u32 = 1;
+
+/// Addresses this node dials: the configured static peers plus addresses
+/// learned from peers, which are saved so they survive restarts.
+///
+/// Addresses are plain multiaddr strings. The book only decides *when* to
+/// dial; the network loop performs the dials and reports the outcome.
+pub struct PeerBook {
+    path: Option<PathBuf>,
+    entries: HashMap<String, Entry>,
+    dirty: bool,
+}
+
+fn backoff(failures: u32) -> Duration {
+    let factor = 1u32 << failures.saturating_sub(1).min(16);
+    (INITIAL_BACKOFF * factor).min(MAX_BACKOFF)
+}
+
+impl PeerBook {
+    /// An empty book that is never saved
+    pub fn in_memory() -> Self {
+        PeerBook { path: None, entries: HashMap::new(), dirty: false }
+    }
+
+    /// Load learned addresses from `path`; a missing file is an empty book
+    pub fn open(path: impl AsRef<Path>, now: Instant) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut book = PeerBook { path: Some(path.clone()), ..PeerBook::in_memory() };
+
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(book),
+            Err(e) => return Err(e),
+        };
+        let file: PeerFile = serde_json::from_slice(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if file.version != PEER_FILE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported peer file version {}", file.version),
+            ));
+        }
+
+        for addr in file.addresses {
+            book.learn(addr, now);
+        }
+        book.dirty = false;
+        Ok(book)
+    }
+
+    /// Write learned and static addresses if anything changed since the last save
+    pub fn save(&mut self) -> io::Result<()> {
+        let path = match (&self.path, self.dirty) {
+            (Some(path), true) => path,
+            _ => return Ok(()),
+        };
+
+        let mut addresses: Vec<String> = self.entries.keys().cloned().collect();
+        addresses.sort();
+        let bytes = serde_json::to_vec_pretty(&PeerFile { version: PEER_FILE_VERSION, addresses })
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, path)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, addr: &str) -> bool {
+        self.entries.contains_key(addr)
+    }
+
+    pub fn is_connected(&self, addr: &str) -> bool {
+        matches!(self.entries.get(addr), Some(Entry { state: DialState::Connected, .. }))
+    }
+
+    /// Add a configured peer, dialed right away and never forgotten
+    pub fn add_static(&mut self, addr: String, now: Instant) {
+        let entry = self.entries.entry(addr).or_insert(Entry {
+            is_static: true,
+            failures: 0,
+            state: DialState::Idle { next_dial: now },
+        });
+        entry.is_static = true;
+        self.dirty = true;
+    }
+
+    /// Remember an address a peer told us about. Returns false if it was
+    /// already known or the book is full.
+    pub fn learn(&mut self, addr: String, now: Instant) -> bool {
+        if self.entries.contains_key(&addr) || self.entries.len() >= MAX_KNOWN_ADDRESSES {
+            return false;
+        }
+
+        self.entries.insert(
+            addr,
+            Entry { is_static: false, failures: 0, state: DialState::Idle { next_dial: now } },
+        );
+        self.dirty = true;
+        true
+    }
+
+    /// Addresses to dial now; they stay "dialing" until an outcome is reported
+    pub fn due(&mut self, now: Instant) -> Vec<String> {
+        // Dials that never reported back count as failures
+        let stalled: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, e)| matches!(e.state, DialState::Dialing { since } if now.duration_since(since) >= DIAL_TIMEOUT))
+            .map(|(addr, _)| addr.clone())
+            .collect();
+        for addr in stalled {
+            self.failed(&addr, now);
+        }
+
+        let mut due = Vec::new();
+        for (addr, entry) in self.entries.iter_mut() {
+            if let DialState::Idle { next_dial } = entry.state {
+                if next_dial <= now {
+                    entry.state = DialState::Dialing { since: now };
+                    due.push(addr.clone());
+                }
+            }
+        }
+        due.sort();
+        due
+    }
+
+    /// A connection to `addr` was established (by us or by the peer)
+    pub fn connected(&mut self, addr: &str) {
+        if let Some(entry) = self.entries.get_mut(addr) {
+            entry.failures = 0;
+            entry.state = DialState::Connected;
+        }
+    }
+
+    /// The last connection to `addr` closed; redial after the first backoff
+    pub fn disconnected(&mut self, addr: &str, now: Instant) {
+        if let Some(entry) = self.entries.get_mut(addr) {
+            entry.state = DialState::Idle { next_dial: now + INITIAL_BACKOFF };
+        }
+    }
+
+    /// Dialing `addr` failed; back off exponentially, or forget a learned
+    /// address that keeps failing
+    pub fn failed(&mut self, addr: &str, now: Instant) {
+        let entry = match self.entries.get_mut(addr) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        entry.failures += 1;
+        if !entry.is_static && entry.failures >= MAX_LEARNED_FAILURES {
+            self.entries.remove(addr);
+            self.dirty = true;
+            return;
+        }
+        entry.state = DialState::Idle { next_dial: now + backoff(entry.failures) };
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+
+use tokio::sync::mpsc;
+
+use super::compact::{CompactBlock, PartialBlock};
+use super::messages::GossipItem;
+use super::sync::{block_locator, serve_request, SyncRequest, SyncResponse};
+use crate::blockchain::block::{Block, BlockHeader};
+use crate::blockchain::chain::{ChainManager, Verdict};
+use crate::blockchain::transaction::Transaction;
+use crate::crypto::hash::Hash;
+use crate::storage::Store;
+
+/// A decoded item handed from the network loop to the chain manager.
+/// `P` identifies the peer it came from.
+#[derive(Debug)]
+pub struct Inbound<P> {
+    pub source: P,
+    /// Gossip message ID, echoed back with the verdict; empty for items
+    /// fetched directly from the peer
+    pub id: Vec<u8>,
+    pub item: GossipItem,
+}
+
+/// The chain manager's answer for an `Inbound` item
+#[derive(Debug)]
+pub struct Validated<P> {
+    pub source: P,
+    pub id: Vec<u8>,
+    pub item: GossipItem,
+    pub verdict: Verdict,
+}
+
+/// Work sent from the network loop to the chain manager.
+/// `C` is whatever the network needs to answer a sync request.
+#[derive(Debug)]
+pub enum ChainCommand<P, C> {
+    Gossip(Inbound<P>),
+    /// Blocks downloaded during initial sync, in chain order
+    Synced { blocks: Vec<Block> },
+    /// A peer's sync request to answer from our chain
+    Serve { request: SyncRequest, channel: C },
+    /// Transactions a peer sent to complete a compact block it announced
+    BlockTxn { source: P, block_hash: Hash, transactions: Vec<Transaction> },
+}
+
+/// Results reported back to the network loop
+#[derive(Debug)]
+pub enum ChainEvent<P, C> {
+    Validated(Validated<P>),
+    /// `rejected` is the first synced block that failed validation; the
+    /// blocks after it were not processed. `locator` locates the chain
+    /// up to `tip` (see `block_locator`)
+    SyncProcessed { rejected: Option<Hash>, tip: Option<(u64, Hash)>, locator: Vec<Hash> },
+    Served { channel: C, response: SyncResponse },
+    /// A compact block from `source` needs these transactions (by position
+    /// in the block) before it can be validated
+    MissingTransactions { source: P, block_hash: Hash, indexes: Vec<u32> },
+}
+
+/// Most compact blocks waiting for missing transactions at once
+pub const MAX_PENDING_COMPACT_BLOCKS: usize = 8;
+
+// A compact block waiting for the transactions we asked its sender for
+struct PendingBlock<P> {
+    source: P,
+    id: Vec<u8>,
+    partial: PartialBlock,
+}
+
+/// Validate items from the network loop and report the outcome of each.
+///
+/// Runs until the command channel closes; a closed event channel only
+/// stops the reports, not the validation.
+pub async fn run_chain_manager<S: Store, P: Clone + PartialEq, C>(
+    mut manager: ChainManager<S>,
+    mut commands: mpsc::Receiver<ChainCommand<P, C>>,
+    events: mpsc::Sender<ChainEvent<P, C>>,
+) -> ChainManager<S> {
+    // Oldest first; at most MAX_PENDING_COMPACT_BLOCKS, so a scan is cheap
+    let mut pending: VecDeque<(Hash, PendingBlock<P>)> = VecDeque::new();
+
+    while let Some(command) = commands.recv().await {
+        let event = match command {
+            ChainCommand::Gossip(Inbound { source, id, item: GossipItem::CompactBlock(compact) }) => {
+                process_compact_block(&mut manager, &mut pending, source, id, compact)
+            }
+            ChainCommand::Gossip(Inbound { source, id, item }) => {
+                let verdict = match &item {
+                    GossipItem::Block(block) => manager.process_block(block.clone()),
+                    GossipItem::Transaction(tx) => manager.process_transaction(tx.clone()),
+                    GossipItem::CompactBlock(_) => unreachable!("handled above"),
+                };
+                ChainEvent::Validated(Validated { source, id, item, verdict })
+            }
+            ChainCommand::BlockTxn { source, block_hash, transactions } => {
+                // Only the peer we asked can complete the block
+                let position = pending
+                    .iter()
+                    .position(|(hash, entry)| *hash == block_hash && entry.source == source);
+                match position.and_then(|position| pending.remove(position)) {
+                    Some((_, entry)) => complete_block(&mut manager, entry, transactions),
+                    None => continue,
+                }
+            }
+            ChainCommand::Synced { blocks } => {
+                let mut rejected = None;
+                for block in blocks {
+                    let hash = block.hash.clone();
+                    if manager.process_block(block) == Verdict::Reject {
+                        rejected = Some(hash);
+                        break;
+                    }
+                }
+                let tip = manager.state.utxo_set.tip().ok().flatten();
+                let locator = block_locator(&manager.state.utxo_set);
+                ChainEvent::SyncProcessed { rejected, tip, locator }
+            }
+            ChainCommand::Serve { request, channel } => {
+                let response = match &request {
+                    SyncRequest::GetTxs { txids } => SyncResponse::Txs(
+                        txids.iter().filter_map(|txid| manager.mempool.get(txid).cloned()).collect(),
+                    ),
+                    _ => serve_request(&manager.state.utxo_set, &request),
+                };
+                ChainEvent::Served { channel, response }
+            }
+        };
+
+        let _ = events.send(event).await;
+    }
+
+    manager
+}
+
+// Whether a block with this header would extend our current tip
+fn builds_on_tip<S: Store>(manager: &ChainManager<S>, header: &BlockHeader) -> bool {
+    match manager.state.utxo_set.tip() {
+        Ok(Some((_, tip))) => tip == header.prev_hash,
+        Ok(None) => header.index == 0,
+        Err(_) => false,
+    }
+}
+
+fn process_compact_block<S: Store, P: Clone, C>(
+    manager: &mut ChainManager<S>,
+    pending: &mut VecDeque<(Hash, PendingBlock<P>)>,
+    source: P,
+    id: Vec<u8>,
+    compact: CompactBlock,
+) -> ChainEvent<P, C> {
+    let block_hash = compact.header.hash.clone();
+
+    // Duplicates, orphans and blocks already being fetched need no round-trip
+    if !builds_on_tip(manager, &compact.header) || pending.iter().any(|(hash, _)| *hash == block_hash) {
+        let item = GossipItem::CompactBlock(compact);
+        return ChainEvent::Validated(Validated { source, id, item, verdict: Verdict::Ignore });
+    }
+
+    let partial = match compact.reconstruct(&manager.mempool) {
+        Ok(partial) => partial,
+        Err(_) => {
+            let item = GossipItem::CompactBlock(compact);
+            return ChainEvent::Validated(Validated { source, id, item, verdict: Verdict::Reject });
+        }
+    };
+
+    let entry = PendingBlock { source: source.clone(), id, partial };
+    if entry.partial.is_complete() {
+        return complete_block(manager, entry, Vec::new());
+    }
+
+    // Make room by dropping the oldest entry
+    if pending.len() >= MAX_PENDING_COMPACT_BLOCKS {
+        pending.pop_front();
+    }
+
+    let indexes = entry.partial.missing();
+    pending.push_back((block_hash.clone(), entry));
+    ChainEvent::MissingTransactions { source, block_hash, indexes }
+}
+
+// Fill in the fetched transactions and validate the finished block
+fn complete_block<S: Store, P, C>(
+    manager: &mut ChainManager<S>,
+    entry: PendingBlock<P>,
+    transactions: Vec<Transaction>,
+) -> ChainEvent<P, C> {
+    let PendingBlock { source, id, mut partial } = entry;
+
+    if partial.fill(transactions).is_err() {
+        let item = GossipItem::CompactBlock(partial.into_compact());
+        return ChainEvent::Validated(Validated { source, id, item, verdict: Verdict::Reject });
+    }
+    let block = partial.into_block().expect("filled blocks are complete");
+
+    let verdict = manager.process_block(block.clone());
+    ChainEvent::Validated(Validated { source, id, item: GossipItem::Block(block), verdict })
+}
@@ -0,0 +1,99 @@
+use async_trait::async_trait;
+use libp2p::core::upgrade::{read_length_prefixed, write_length_prefixed, ProtocolName};
+use libp2p::futures::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use libp2p::request_response;
+use tokio::io;
+
+use super::messages::MAX_MESSAGE_SIZE;
+use super::sync::{SyncRequest, SyncResponse, MAX_BLOCKS_PER_REQUEST};
+use crate::blockchain::params::NetworkParams;
+use crate::encoding;
+
+const MAX_REQUEST_SIZE: usize = MAX_MESSAGE_SIZE as usize + MAGIC_SIZE;
+// A full `Blocks` response may carry up to MAX_BLOCKS_PER_REQUEST blocks
+const MAX_RESPONSE_SIZE: usize = MAX_MESSAGE_SIZE as usize * MAX_BLOCKS_PER_REQUEST + MAGIC_SIZE;
+const MAGIC_SIZE: usize = 4;
+
+/// The sync protocol name of one network, e.g. `/arcnova/testnet/sync/1.0.0`
+#[derive(Debug, Clone)]
+pub struct SyncProtocol(pub String);
+
+impl SyncProtocol {
+    pub fn for_network(params: &NetworkParams) -> Self {
+        SyncProtocol(params.sync_protocol())
+    }
+}
+
+impl ProtocolName for SyncProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+/// Length-prefixed bincode framing for `SyncRequest`/`SyncResponse`.
+///
+/// Every frame starts with the network magic; frames from another network
+/// are refused even if the protocol names were to match.
+#[derive(Debug, Clone)]
+pub struct SyncCodec {
+    magic: [u8; 4],
+}
+
+impl SyncCodec {
+    pub fn for_network(params: &NetworkParams) -> Self {
+        SyncCodec { magic: params.magic }
+    }
+
+    // Decoded as strictly as gossip, within the frame's `max_size`
+    fn decode<T: serde::de::DeserializeOwned>(&self, bytes: &[u8], max_size: usize) -> io::Result<T> {
+        match bytes.split_first_chunk::<MAGIC_SIZE>() {
+            Some((magic, payload)) if *magic == self.magic => {
+                encoding::decode(payload, (max_size - MAGIC_SIZE) as u64)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "wrong network magic")),
+        }
+    }
+
+    fn encode<T: serde::Serialize>(&self, value: &T) -> io::Result<Vec<u8>> {
+        let payload = bincode::serialize(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok([&self.magic[..], &payload].concat())
+    }
+}
+
+#[async_trait]
+impl request_response::Codec for SyncCodec {
+    type Protocol = SyncProtocol;
+    type Request = SyncRequest;
+    type Response = SyncResponse;
+
+    async fn read_request<T>(&mut self, _: &SyncProtocol, io: &mut T) -> io::Result<SyncRequest>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        self.decode(&read_length_prefixed(io, MAX_REQUEST_SIZE).await?, MAX_REQUEST_SIZE)
+    }
+
+    async fn read_response<T>(&mut self, _: &SyncProtocol, io: &mut T) -> io::Result<SyncResponse>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        self.decode(&read_length_prefixed(io, MAX_RESPONSE_SIZE).await?, MAX_RESPONSE_SIZE)
+    }
+
+    async fn write_request<T>(&mut self, _: &SyncProtocol, io: &mut T, req: SyncRequest) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_length_prefixed(io, self.encode(&req)?).await?;
+        io.close().await
+    }
+
+    async fn write_response<T>(&mut self, _: &SyncProtocol, io: &mut T, res: SyncResponse) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_length_prefixed(io, self.encode(&res)?).await?;
+        io.close().await
+    }
+}
@@ -0,0 +1,53 @@
+/// Capabilities a node advertises to its peers.
+///
+/// Sent as part of the identify agent version, e.g. `arcnova/0.1.0 services=3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ServiceFlags(pub u64);
+
+impl ServiceFlags {
+    pub const NONE: ServiceFlags = ServiceFlags(0);
+
+    /// Relays blocks and transactions and serves recent blocks
+    pub const NETWORK: ServiceFlags = ServiceFlags(1 << 0);
+
+    /// Serves the complete block history; unset on pruned nodes
+    pub const ARCHIVE: ServiceFlags = ServiceFlags(1 << 1);
+
+    /// Services for a node whose block store is (or is not) pruned
+    pub fn for_node(pruned: bool) -> Self {
+        if pruned {
+            ServiceFlags::NETWORK
+        } else {
+            ServiceFlags::NETWORK | ServiceFlags::ARCHIVE
+        }
+    }
+
+    pub fn contains(self, other: ServiceFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_pruned(self) -> bool {
+        self.contains(ServiceFlags::NETWORK) && !self.contains(ServiceFlags::ARCHIVE)
+    }
+
+    pub fn agent_version(self) -> String {
+        format!("arcnova/{} services={}", env!("CARGO_PKG_VERSION"), self.0)
+    }
+
+    /// Parse the flags out of a peer's identify agent version
+    pub fn from_agent_version(agent: &str) -> Option<Self> {
+        agent
+            .split_whitespace()
+            .find_map(|part| part.strip_prefix("services="))
+            .and_then(|flags| flags.parse().ok())
+            .map(ServiceFlags)
+    }
+}
+
+impl std::ops::BitOr for ServiceFlags {
+    type Output = ServiceFlags;
+
+    fn bitor(self, rhs: ServiceFlags) -> ServiceFlags {
+        ServiceFlags(self.0 | rhs.0)
+    }
+}
@@ -0,0 +1,449 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::time::Duration;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::sync::{block_locator, serve_request, SyncManager, SyncRequest, SyncResponse};
+use crate::blockchain::address::{Address, ADDRESS_HASH_SIZE, ADDRESS_VERSION};
+use crate::blockchain::block::Block;
+use crate::blockchain::chain::{ChainManager, Verdict};
+use crate::blockchain::params::NetworkParams;
+use crate::blockchain::transaction::{Transaction, TxOutput};
+use crate::blockchain::utxo::UtxoSet;
+use crate::crypto::hash::Hash;
+use crate::storage::Store;
+
+/// Index of a node in the simulation
+pub type NodeId = usize;
+
+/// How long a requester waits for a lost request or response before giving
+/// up on the peer, as the request-response protocol does
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Network conditions. All randomness comes from `seed`, so a run is
+/// repeated exactly by using the same seed and the same calls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimConfig {
+    /// Delay of every message
+    pub latency: Duration,
+    /// Up to this much is added to `latency`, at random
+    pub jitter: Duration,
+    /// Chance of a message being lost, between 0 and 1
+    pub drop_rate: f64,
+    pub seed: u64,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            latency: Duration::from_millis(50),
+            jitter: Duration::ZERO,
+            drop_rate: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+/// Counters for a simulation run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SimStats {
+    pub delivered: u64,
+    pub dropped: u64,
+    /// Sync requests that timed out or went to a disconnected peer
+    pub failed_requests: u64,
+}
+
+#[derive(Debug)]
+enum Message {
+    Gossip(Block),
+    Request { id: u64, request: SyncRequest },
+    Response { id: u64, response: SyncResponse },
+    // Our request `id` will never be answered
+    Failure { id: u64 },
+}
+
+#[derive(Debug)]
+struct Delivery {
+    at: Duration,
+    seq: u64,
+    from: NodeId,
+    to: NodeId,
+    message: Message,
+}
+
+// Ordered by time, then by send order, so ties are broken the same way on
+// every run
+impl PartialEq for Delivery {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at, self.seq) == (other.at, other.seq)
+    }
+}
+
+impl Eq for Delivery {}
+
+impl PartialOrd for Delivery {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Delivery {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.at, self.seq).cmp(&(other.at, other.seq))
+    }
+}
+
+/// One simulated node: the same chain and sync logic as `arcnova-node`,
+/// without the sockets
+pub struct SimNode {
+    pub manager: ChainManager<UtxoSet>,
+    sync: SyncManager<NodeId>,
+    peers: BTreeSet<NodeId>,
+    // Our outstanding sync requests, by request id
+    requests: BTreeMap<u64, NodeId>,
+}
+
+impl SimNode {
+    pub fn tip(&self) -> Option<(u64, Hash)> {
+        self.manager.state.utxo_set.tip().ok().flatten()
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.peers.iter().copied()
+    }
+}
+
+/// Runs several nodes in one process over a simulated network.
+///
+/// Time is virtual: messages are delivered in timestamp order and nothing
+/// waits on a real clock. Links can be cut (`disconnect`, `partition`) and
+/// restored, and messages delayed or dropped according to `SimConfig`.
+/// Reconnecting two nodes starts header-first sync between them, as an
+/// identify exchange does on a real connection. Blocks are gossiped in full
+/// and forwarded by every node that accepts them.
+pub struct Simulator {
+    config: SimConfig,
+    rng: StdRng,
+    now: Duration,
+    seq: u64,
+    next_request: u64,
+    nodes: Vec<SimNode>,
+    queue: BinaryHeap<Reverse<Delivery>>,
+    // Links cut by `partition`, restored by `heal`
+    cut: Vec<(NodeId, NodeId)>,
+    stats: SimStats,
+}
+
+impl Simulator {
+    /// `count` unconnected nodes, each holding only the genesis block of
+    /// `params`
+    pub fn new(count: usize, params: &NetworkParams, config: SimConfig) -> Self {
+        let nodes = (0..count)
+            .map(|_| {
+                let mut manager = ChainManager::new(UtxoSet::default());
+                params.init_store(&mut manager.state.utxo_set).expect("empty stores take genesis");
+                let mut sync = SyncManager::new(manager.state.utxo_set.tip().ok().flatten());
+                sync.set_chain_locator(block_locator(&manager.state.utxo_set));
+                SimNode { manager, sync, peers: BTreeSet::new(), requests: BTreeMap::new() }
+            })
+            .collect();
+
+        Simulator {
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+            now: Duration::ZERO,
+            seq: 0,
+            next_request: 0,
+            nodes,
+            queue: BinaryHeap::new(),
+            cut: Vec::new(),
+            stats: SimStats::default(),
+        }
+    }
+
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    pub fn stats(&self) -> SimStats {
+        self.stats
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, id: NodeId) -> &SimNode {
+        &self.nodes[id]
+    }
+
+    /// Change network conditions from now on; the random stream is kept
+    pub fn set_conditions(&mut self, latency: Duration, jitter: Duration, drop_rate: f64) {
+        self.config.latency = latency;
+        self.config.jitter = jitter;
+        self.config.drop_rate = drop_rate;
+    }
+
+    /// Link two nodes; both start syncing from each other
+    pub fn connect(&mut self, a: NodeId, b: NodeId) {
+        if a == b || self.nodes[a].peers.contains(&b) {
+            return;
+        }
+        for (node, peer) in [(a, b), (b, a)] {
+            self.nodes[node].peers.insert(peer);
+            let requests = self.nodes[node].sync.add_peer(peer, true);
+            self.send_requests(node, requests);
+        }
+    }
+
+    pub fn connect_all(&mut self) {
+        for a in 0..self.nodes.len() {
+            for b in a + 1..self.nodes.len() {
+                self.connect(a, b);
+            }
+        }
+    }
+
+    /// Cut the link between two nodes. Messages already on the way are lost
+    /// and requests waiting on the other side fail.
+    pub fn disconnect(&mut self, a: NodeId, b: NodeId) {
+        if !self.nodes[a].peers.contains(&b) {
+            return;
+        }
+        for (node, peer) in [(a, b), (b, a)] {
+            self.nodes[node].peers.remove(&peer);
+            let before = self.nodes[node].requests.len();
+            self.nodes[node].requests.retain(|_, to| *to != peer);
+            self.stats.failed_requests += (before - self.nodes[node].requests.len()) as u64;
+            let requests = self.nodes[node].sync.remove_peer(&peer);
+            self.send_requests(node, requests);
+        }
+    }
+
+    /// Cut every link between nodes in different groups; the nodes not
+    /// listed together form one more group
+    pub fn partition(&mut self, groups: &[&[NodeId]]) {
+        let group_of = |node: NodeId| groups.iter().position(|group| group.contains(&node));
+
+        for a in 0..self.nodes.len() {
+            let peers: Vec<NodeId> = self.nodes[a].peers.iter().copied().filter(|b| *b > a).collect();
+            for b in peers {
+                if group_of(a) != group_of(b) {
+                    self.disconnect(a, b);
+                    self.cut.push((a, b));
+                }
+            }
+        }
+    }
+
+    /// Restore the links cut by `partition`
+    pub fn heal(&mut self) {
+        for (a, b) in std::mem::take(&mut self.cut) {
+            self.connect(a, b);
+        }
+    }
+
+    /// Build a block on `node`'s tip with the given transactions, connect it
+    /// there and gossip it to the node's peers
+    pub fn mine(&mut self, node: NodeId, transactions: Vec<Transaction>) -> Block {
+        let (height, prev_hash) = self.nodes[node].tip().expect("nodes start at genesis");
+        let index = height + 1;
+        let timestamp = self.now.as_millis();
+
+        let coinbase =
+            Transaction::coinbase(index, vec![TxOutput { value: 50, address: node_address(node) }]);
+        let mut all = vec![coinbase];
+        all.extend(transactions);
+
+        let mut block = Block {
+            index,
+            prev_hash,
+            timestamp,
+            transactions: all,
+            nonce: 0,
+            hash: Hash([0u8; 32]),
+        };
+        block.hash = block.calculate_hash();
+        let verdict = self.nodes[node].manager.process_block(block.clone());
+        assert_eq!(verdict, Verdict::Accept, "mined blocks must be valid");
+
+        self.gossip(node, None, &block);
+        block
+    }
+
+    /// Deliver the next message, advancing the clock to it.
+    /// Returns false when nothing is left to deliver.
+    pub fn step(&mut self) -> bool {
+        let Reverse(delivery) = match self.queue.pop() {
+            Some(delivery) => delivery,
+            None => return false,
+        };
+        self.now = self.now.max(delivery.at);
+        self.deliver(delivery);
+        true
+    }
+
+    /// Deliver everything due within `duration` from now
+    pub fn run_for(&mut self, duration: Duration) {
+        let until = self.now + duration;
+        while self.queue.peek().is_some_and(|Reverse(d)| d.at <= until) {
+            self.step();
+        }
+        self.now = until;
+    }
+
+    /// Deliver messages until none are left, or `limit` of virtual time has
+    /// passed. Returns whether the network went quiet.
+    pub fn run_until_idle(&mut self, limit: Duration) -> bool {
+        let until = self.now + limit;
+        while self.queue.peek().is_some_and(|Reverse(d)| d.at <= until) {
+            self.step();
+        }
+        self.queue.is_empty()
+    }
+
+    /// Every node has the same tip
+    pub fn converged(&self) -> bool {
+        let mut tips = self.nodes.iter().map(SimNode::tip);
+        match tips.next() {
+            Some(first) => tips.all(|tip| tip == first),
+            None => true,
+        }
+    }
+
+    /// Run until every node has the same tip and nothing is in flight.
+    /// Returns false if that does not happen within `limit`.
+    pub fn run_until_converged(&mut self, limit: Duration) -> bool {
+        let until = self.now + limit;
+        loop {
+            if self.queue.is_empty() {
+                return self.converged();
+            }
+            if self.queue.peek().is_some_and(|Reverse(d)| d.at > until) {
+                return false;
+            }
+            self.step();
+        }
+    }
+
+    fn send(&mut self, from: NodeId, to: NodeId, message: Message) {
+        let jitter = self.config.jitter.mul_f64(self.rng.gen::<f64>());
+        let dropped = self.rng.gen::<f64>() < self.config.drop_rate;
+        self.seq += 1;
+
+        if !dropped {
+            let at = self.now + self.config.latency + jitter;
+            let delivery = Delivery { at, seq: self.seq, from, to, message };
+            self.queue.push(Reverse(delivery));
+            return;
+        }
+
+        self.stats.dropped += 1;
+        // The requester only finds out by timing out
+        let failed = match message {
+            Message::Request { id, .. } => Some((from, to, id)),
+            Message::Response { id, .. } => Some((to, from, id)),
+            _ => None,
+        };
+        if let Some((requester, peer, id)) = failed {
+            let at = self.now + REQUEST_TIMEOUT;
+            let message = Message::Failure { id };
+            let delivery = Delivery { at, seq: self.seq, from: peer, to: requester, message };
+            self.queue.push(Reverse(delivery));
+        }
+    }
+
+    fn send_requests(&mut self, node: NodeId, requests: Vec<(NodeId, SyncRequest)>) {
+        for (peer, request) in requests {
+            let id = self.next_request;
+            self.next_request += 1;
+            self.nodes[node].requests.insert(id, peer);
+            self.send(node, peer, Message::Request { id, request });
+        }
+    }
+
+    fn gossip(&mut self, node: NodeId, source: Option<NodeId>, block: &Block) {
+        let peers: Vec<NodeId> = self.nodes[node].peers.iter().copied().collect();
+        for peer in peers.into_iter().filter(|peer| Some(*peer) != source) {
+            self.send(node, peer, Message::Gossip(block.clone()));
+        }
+    }
+
+    fn deliver(&mut self, delivery: Delivery) {
+        let Delivery { from, to, message, .. } = delivery;
+
+        // Failures are local; everything else needs the link to still be up
+        if !matches!(message, Message::Failure { .. }) && !self.nodes[to].peers.contains(&from) {
+            self.stats.dropped += 1;
+            return;
+        }
+
+        match message {
+            Message::Gossip(block) => {
+                self.stats.delivered += 1;
+                if self.nodes[to].manager.process_block(block.clone()) == Verdict::Accept {
+                    self.gossip(to, Some(from), &block);
+                }
+            }
+            Message::Request { id, request } => {
+                self.stats.delivered += 1;
+                let response = serve_request(&self.nodes[to].manager.state.utxo_set, &request);
+                self.send(to, from, Message::Response { id, response });
+            }
+            Message::Response { id, response } => {
+                self.stats.delivered += 1;
+                if self.nodes[to].requests.remove(&id).is_none() {
+                    return;
+                }
+                self.on_response(to, from, response);
+            }
+            Message::Failure { id } => {
+                if self.nodes[to].requests.remove(&id).is_none() {
+                    return;
+                }
+                self.stats.failed_requests += 1;
+                let requests = self.nodes[to].sync.remove_peer(&from);
+                self.send_requests(to, requests);
+            }
+        }
+    }
+
+    // Feed a sync response to the node and connect whatever is now ready
+    fn on_response(&mut self, node: NodeId, peer: NodeId, response: SyncResponse) {
+        let sim_node = &mut self.nodes[node];
+        let result = match response {
+            SyncResponse::Headers(headers) => sim_node.sync.on_headers(&peer, headers),
+            SyncResponse::Blocks(blocks) => sim_node.sync.on_blocks(&peer, blocks),
+            _ => return,
+        };
+
+        let mut requests = match result {
+            Ok(requests) => requests,
+            Err(_) => sim_node.sync.remove_peer(&peer),
+        };
+
+        for block in sim_node.sync.drain_ready() {
+            if sim_node.manager.process_block(block) == Verdict::Reject {
+                let tip = sim_node.tip();
+                requests = sim_node.sync.reset(tip);
+                break;
+            }
+        }
+        let locator = block_locator(&sim_node.manager.state.utxo_set);
+        sim_node.sync.set_chain_locator(locator);
+
+        self.send_requests(node, requests);
+    }
+}
+
+// Where a node's block rewards go; nobody holds the key
+fn node_address(node: NodeId) -> Address {
+    let mut hash = [0u8; ADDRESS_HASH_SIZE];
+    hash[..8].copy_from_slice(&(node as u64).to_le_bytes());
+    Address::new(ADDRESS_VERSION, hash)
+}
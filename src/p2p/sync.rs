@@ -0,0 +1,465 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::blockchain::block::{Block, BlockHeader};
+use crate::blockchain::transaction::Transaction;
+use crate::crypto::hash::Hash;
+use crate::storage::{Store, MIN_PRUNE_DEPTH};
+
+/// Most headers returned for one `GetHeaders`
+pub const MAX_HEADERS_PER_RESPONSE: usize = 2000;
+
+/// Most blocks requested from (or served to) a peer at once
+pub const MAX_BLOCKS_PER_REQUEST: usize = 16;
+
+/// A peer that has not answered a block request within this time is dropped
+/// from sync and its blocks are requested elsewhere
+pub const BLOCK_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Requests of the `/arcnova/sync` request-response protocol
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncRequest {
+    /// Headers following the first locator hash found on the peer's main
+    /// chain, or from genesis if none is known
+    GetHeaders { locator: Vec<Hash> },
+    GetBlocks { hashes: Vec<Hash> },
+    /// Transactions at `indexes` of a block, to complete a compact block
+    GetBlockTxn { block_hash: Hash, indexes: Vec<u32> },
+    /// Announce transactions by txid (see `TxRelay`)
+    TxInv { txids: Vec<Hash> },
+    /// Fetch announced transactions
+    GetTxs { txids: Vec<Hash> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SyncResponse {
+    Headers(Vec<BlockHeader>),
+    Blocks(Vec<Block>),
+    /// Empty if the block is unknown or an index is out of range
+    BlockTxn { block_hash: Hash, transactions: Vec<Transaction> },
+    /// Reply to `TxInv`
+    Ack,
+    Txs(Vec<Transaction>),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SyncError {
+    #[error("headers do not connect to our header chain")]
+    UnconnectedHeaders,
+
+    #[error("headers are not a contiguous chain")]
+    InvalidHeaders,
+
+    #[error("block was not requested from this peer")]
+    UnsolicitedBlock,
+
+    #[error("block does not match its header")]
+    HeaderMismatch,
+
+    #[error("block contents do not match its hash")]
+    HashMismatch,
+
+    #[error("peer sent none of the requested blocks")]
+    NoBlocks,
+}
+
+/// Answer a sync request from our own chain
+pub fn serve_request<S: Store>(store: &S, request: &SyncRequest) -> SyncResponse {
+    match request {
+        SyncRequest::GetHeaders { locator } => {
+            let start = locator
+                .iter()
+                .find_map(|hash| store.main_chain_height(hash).ok().flatten())
+                .map_or(0, |height| height + 1);
+
+            let mut headers = Vec::new();
+            for height in start..start + MAX_HEADERS_PER_RESPONSE as u64 {
+                match store.get_header_at_height(height) {
+                    Ok(Some(header)) => headers.push(header),
+                    _ => break,
+                }
+            }
+            SyncResponse::Headers(headers)
+        }
+        SyncRequest::GetBlocks { hashes } => {
+            // Unknown or pruned blocks are simply left out
+            let blocks = hashes
+                .iter()
+                .take(MAX_BLOCKS_PER_REQUEST)
+                .filter_map(|hash| store.get_block(hash).ok().flatten())
+                .collect();
+            SyncResponse::Blocks(blocks)
+        }
+        SyncRequest::GetBlockTxn { block_hash, indexes } => {
+            let transactions = match store.get_block(block_hash) {
+                Ok(Some(block)) => indexes
+                    .iter()
+                    .map(|i| block.transactions.get(*i as usize).cloned())
+                    .collect::<Option<Vec<_>>>()
+                    .unwrap_or_default(),
+                _ => Vec::new(),
+            };
+            SyncResponse::BlockTxn { block_hash: block_hash.clone(), transactions }
+        }
+        SyncRequest::TxInv { .. } => SyncResponse::Ack,
+        // Transactions come from the mempool, which the store does not have
+        SyncRequest::GetTxs { .. } => SyncResponse::Txs(Vec::new()),
+    }
+}
+
+/// Hashes of `store`'s main chain to locate it with in `GetHeaders`: the
+/// tip, then stepping back 1, 2, 4, … blocks, ending with genesis
+pub fn block_locator<S: Store>(store: &S) -> Vec<Hash> {
+    let (mut height, tip) = match store.tip() {
+        Ok(Some(tip)) => tip,
+        _ => return Vec::new(),
+    };
+
+    // Stores bootstrapped from a snapshot have no headers below its base
+    let mut locator = vec![tip];
+    let mut step = 1;
+    while height > 0 {
+        height = height.saturating_sub(step);
+        step = step.saturating_mul(2);
+        if let Ok(Some(header)) = store.get_header_at_height(height) {
+            locator.push(header.hash);
+        }
+    }
+    locator
+}
+
+struct PeerState {
+    /// Serves full history (not pruned)
+    archive: bool,
+    /// Hashes of the block batch currently requested from this peer
+    in_flight: Vec<Hash>,
+    requested_at: Option<Instant>,
+}
+
+/// Headers-first initial block download.
+///
+/// Headers are fetched from one peer at a time; the blocks they describe are
+/// then requested in batches from every sync peer in parallel. Downloaded
+/// blocks are released strictly in chain order via `drain_ready`.
+///
+/// `P` identifies a peer. The manager only produces requests; the caller
+/// sends them and feeds responses and peer changes back in. When a peer goes
+/// away its outstanding blocks go back to the queue, and after a restart
+/// sync resumes from the stored tip.
+pub struct SyncManager<P> {
+    tip: Option<(u64, Hash)>,
+    // Locator of the chain below `tip`, see `set_chain_locator`
+    chain_locator: Vec<Hash>,
+    headers: VecDeque<BlockHeader>,
+    queue: VecDeque<BlockHeader>,
+    downloaded: HashMap<Hash, Block>,
+    // Ordered so that work is handed out the same way on every run
+    peers: BTreeMap<P, PeerState>,
+    header_peer: Option<P>,
+}
+
+impl<P: Clone + Ord> SyncManager<P> {
+    /// Start syncing on top of `tip`, the last block already in our chain
+    pub fn new(tip: Option<(u64, Hash)>) -> Self {
+        SyncManager {
+            chain_locator: tip.iter().map(|(_, hash)| hash.clone()).collect(),
+            tip,
+            headers: VecDeque::new(),
+            queue: VecDeque::new(),
+            downloaded: HashMap::new(),
+            peers: BTreeMap::new(),
+            header_peer: None,
+        }
+    }
+
+    /// Locate our chain with `locator` (see `block_locator`) when asking
+    /// for headers, so that peers on another branch can find where it forks
+    pub fn set_chain_locator(&mut self, locator: Vec<Hash>) {
+        self.chain_locator = locator;
+    }
+
+    /// Last block handed out by `drain_ready`
+    pub fn tip(&self) -> Option<&(u64, Hash)> {
+        self.tip.as_ref()
+    }
+
+    /// Height of the best header we know of
+    pub fn best_header_height(&self) -> Option<u64> {
+        self.headers
+            .back()
+            .map(|h| h.index)
+            .or(self.tip.as_ref().map(|(height, _)| *height))
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.header_peer.is_none() && self.headers.is_empty()
+    }
+
+    pub fn has_peer(&self, peer: &P) -> bool {
+        self.peers.contains_key(peer)
+    }
+
+    fn best_known_hash(&self) -> Option<Hash> {
+        self.headers
+            .back()
+            .map(|h| h.hash.clone())
+            .or(self.tip.as_ref().map(|(_, hash)| hash.clone()))
+    }
+
+    // Our best known header, then stepping back 1, 2, 4, … through the
+    // headers, followed by the tip and the locator of the chain below it
+    fn locator(&self) -> Vec<Hash> {
+        let mut locator = Vec::new();
+        let mut back = 0;
+        let mut step = 1;
+        while back < self.headers.len() {
+            locator.push(self.headers[self.headers.len() - 1 - back].hash.clone());
+            back += step;
+            step *= 2;
+        }
+
+        let below = self.tip.iter().map(|(_, hash)| hash).chain(&self.chain_locator);
+        for hash in below {
+            if !locator.contains(hash) {
+                locator.push(hash.clone());
+            }
+        }
+        locator
+    }
+
+    fn request_headers(&mut self, peer: P) -> (P, SyncRequest) {
+        self.header_peer = Some(peer.clone());
+        (peer, SyncRequest::GetHeaders { locator: self.locator() })
+    }
+
+    /// Register a connected peer; `archive` is false for pruned peers
+    pub fn add_peer(&mut self, peer: P, archive: bool) -> Vec<(P, SyncRequest)> {
+        self.peers.insert(
+            peer.clone(),
+            PeerState { archive, in_flight: Vec::new(), requested_at: None },
+        );
+
+        let mut requests = Vec::new();
+        if self.header_peer.is_none() {
+            requests.push(self.request_headers(peer));
+        }
+        requests.extend(self.schedule());
+        requests
+    }
+
+    /// Forget a peer (disconnected, failed or stalled) and hand its work to
+    /// the remaining peers
+    pub fn remove_peer(&mut self, peer: &P) -> Vec<(P, SyncRequest)> {
+        if let Some(state) = self.peers.remove(peer) {
+            self.requeue(state.in_flight);
+        }
+
+        let mut requests = Vec::new();
+        if self.header_peer.as_ref() == Some(peer) {
+            self.header_peer = None;
+            if let Some(next) = self.peers.keys().next().cloned() {
+                requests.push(self.request_headers(next));
+            }
+        }
+        requests.extend(self.schedule());
+        requests
+    }
+
+    // Put unanswered hashes back at the front of the queue, keeping order
+    fn requeue(&mut self, hashes: Vec<Hash>) {
+        let mut missing: Vec<BlockHeader> = self
+            .headers
+            .iter()
+            .filter(|h| hashes.contains(&h.hash) && !self.downloaded.contains_key(&h.hash))
+            .cloned()
+            .collect();
+        missing.sort_by_key(|h| h.index);
+
+        for header in missing.into_iter().rev() {
+            self.queue.push_front(header);
+        }
+    }
+
+    pub fn on_headers(
+        &mut self,
+        peer: &P,
+        headers: Vec<BlockHeader>,
+    ) -> Result<Vec<(P, SyncRequest)>, SyncError> {
+        let full_batch = headers.len() >= MAX_HEADERS_PER_RESPONSE;
+
+        // 1. Skip headers we already have, then require a contiguous chain
+        //    on top of our best known header
+        let known = |h: &BlockHeader| {
+            self.headers.iter().any(|k| k.hash == h.hash)
+                || self.tip.as_ref().is_some_and(|(_, tip)| *tip == h.hash)
+        };
+        let new: Vec<BlockHeader> = headers.into_iter().skip_while(|h| known(h)).collect();
+
+        let mut prev_hash = self.best_known_hash();
+        let first_index = self.best_header_height().map_or(0, |height| height + 1);
+
+        for (i, header) in new.iter().enumerate() {
+            let links = prev_hash.as_ref().is_none_or(|prev| header.prev_hash == *prev);
+            if !links || header.index != first_index + i as u64 {
+                // A bad first header means the peer is on another chain
+                return Err(if i == 0 {
+                    SyncError::UnconnectedHeaders
+                } else {
+                    SyncError::InvalidHeaders
+                });
+            }
+            prev_hash = Some(header.hash.clone());
+        }
+
+        // 2. Queue the new blocks for download
+        self.headers.extend(new.iter().cloned());
+        self.queue.extend(new);
+
+        // 3. Keep fetching headers from this peer while it sends full batches
+        let mut requests = Vec::new();
+        if self.header_peer.as_ref() == Some(peer) {
+            self.header_peer = None;
+            if full_batch {
+                requests.push(self.request_headers(peer.clone()));
+            }
+        }
+        requests.extend(self.schedule());
+        Ok(requests)
+    }
+
+    pub fn on_blocks(
+        &mut self,
+        peer: &P,
+        blocks: Vec<Block>,
+    ) -> Result<Vec<(P, SyncRequest)>, SyncError> {
+        let in_flight = match self.peers.get_mut(peer) {
+            Some(state) => {
+                state.requested_at = None;
+                std::mem::take(&mut state.in_flight)
+            }
+            None => return Err(SyncError::UnsolicitedBlock),
+        };
+
+        // Asked again, a peer that has none of the blocks would answer the same
+        let mut result = if blocks.is_empty() && !in_flight.is_empty() {
+            Err(SyncError::NoBlocks)
+        } else {
+            Ok(())
+        };
+        for block in blocks {
+            if !in_flight.contains(&block.hash) {
+                result = Err(SyncError::UnsolicitedBlock);
+                break;
+            }
+            // Headers do not cover the transactions, the hash does
+            if !block.is_consistent() {
+                result = Err(SyncError::HashMismatch);
+                break;
+            }
+            match self.headers.iter().find(|h| h.hash == block.hash) {
+                Some(header) if *header == block.header() => {
+                    self.downloaded.insert(block.hash.clone(), block);
+                }
+                _ => {
+                    result = Err(SyncError::HeaderMismatch);
+                    break;
+                }
+            }
+        }
+
+        // Anything the peer did not deliver goes to someone else
+        self.requeue(in_flight);
+        result?;
+        Ok(self.schedule())
+    }
+
+    /// Blocks ready to be connected, in chain order
+    pub fn drain_ready(&mut self) -> Vec<Block> {
+        let mut ready = Vec::new();
+
+        while let Some(header) = self.headers.front() {
+            let block = match self.downloaded.remove(&header.hash) {
+                Some(block) => block,
+                None => break,
+            };
+            self.tip = Some((header.index, header.hash.clone()));
+            self.headers.pop_front();
+            ready.push(block);
+        }
+
+        ready
+    }
+
+    /// Peers whose block request has been outstanding for too long
+    pub fn timed_out(&self, now: Instant) -> Vec<P> {
+        self.peers
+            .iter()
+            .filter(|(_, state)| {
+                state
+                    .requested_at
+                    .is_some_and(|at| now.duration_since(at) > BLOCK_REQUEST_TIMEOUT)
+            })
+            .map(|(peer, _)| peer.clone())
+            .collect()
+    }
+
+    /// Start over from `tip`, e.g. after a synced block was rejected
+    pub fn reset(&mut self, tip: Option<(u64, Hash)>) -> Vec<(P, SyncRequest)> {
+        self.tip = tip;
+        self.headers.clear();
+        self.queue.clear();
+        self.downloaded.clear();
+        self.header_peer = None;
+        for state in self.peers.values_mut() {
+            state.in_flight.clear();
+            state.requested_at = None;
+        }
+
+        match self.peers.keys().next().cloned() {
+            Some(peer) => vec![self.request_headers(peer)],
+            None => Vec::new(),
+        }
+    }
+
+    /// Hand out queued blocks to idle peers, one batch per peer
+    fn schedule(&mut self) -> Vec<(P, SyncRequest)> {
+        let best = self.best_header_height().unwrap_or(0);
+        let mut requests = Vec::new();
+        let now = Instant::now();
+
+        for (peer, state) in self.peers.iter_mut() {
+            if !state.in_flight.is_empty() || self.queue.is_empty() {
+                continue;
+            }
+
+            // Pruned peers only keep the most recent blocks
+            let mut batch = Vec::new();
+            let mut skipped = Vec::new();
+            while batch.len() < MAX_BLOCKS_PER_REQUEST {
+                let header = match self.queue.pop_front() {
+                    Some(header) => header,
+                    None => break,
+                };
+                if state.archive || header.index + MIN_PRUNE_DEPTH > best {
+                    batch.push(header.hash);
+                } else {
+                    skipped.push(header);
+                }
+            }
+            for header in skipped.into_iter().rev() {
+                self.queue.push_front(header);
+            }
+
+            if !batch.is_empty() {
+                state.in_flight = batch.clone();
+                state.requested_at = Some(now);
+                requests.push((peer.clone(), SyncRequest::GetBlocks { hashes: batch }));
+            }
+        }
+
+        requests
+    }
+}
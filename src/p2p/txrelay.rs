@@ -0,0 +1,283 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash as StdHash;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::crypto::hash::Hash;
+
+/// Most txids in one announcement or request
+pub const MAX_INV_SIZE: usize = 1000;
+
+/// Average delay before queued announcements are sent to a peer. Delays are
+/// random per peer so the first announcer of a transaction is hard to tell.
+pub const TRICKLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Sustained txids per second accepted from a peer, for announcements and
+/// requests separately; up to `RATE_BURST` may arrive at once
+pub const RATE_PER_SECOND: f64 = 100.0;
+pub const RATE_BURST: f64 = 2000.0;
+
+/// An unanswered request is retried with another announcer after this
+pub const TX_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Txids remembered per peer and overall
+const KNOWN_PER_PEER: usize = 10_000;
+const SEEN_LIMIT: usize = 50_000;
+
+/// Insertion-ordered set that forgets its oldest entries past `limit`
+struct BoundedSet {
+    items: HashSet<Hash>,
+    order: VecDeque<Hash>,
+    limit: usize,
+}
+
+impl BoundedSet {
+    fn new(limit: usize) -> Self {
+        BoundedSet { items: HashSet::new(), order: VecDeque::new(), limit }
+    }
+
+    fn contains(&self, item: &Hash) -> bool {
+        self.items.contains(item)
+    }
+
+    fn insert(&mut self, item: Hash) {
+        if !self.items.insert(item.clone()) {
+            return;
+        }
+        self.order.push_back(item);
+        if self.order.len() > self.limit {
+            if let Some(old) = self.order.pop_front() {
+                self.items.remove(&old);
+            }
+        }
+    }
+}
+
+/// Token bucket allowing `RATE_PER_SECOND` with bursts of `RATE_BURST`
+struct RateLimit {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimit {
+    fn new(now: Instant) -> Self {
+        RateLimit { tokens: RATE_BURST, updated: now }
+    }
+
+    /// How many of `wanted` items are allowed now
+    fn take(&mut self, wanted: usize, now: Instant) -> usize {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * RATE_PER_SECOND).min(RATE_BURST);
+        self.updated = now;
+
+        let allowed = (self.tokens.floor() as usize).min(wanted);
+        self.tokens -= allowed as f64;
+        allowed
+    }
+}
+
+struct PeerRelay {
+    /// Txids this peer has announced to us or we have announced to it
+    known: BoundedSet,
+    /// Txids we announced to it, the only ones it may request
+    announced: BoundedSet,
+    queue: Vec<Hash>,
+    next_trickle: Instant,
+    inv_limit: RateLimit,
+    getdata_limit: RateLimit,
+}
+
+/// Inventory-based transaction relay.
+///
+/// Accepted transactions are queued for every peer that does not know them
+/// yet and announced by txid after a random trickle delay. Peers request
+/// only txids they have not seen, and may only request what was announced
+/// to them. Incoming announcements and requests are rate limited per peer.
+///
+/// Like `SyncManager`, this only decides what to send; the network loop
+/// does the sending.
+pub struct TxRelay<P> {
+    peers: HashMap<P, PeerRelay>,
+    /// Txids we have (or had) so do not need to fetch
+    seen: BoundedSet,
+    /// Outstanding requests: who we asked and when
+    requested: HashMap<Hash, (P, Instant)>,
+    /// Other peers that announced a requested txid, to ask on timeout
+    announcers: HashMap<Hash, Vec<P>>,
+}
+
+impl<P: Clone + Eq + StdHash> Default for TxRelay<P> {
+    fn default() -> Self {
+        TxRelay {
+            peers: HashMap::new(),
+            seen: BoundedSet::new(SEEN_LIMIT),
+            requested: HashMap::new(),
+            announcers: HashMap::new(),
+        }
+    }
+}
+
+// Exponentially distributed delay with mean `TRICKLE_INTERVAL`
+fn trickle_delay<R: Rng>(rng: &mut R) -> Duration {
+    let uniform: f64 = rng.gen();
+    TRICKLE_INTERVAL.mul_f64(-(1.0 - uniform).ln())
+}
+
+impl<P: Clone + Eq + StdHash> TxRelay<P> {
+    pub fn add_peer<R: Rng>(&mut self, peer: P, now: Instant, rng: &mut R) {
+        self.peers.entry(peer).or_insert_with(|| PeerRelay {
+            known: BoundedSet::new(KNOWN_PER_PEER),
+            announced: BoundedSet::new(KNOWN_PER_PEER),
+            queue: Vec::new(),
+            next_trickle: now + trickle_delay(rng),
+            inv_limit: RateLimit::new(now),
+            getdata_limit: RateLimit::new(now),
+        });
+    }
+
+    pub fn remove_peer(&mut self, peer: &P) {
+        self.peers.remove(peer);
+        for announcers in self.announcers.values_mut() {
+            announcers.retain(|p| p != peer);
+        }
+    }
+
+    pub fn has_seen(&self, txid: &Hash) -> bool {
+        self.seen.contains(txid)
+    }
+
+    /// A transaction entered our mempool: announce it to every peer that
+    /// does not know it yet
+    pub fn announce(&mut self, txid: Hash) {
+        self.seen.insert(txid.clone());
+        self.requested.remove(&txid);
+        self.announcers.remove(&txid);
+
+        for state in self.peers.values_mut() {
+            if !state.known.contains(&txid) {
+                state.known.insert(txid.clone());
+                state.queue.push(txid.clone());
+            }
+        }
+    }
+
+    /// A transaction was processed but not accepted; do not fetch it again
+    pub fn mark_seen(&mut self, txid: Hash) {
+        self.requested.remove(&txid);
+        self.announcers.remove(&txid);
+        self.seen.insert(txid);
+    }
+
+    /// `peer` announced `txids`; returns the ones to request from it
+    pub fn on_inv(&mut self, peer: &P, txids: Vec<Hash>, now: Instant) -> Vec<Hash> {
+        let state = match self.peers.get_mut(peer) {
+            Some(state) => state,
+            None => return Vec::new(),
+        };
+
+        let allowed = state.inv_limit.take(txids.len().min(MAX_INV_SIZE), now);
+        let mut wanted = Vec::new();
+        for txid in txids.into_iter().take(allowed) {
+            state.known.insert(txid.clone());
+            if self.seen.contains(&txid) {
+                continue;
+            }
+
+            if self.requested.contains_key(&txid) {
+                let announcers = self.announcers.entry(txid).or_default();
+                if !announcers.contains(peer) {
+                    announcers.push(peer.clone());
+                }
+            } else {
+                self.requested.insert(txid.clone(), (peer.clone(), now));
+                wanted.push(txid);
+            }
+        }
+        wanted
+    }
+
+    /// `peer` asked for `txids`; returns the ones it may have
+    pub fn on_getdata(&mut self, peer: &P, txids: Vec<Hash>, now: Instant) -> Vec<Hash> {
+        let state = match self.peers.get_mut(peer) {
+            Some(state) => state,
+            None => return Vec::new(),
+        };
+
+        let allowed = state.getdata_limit.take(txids.len().min(MAX_INV_SIZE), now);
+        txids
+            .into_iter()
+            .take(allowed)
+            .filter(|txid| state.announced.contains(txid))
+            .collect()
+    }
+
+    /// Whether a transaction `peer` sent was requested from it. Unrequested
+    /// transactions should be dropped.
+    pub fn on_tx(&mut self, peer: &P, txid: &Hash) -> bool {
+        match self.requested.get(txid) {
+            Some((asked, _)) if asked == peer => {
+                self.requested.remove(txid);
+                self.seen.insert(txid.clone());
+                self.announcers.remove(txid);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Announcements due now, per peer
+    pub fn trickle<R: Rng>(&mut self, now: Instant, rng: &mut R) -> Vec<(P, Vec<Hash>)> {
+        let mut due = Vec::new();
+
+        for (peer, state) in self.peers.iter_mut() {
+            if state.next_trickle > now {
+                continue;
+            }
+            state.next_trickle = now + trickle_delay(rng);
+            if state.queue.is_empty() {
+                continue;
+            }
+
+            let count = state.queue.len().min(MAX_INV_SIZE);
+            let txids: Vec<Hash> = state.queue.drain(..count).collect();
+            for txid in &txids {
+                state.announced.insert(txid.clone());
+            }
+            due.push((peer.clone(), txids));
+        }
+
+        due
+    }
+
+    /// Requests that went unanswered, re-sent to another announcer where
+    /// there is one
+    pub fn timed_out(&mut self, now: Instant) -> Vec<(P, Vec<Hash>)> {
+        let expired: Vec<Hash> = self
+            .requested
+            .iter()
+            .filter(|(_, (_, at))| now.saturating_duration_since(*at) >= TX_REQUEST_TIMEOUT)
+            .map(|(txid, _)| txid.clone())
+            .collect();
+
+        let mut retries: HashMap<P, Vec<Hash>> = HashMap::new();
+        for txid in expired {
+            self.requested.remove(&txid);
+            let next = match self.announcers.get_mut(&txid) {
+                Some(announcers) if !announcers.is_empty() => Some(announcers.remove(0)),
+                _ => None,
+            };
+            match next {
+                Some(peer) => {
+                    self.requested.insert(txid.clone(), (peer.clone(), now));
+                    retries.entry(peer).or_default().push(txid);
+                }
+                None => {
+                    self.announcers.remove(&txid);
+                }
+            }
+        }
+
+        retries.into_iter().collect()
+    }
+}
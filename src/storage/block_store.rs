@@ -0,0 +1,166 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{record, StorageError};
+
+/// Default size after which a new segment file is started.
+pub const MAX_SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Location of a record inside the segment files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilePos {
+    pub file: u32,
+    pub offset: u64,
+    pub len: u32,
+}
+
+/// Which family of segment files a record lives in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    /// Raw blocks (`blkNNNNN.dat`)
+    Block,
+    /// Undo data for connected blocks (`revNNNNN.dat`)
+    Undo,
+}
+
+impl SegmentKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            SegmentKind::Block => "blk",
+            SegmentKind::Undo => "rev",
+        }
+    }
+}
+
+/// Append-only block and undo files, split into numbered segments.
+///
+/// Block `N` and its undo data always land in segments with the same number,
+/// so a whole segment pair can later be deleted at once.
+pub struct BlockStore {
+    dir: PathBuf,
+    current_file: u32,
+    max_segment_size: u64,
+}
+
+impl BlockStore {
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::open_with_segment_size(dir, MAX_SEGMENT_SIZE)
+    }
+
+    pub fn open_with_segment_size(
+        dir: impl AsRef<Path>,
+        max_segment_size: u64,
+    ) -> Result<Self, StorageError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let current_file = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| parse_segment_number(&entry.file_name().to_string_lossy()))
+            .max()
+            .unwrap_or(0);
+
+        Ok(BlockStore {
+            dir,
+            current_file,
+            max_segment_size,
+        })
+    }
+
+    pub fn segment_path(&self, kind: SegmentKind, file: u32) -> PathBuf {
+        self.dir.join(format!("{}{:05}.dat", kind.prefix(), file))
+    }
+
+    pub fn current_file(&self) -> u32 {
+        self.current_file
+    }
+
+    /// Append a record to the current segment and fsync it.
+    pub fn append<T: Serialize>(
+        &mut self,
+        kind: SegmentKind,
+        item: &T,
+    ) -> Result<FilePos, StorageError> {
+        let payload = bincode::serialize(item)?;
+        let framed = record::encode(&payload);
+
+        // Roll over to a new segment pair when the block file is full
+        if kind == SegmentKind::Block {
+            let size = file_len(&self.segment_path(SegmentKind::Block, self.current_file))?;
+            if size > 0 && size + framed.len() as u64 > self.max_segment_size {
+                self.current_file += 1;
+            }
+        }
+
+        let path = self.segment_path(kind, self.current_file);
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let offset = file.seek(SeekFrom::End(0))?;
+        file.write_all(&framed)?;
+        file.sync_data()?;
+
+        Ok(FilePos {
+            file: self.current_file,
+            offset,
+            len: framed.len() as u32,
+        })
+    }
+
+    pub fn read<T: DeserializeOwned>(
+        &self,
+        kind: SegmentKind,
+        pos: FilePos,
+    ) -> Result<T, StorageError> {
+        let mut file = File::open(self.segment_path(kind, pos.file))?;
+        file.seek(SeekFrom::Start(pos.offset))?;
+
+        let mut buf = vec![0u8; pos.len as usize];
+        file.read_exact(&mut buf)?;
+
+        let payload = record::decode_at(&buf, 0)
+            .ok_or_else(|| StorageError::Corrupt(format!("bad record at {:?}", pos)))?;
+        Ok(bincode::deserialize(payload)?)
+    }
+
+    /// Cut a segment back to `len` bytes, dropping records that were written
+    /// but never made it into the index.
+    pub fn truncate(&self, kind: SegmentKind, file: u32, len: u64) -> Result<(), StorageError> {
+        let path = self.segment_path(kind, file);
+        if file_len(&path)? > len {
+            let f = OpenOptions::new().write(true).open(&path)?;
+            f.set_len(len)?;
+            f.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Delete a block segment and its matching undo segment.
+    pub fn remove_segment(&self, file: u32) -> Result<(), StorageError> {
+        for kind in [SegmentKind::Block, SegmentKind::Undo] {
+            match fs::remove_file(self.segment_path(kind, file)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn file_len(path: &Path) -> Result<u64, StorageError> {
+    match fs::metadata(path) {
+        Ok(meta) => Ok(meta.len()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn parse_segment_number(name: &str) -> Option<u32> {
+    let digits = name
+        .strip_prefix("blk")
+        .or_else(|| name.strip_prefix("rev"))?
+        .strip_suffix(".dat")?;
+    digits.parse().ok()
+}
@@ -0,0 +1,606 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::block_store::{BlockStore, FilePos, SegmentKind, MAX_SEGMENT_SIZE};
+use super::kv::{KvLog, WriteBatch};
+use super::StorageError;
+use crate::blockchain::address::Address;
+use crate::blockchain::block::{Block, BlockHeader};
+use crate::blockchain::transaction::TxOutput;
+use crate::blockchain::utxo::{commitment_element, BlockUndo, OutPoint, UtxoSet};
+use crate::crypto::hash::Hash;
+use crate::crypto::multiset::MultisetHash;
+use super::snapshot::SnapshotHeader;
+
+// Key prefixes in the index/UTXO log
+const VERSION_KEY: &[u8] = b"v";
+const TIP_KEY: &[u8] = b"t";
+const LAST_POS_KEY: &[u8] = b"l";
+const COMMITMENT_KEY: &[u8] = b"c";
+const SNAPSHOT_BASE_KEY: &[u8] = b"s";
+const PRUNE_STATE_KEY: &[u8] = b"p";
+const SEGMENT_PREFIX: u8 = b'f';
+const BLOCK_PREFIX: u8 = b'h';
+const HEIGHT_PREFIX: u8 = b'n';
+const UTXO_PREFIX: u8 = b'u';
+const ADDRESS_PREFIX: u8 = b'a';
+
+/// Current layout of the index/UTXO log. Stores written before it was
+/// recorded have no version entry and count as version 1.
+pub const CHAIN_STORE_VERSION: u32 = 2;
+
+/// Smallest allowed prune depth. Undo data for the blocks above it is always
+/// kept, so reorgs up to this depth can still be disconnected.
+pub const MIN_PRUNE_DEPTH: u64 = 100;
+
+#[derive(Debug, Clone)]
+pub struct StoreConfig {
+    /// Delete raw blocks more than this many blocks below the tip
+    pub prune_depth: Option<u64>,
+    pub max_segment_size: u64,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        StoreConfig {
+            prune_depth: None,
+            max_segment_size: MAX_SEGMENT_SIZE,
+        }
+    }
+}
+
+/// How far raw block data has been pruned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneState {
+    /// Segment files below this number have been deleted
+    pub next_file: u32,
+    /// Highest block height whose data has been deleted
+    pub pruned_height: u64,
+}
+
+/// Everything we keep about a stored block besides its transactions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockIndexEntry {
+    pub height: u64,
+    pub hash: Hash,
+    pub prev_hash: Hash,
+    pub timestamp: u128,
+    pub nonce: u64,
+    pub tx_count: u32,
+    pub block_pos: FilePos,
+    pub undo_pos: FilePos,
+}
+
+impl BlockIndexEntry {
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            index: self.height,
+            prev_hash: self.prev_hash.clone(),
+            timestamp: self.timestamp,
+            nonce: self.nonce,
+            hash: self.hash.clone(),
+        }
+    }
+}
+
+/// End of the last block/undo records referenced by a committed batch.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct LastPos {
+    block: FilePos,
+    undo: FilePos,
+}
+
+/// Persistent chain storage for a node's data directory.
+///
+/// Layout:
+/// - `blocks/blkNNNNN.dat`, `blocks/revNNNNN.dat` — append-only block and undo files
+/// - `chainstate.log` — format version, block index (by hash and height), the UTXO set and the tip
+///
+/// Connecting a block writes the raw block and its undo data first, then
+/// commits the index and UTXO changes as a single batch. A crash before the
+/// batch lands leaves only unreferenced bytes, which are truncated on open.
+///
+/// With pruning enabled, whole segment pairs whose blocks are all deeper than
+/// `prune_depth` are deleted after each connected block. Index entries
+/// (headers) and the UTXO set are kept.
+pub struct ChainStore {
+    blocks: BlockStore,
+    db: KvLog,
+    prune_depth: Option<u64>,
+}
+
+impl ChainStore {
+    pub fn open(data_dir: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::open_with_config(data_dir, StoreConfig::default())
+    }
+
+    pub fn open_with_config(
+        data_dir: impl AsRef<Path>,
+        config: StoreConfig,
+    ) -> Result<Self, StorageError> {
+        if let Some(depth) = config.prune_depth {
+            if depth < MIN_PRUNE_DEPTH {
+                return Err(StorageError::Config(format!(
+                    "prune depth {} is below the minimum of {}",
+                    depth, MIN_PRUNE_DEPTH
+                )));
+            }
+        }
+
+        let data_dir = data_dir.as_ref();
+        std::fs::create_dir_all(data_dir)?;
+
+        let mut store = ChainStore {
+            blocks: BlockStore::open_with_segment_size(
+                data_dir.join("blocks"),
+                config.max_segment_size,
+            )?,
+            db: KvLog::open(data_dir.join("chainstate.log"))?,
+            prune_depth: config.prune_depth,
+        };
+        store.check_version()?;
+        store.recover()?;
+        Ok(store)
+    }
+
+    /// Stamp a new store with `CHAIN_STORE_VERSION`, or refuse one written
+    /// in another format
+    fn check_version(&mut self) -> Result<(), StorageError> {
+        let version = match self.get::<u32>(VERSION_KEY)? {
+            Some(version) => version,
+            None if self.db.is_empty() => {
+                let mut batch = WriteBatch::new();
+                batch.put(VERSION_KEY.to_vec(), bincode::serialize(&CHAIN_STORE_VERSION)?);
+                return self.db.commit(batch);
+            }
+            None => 1,
+        };
+
+        if version != CHAIN_STORE_VERSION {
+            return Err(StorageError::UnsupportedVersion { what: "chain store", version });
+        }
+        Ok(())
+    }
+
+    /// Truncate block/undo bytes written after the last committed batch and
+    /// finish deleting segments a crash left behind during pruning.
+    fn recover(&self) -> Result<(), StorageError> {
+        if let Some(state) = self.prune_state()? {
+            for file in 0..state.next_file {
+                self.blocks.remove_segment(file)?;
+            }
+        }
+
+        let current = self.blocks.current_file();
+
+        let (block_end, undo_end) = match self.get::<LastPos>(LAST_POS_KEY)? {
+            Some(last) if last.block.file == current => (
+                last.block.offset + last.block.len as u64,
+                last.undo.offset + last.undo.len as u64,
+            ),
+            _ => (0, 0),
+        };
+
+        self.blocks.truncate(SegmentKind::Block, current, block_end)?;
+        self.blocks.truncate(SegmentKind::Undo, current, undo_end)?;
+        Ok(())
+    }
+
+    pub fn tip(&self) -> Result<Option<BlockIndexEntry>, StorageError> {
+        match self.get::<Hash>(TIP_KEY)? {
+            Some(hash) => self.block_entry(&hash),
+            None => Ok(None),
+        }
+    }
+
+    /// Height of the best block, falling back to the snapshot base when the
+    /// store was bootstrapped from a UTXO snapshot and no block is connected yet.
+    pub fn height(&self) -> Result<Option<u64>, StorageError> {
+        match self.tip()? {
+            Some(entry) => Ok(Some(entry.height)),
+            None => Ok(self.snapshot_base()?.map(|base| base.height)),
+        }
+    }
+
+    /// Header of the snapshot this store was bootstrapped from, if any.
+    pub fn snapshot_base(&self) -> Result<Option<SnapshotHeader>, StorageError> {
+        self.get(SNAPSHOT_BASE_KEY)
+    }
+
+    /// Commitment over the persisted UTXO set.
+    pub fn utxo_commitment(&self) -> Result<Hash, StorageError> {
+        Ok(self.commitment_acc()?.finalize())
+    }
+
+    fn commitment_acc(&self) -> Result<MultisetHash, StorageError> {
+        match self.get::<[u8; 32]>(COMMITMENT_KEY)? {
+            Some(bytes) => MultisetHash::from_bytes(&bytes)
+                .ok_or_else(|| StorageError::Corrupt("invalid UTXO commitment".into())),
+            None => Ok(MultisetHash::default()),
+        }
+    }
+
+    /// Seed an empty store with a verified UTXO snapshot.
+    ///
+    /// Blocks connected afterwards must build on `header.block_hash`.
+    pub fn load_snapshot(
+        &mut self,
+        header: &SnapshotHeader,
+        utxo_set: &UtxoSet,
+    ) -> Result<(), StorageError> {
+        if self.height()?.is_some() || self.db.scan_prefix(&[UTXO_PREFIX]).next().is_some() {
+            return Err(StorageError::Corrupt("snapshot can only be loaded into an empty store".into()));
+        }
+
+        let mut batch = WriteBatch::new();
+        let mut acc = MultisetHash::default();
+
+        for (outpoint, output) in utxo_set.iter() {
+            put_utxo(&mut batch, &mut acc, outpoint, output)?;
+        }
+
+        batch.put(COMMITMENT_KEY.to_vec(), bincode::serialize(&acc.to_bytes())?);
+        batch.put(SNAPSHOT_BASE_KEY.to_vec(), bincode::serialize(header)?);
+        self.db.commit(batch)
+    }
+
+    pub fn block_entry(&self, hash: &Hash) -> Result<Option<BlockIndexEntry>, StorageError> {
+        self.get(&block_key(hash))
+    }
+
+    pub fn hash_at_height(&self, height: u64) -> Result<Option<Hash>, StorageError> {
+        self.get(&height_key(height))
+    }
+
+    pub fn get_block(&self, hash: &Hash) -> Result<Option<Block>, StorageError> {
+        match self.block_entry(hash)? {
+            Some(entry) => {
+                self.ensure_not_pruned(&entry)?;
+                Ok(Some(self.blocks.read(SegmentKind::Block, entry.block_pos)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_block_at_height(&self, height: u64) -> Result<Option<Block>, StorageError> {
+        match self.hash_at_height(height)? {
+            Some(hash) => self.get_block(&hash),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_utxo(&self, outpoint: &OutPoint) -> Result<Option<TxOutput>, StorageError> {
+        self.get(&utxo_key(outpoint))
+    }
+
+    /// All unspent outputs paying to `address`, in outpoint order.
+    pub fn find_unspent(&self, address: &Address) -> Result<Vec<(OutPoint, TxOutput)>, StorageError> {
+        self.find_unspent_page(address, None, usize::MAX)
+    }
+
+    /// Up to `limit` unspent outputs of `address`, starting after `after`.
+    ///
+    /// Served from the address index, so only that address's entries are read.
+    pub fn find_unspent_page(
+        &self,
+        address: &Address,
+        after: Option<&OutPoint>,
+        limit: usize,
+    ) -> Result<Vec<(OutPoint, TxOutput)>, StorageError> {
+        let prefix = address_prefix(address);
+        let start = match after {
+            Some(outpoint) => address_key(address, outpoint),
+            None => prefix.clone(),
+        };
+
+        let mut found = Vec::new();
+        for (key, _) in self.db.scan_prefix_from(&prefix, &start) {
+            if found.len() >= limit {
+                break;
+            }
+
+            let outpoint = decode_outpoint(&key[prefix.len()..])?;
+            if after == Some(&outpoint) {
+                continue;
+            }
+
+            let out = self.get_utxo(&outpoint)?.ok_or_else(|| {
+                StorageError::Corrupt("address index points at a spent output".into())
+            })?;
+            found.push((outpoint, out));
+        }
+
+        Ok(found)
+    }
+
+    /// Load the persisted UTXO set into memory.
+    pub fn load_utxo_set(&self) -> Result<UtxoSet, StorageError> {
+        let mut utxo_set = UtxoSet::default();
+
+        for (key, value) in self.db.scan_prefix(&[UTXO_PREFIX]) {
+            let outpoint = decode_outpoint(&key[1..])?;
+            utxo_set.insert(outpoint, bincode::deserialize(value)?);
+        }
+
+        Ok(utxo_set)
+    }
+
+    /// Append `block` on top of the current tip and update the UTXO set.
+    pub fn connect_block(&mut self, block: &Block) -> Result<BlockUndo, StorageError> {
+        let tip = self.tip()?;
+        let base = self.snapshot_base()?;
+        let height = match (&tip, &base) {
+            (Some(entry), _) if entry.hash == block.prev_hash => entry.height + 1,
+            (None, Some(base)) if base.block_hash == block.prev_hash => base.height + 1,
+            (None, None) => 0,
+            _ => {
+                return Err(StorageError::Corrupt(format!(
+                    "block {} does not extend the stored tip",
+                    hex::encode(block.hash.0)
+                )))
+            }
+        };
+
+        let mut batch = WriteBatch::new();
+        let mut acc = self.commitment_acc()?;
+        let mut undo = BlockUndo::default();
+
+        // 1. Spend inputs and create outputs, collecting undo data
+        let mut created: Vec<(OutPoint, &TxOutput)> = Vec::new();
+        for tx in &block.transactions {
+            for input in &tx.inputs {
+                let outpoint = (input.txid.clone(), input.index);
+
+                // Outputs created earlier in this same block are not in the db yet
+                if let Some(pos) = created.iter().position(|(o, _)| *o == outpoint) {
+                    let (_, out) = created.remove(pos);
+                    delete_utxo(&mut batch, &mut acc, &outpoint, out);
+                    continue;
+                }
+
+                let out = self
+                    .get_utxo(&outpoint)?
+                    .ok_or_else(|| StorageError::MissingUtxo(hex::encode(input.txid.0), input.index))?;
+                delete_utxo(&mut batch, &mut acc, &outpoint, &out);
+                undo.spent.push((outpoint, out));
+            }
+
+            for (i, output) in tx.outputs.iter().enumerate() {
+                let outpoint = (tx.id.clone(), i as u32);
+                put_utxo(&mut batch, &mut acc, &outpoint, output)?;
+                created.push((outpoint, output));
+            }
+        }
+
+        // 2. Persist raw block and undo data before anything references them
+        let block_pos = self.blocks.append(SegmentKind::Block, block)?;
+        let undo_pos = self.blocks.append(SegmentKind::Undo, &undo)?;
+
+        // 3. Commit index, UTXO changes and new tip atomically
+        let entry = BlockIndexEntry {
+            height,
+            hash: block.hash.clone(),
+            prev_hash: block.prev_hash.clone(),
+            timestamp: block.timestamp,
+            nonce: block.nonce,
+            tx_count: block.transactions.len() as u32,
+            block_pos,
+            undo_pos,
+        };
+
+        batch.put(block_key(&block.hash), bincode::serialize(&entry)?);
+        batch.put(height_key(height), bincode::serialize(&block.hash)?);
+        batch.put(TIP_KEY.to_vec(), bincode::serialize(&block.hash)?);
+
+        let segment_max = self.get::<u64>(&segment_key(block_pos.file))?.unwrap_or(0);
+        batch.put(segment_key(block_pos.file), bincode::serialize(&segment_max.max(height))?);
+        batch.put(COMMITMENT_KEY.to_vec(), bincode::serialize(&acc.to_bytes())?);
+        batch.put(
+            LAST_POS_KEY.to_vec(),
+            bincode::serialize(&LastPos { block: block_pos, undo: undo_pos })?,
+        );
+        self.db.commit(batch)?;
+
+        if self.prune_depth.is_some() {
+            self.prune()?;
+        }
+
+        Ok(undo)
+    }
+
+    /// Disconnect the current tip, restoring the outputs it spent.
+    pub fn disconnect_tip(&mut self) -> Result<Option<Block>, StorageError> {
+        let entry = match self.tip()? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        self.ensure_not_pruned(&entry)?;
+        let block: Block = self.blocks.read(SegmentKind::Block, entry.block_pos)?;
+        let undo: BlockUndo = self.blocks.read(SegmentKind::Undo, entry.undo_pos)?;
+
+        let mut batch = WriteBatch::new();
+        let mut acc = self.commitment_acc()?;
+
+        // Outputs spent within the block itself were never in the set
+        let spent_in_block: Vec<OutPoint> = block
+            .transactions
+            .iter()
+            .flat_map(|tx| tx.inputs.iter().map(|i| (i.txid.clone(), i.index)))
+            .collect();
+
+        for tx in &block.transactions {
+            for (i, output) in tx.outputs.iter().enumerate() {
+                let outpoint = (tx.id.clone(), i as u32);
+                if !spent_in_block.contains(&outpoint) {
+                    delete_utxo(&mut batch, &mut acc, &outpoint, output);
+                }
+            }
+        }
+        for (outpoint, out) in &undo.spent {
+            put_utxo(&mut batch, &mut acc, outpoint, out)?;
+        }
+
+        batch.put(COMMITMENT_KEY.to_vec(), bincode::serialize(&acc.to_bytes())?);
+        batch.delete(height_key(entry.height));
+        if self.block_entry(&entry.prev_hash)?.is_none() {
+            // Back at genesis or at the snapshot base
+            batch.delete(TIP_KEY.to_vec());
+        } else {
+            batch.put(TIP_KEY.to_vec(), bincode::serialize(&entry.prev_hash)?);
+        }
+        self.db.commit(batch)?;
+
+        Ok(Some(block))
+    }
+
+    pub fn prune_state(&self) -> Result<Option<PruneState>, StorageError> {
+        self.get(PRUNE_STATE_KEY)
+    }
+
+    /// Whether this store runs in pruning mode or has already deleted blocks.
+    pub fn is_pruned(&self) -> Result<bool, StorageError> {
+        Ok(self.prune_depth.is_some() || self.prune_state()?.is_some())
+    }
+
+    fn ensure_not_pruned(&self, entry: &BlockIndexEntry) -> Result<(), StorageError> {
+        match self.prune_state()? {
+            Some(state) if entry.block_pos.file < state.next_file => {
+                Err(StorageError::BlockPruned(entry.height))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Delete segment pairs whose blocks are all more than `prune_depth`
+    /// below the tip. Returns the number of segments removed.
+    ///
+    /// The current segment is never removed. The new prune state is
+    /// committed before files are deleted, so a crash in between only leaves
+    /// files that `open` deletes again.
+    pub fn prune(&mut self) -> Result<u32, StorageError> {
+        let depth = match self.prune_depth {
+            Some(depth) => depth,
+            None => return Ok(0),
+        };
+        let height = match self.height()? {
+            Some(height) if height >= depth => height,
+            _ => return Ok(0),
+        };
+        let cutoff = height - depth;
+
+        let mut state = self.prune_state()?.unwrap_or_default();
+        let first = state.next_file;
+
+        for file in first..self.blocks.current_file() {
+            match self.get::<u64>(&segment_key(file))? {
+                Some(max_height) if max_height <= cutoff => {
+                    state.next_file = file + 1;
+                    state.pruned_height = state.pruned_height.max(max_height);
+                }
+                _ => break,
+            }
+        }
+
+        if state.next_file == first {
+            return Ok(0);
+        }
+
+        let mut batch = WriteBatch::new();
+        batch.put(PRUNE_STATE_KEY.to_vec(), bincode::serialize(&state)?);
+        for file in first..state.next_file {
+            batch.delete(segment_key(file));
+        }
+        self.db.commit(batch)?;
+
+        for file in first..state.next_file {
+            self.blocks.remove_segment(file)?;
+        }
+
+        Ok(state.next_file - first)
+    }
+
+    /// Rewrite the chainstate log without superseded records.
+    pub fn compact(&mut self) -> Result<(), StorageError> {
+        self.db.compact()
+    }
+
+    fn get<T: serde::de::DeserializeOwned>(&self, key: &[u8]) -> Result<Option<T>, StorageError> {
+        match self.db.get(key) {
+            Some(bytes) => Ok(Some(bincode::deserialize(bytes)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn block_key(hash: &Hash) -> Vec<u8> {
+    let mut key = vec![BLOCK_PREFIX];
+    key.extend_from_slice(&hash.0);
+    key
+}
+
+fn height_key(height: u64) -> Vec<u8> {
+    let mut key = vec![HEIGHT_PREFIX];
+    key.extend_from_slice(&height.to_be_bytes());
+    key
+}
+
+fn segment_key(file: u32) -> Vec<u8> {
+    let mut key = vec![SEGMENT_PREFIX];
+    key.extend_from_slice(&file.to_be_bytes());
+    key
+}
+
+fn put_utxo(
+    batch: &mut WriteBatch,
+    acc: &mut MultisetHash,
+    outpoint: &OutPoint,
+    output: &TxOutput,
+) -> Result<(), StorageError> {
+    batch.put(utxo_key(outpoint), bincode::serialize(output)?);
+    batch.put(address_key(&output.address, outpoint), Vec::new());
+    acc.insert(&commitment_element(outpoint, output));
+    Ok(())
+}
+
+fn delete_utxo(batch: &mut WriteBatch, acc: &mut MultisetHash, outpoint: &OutPoint, output: &TxOutput) {
+    batch.delete(utxo_key(outpoint));
+    batch.delete(address_key(&output.address, outpoint));
+    acc.remove(&commitment_element(outpoint, output));
+}
+
+fn encode_outpoint(key: &mut Vec<u8>, outpoint: &OutPoint) {
+    key.extend_from_slice(&outpoint.0 .0);
+    key.extend_from_slice(&outpoint.1.to_be_bytes());
+}
+
+fn decode_outpoint(bytes: &[u8]) -> Result<OutPoint, StorageError> {
+    if bytes.len() != 32 + 4 {
+        return Err(StorageError::Corrupt("malformed outpoint key".into()));
+    }
+
+    let mut txid = [0u8; 32];
+    txid.copy_from_slice(&bytes[0..32]);
+    let index = u32::from_be_bytes(bytes[32..36].try_into().unwrap());
+    Ok((Hash(txid), index))
+}
+
+fn utxo_key(outpoint: &OutPoint) -> Vec<u8> {
+    let mut key = vec![UTXO_PREFIX];
+    encode_outpoint(&mut key, outpoint);
+    key
+}
+
+// Address index: `a | version | hash | outpoint` -> ()
+fn address_prefix(address: &Address) -> Vec<u8> {
+    let mut key = vec![ADDRESS_PREFIX];
+    key.extend_from_slice(&address.to_bytes());
+    key
+}
+
+fn address_key(address: &Address, outpoint: &OutPoint) -> Vec<u8> {
+    let mut key = address_prefix(address);
+    encode_outpoint(&mut key, outpoint);
+    key
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::{record, StorageError};
+
+/// Compact the log once it holds this many superseded records.
+const COMPACT_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BatchOp {
+    Put(Vec<u8>, Vec<u8>),
+    Delete(Vec<u8>),
+}
+
+/// A set of writes that is committed to disk as a single record, so either
+/// all of them survive a crash or none do.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct WriteBatch {
+    pub ops: Vec<BatchOp>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        WriteBatch::default()
+    }
+
+    pub fn put(&mut self, key: Vec<u8>, value: Vec<u8>) {
+        self.ops.push(BatchOp::Put(key, value));
+    }
+
+    pub fn delete(&mut self, key: Vec<u8>) {
+        self.ops.push(BatchOp::Delete(key));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+/// Append-only key-value log.
+///
+/// Every committed batch is appended as one checksummed record and fsynced.
+/// On open the log is replayed into memory; a torn record at the tail (crash
+/// mid-write) is discarded and truncated away.
+pub struct KvLog {
+    path: PathBuf,
+    file: File,
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+    stale_records: usize,
+}
+
+impl KvLog {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let path = path.as_ref().to_path_buf();
+        let bytes = if path.exists() { fs::read(&path)? } else { Vec::new() };
+
+        let (records, valid_len) = record::decode_all(&bytes);
+        let mut entries = BTreeMap::new();
+
+        for payload in &records {
+            let batch: WriteBatch = bincode::deserialize(payload)?;
+            apply(&mut entries, batch);
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        // 1. Drop whatever a crash left after the last complete batch
+        if valid_len < bytes.len() {
+            file.set_len(valid_len as u64)?;
+            file.sync_all()?;
+        }
+
+        Ok(KvLog {
+            path,
+            file,
+            entries,
+            stale_records: records.len(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.entries.get(key).map(|v| v.as_slice())
+    }
+
+    /// Iterate all entries whose key starts with `prefix`, in key order.
+    pub fn scan_prefix<'a>(
+        &'a self,
+        prefix: &'a [u8],
+    ) -> impl Iterator<Item = (&'a [u8], &'a [u8])> + 'a {
+        self.scan_prefix_from(prefix, prefix)
+    }
+
+    /// Like `scan_prefix`, but starting at the first key `>= start`.
+    pub fn scan_prefix_from<'a>(
+        &'a self,
+        prefix: &'a [u8],
+        start: &[u8],
+    ) -> impl Iterator<Item = (&'a [u8], &'a [u8])> + 'a {
+        self.entries
+            .range(start.to_vec()..)
+            .take_while(move |(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (k.as_slice(), v.as_slice()))
+    }
+
+    /// Durably write `batch`, then apply it to the in-memory view.
+    pub fn commit(&mut self, batch: WriteBatch) -> Result<(), StorageError> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let payload = bincode::serialize(&batch)?;
+        self.file.write_all(&record::encode(&payload))?;
+        self.file.sync_data()?;
+
+        apply(&mut self.entries, batch);
+        self.stale_records += 1;
+
+        if self.stale_records >= COMPACT_THRESHOLD {
+            self.compact()?;
+        }
+
+        Ok(())
+    }
+
+    /// Rewrite the log as a single batch holding the live entries.
+    ///
+    /// The new log is written to a temp file and renamed over the old one,
+    /// so a crash during compaction leaves the previous log intact.
+    pub fn compact(&mut self) -> Result<(), StorageError> {
+        let mut batch = WriteBatch::new();
+        for (k, v) in &self.entries {
+            batch.put(k.clone(), v.clone());
+        }
+
+        let tmp_path = self.path.with_extension("compact");
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            if !batch.is_empty() {
+                tmp.write_all(&record::encode(&bincode::serialize(&batch)?))?;
+            }
+            tmp.sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.stale_records = 1;
+        Ok(())
+    }
+}
+
+fn apply(entries: &mut BTreeMap<Vec<u8>, Vec<u8>>, batch: WriteBatch) {
+    for op in batch.ops {
+        match op {
+            BatchOp::Put(k, v) => {
+                entries.insert(k, v);
+            }
+            BatchOp::Delete(k) => {
+                entries.remove(&k);
+            }
+        }
+    }
+}
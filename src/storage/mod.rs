@@ -0,0 +1,66 @@
+pub mod block_store;
+pub mod chain_store;
+pub mod kv;
+mod record;
+pub mod snapshot;
+pub mod store;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+
+pub use chain_store::{
+    BlockIndexEntry, ChainStore, PruneState, StoreConfig, CHAIN_STORE_VERSION, MIN_PRUNE_DEPTH,
+};
+pub use snapshot::{export_snapshot, import_snapshot, SnapshotHeader};
+pub use store::Store;
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("encoding error: {0}")]
+    Encoding(#[from] bincode::Error),
+
+    #[error("corrupt storage: {0}")]
+    Corrupt(String),
+
+    #[error("missing UTXO {0}:{1}")]
+    MissingUtxo(String, u32),
+
+    #[error("block at height {0} has been pruned")]
+    BlockPruned(u64),
+
+    #[error("unsupported {what} version {version}")]
+    UnsupportedVersion { what: &'static str, version: u32 },
+
+    #[error("invalid storage config: {0}")]
+    Config(String),
+
+    #[error("UTXO commitment mismatch: expected {expected}, got {actual}")]
+    CommitmentMismatch { expected: String, actual: String },
+}
+
+/// Write a file only its owner can read, for secrets
+#[cfg(unix)]
+pub(crate) fn write_private(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn write_private(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    fs::write(path, bytes)
+}
@@ -0,0 +1,52 @@
+use sha2::{Digest, Sha256};
+
+/// Size of the `[len][checksum]` prefix written before every payload.
+pub const HEADER_LEN: usize = 8;
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let digest = Sha256::digest(payload);
+    let mut out = [0u8; 4];
+    out.copy_from_slice(&digest[0..4]);
+    out
+}
+
+/// Frame a payload as `[u32 LE length][4-byte checksum][payload]`.
+pub fn encode(payload: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_LEN + payload.len());
+    buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&checksum(payload));
+    buf.extend_from_slice(payload);
+    buf
+}
+
+/// Decode the record starting at `offset`.
+///
+/// Returns `None` when the record is truncated or its checksum does not
+/// match, which is what a crash in the middle of a write leaves behind.
+pub fn decode_at(bytes: &[u8], offset: usize) -> Option<&[u8]> {
+    let header = bytes.get(offset..offset + HEADER_LEN)?;
+    let len = u32::from_le_bytes(header[0..4].try_into().ok()?) as usize;
+    let payload = bytes.get(offset + HEADER_LEN..offset + HEADER_LEN + len)?;
+
+    if checksum(payload) != header[4..8] {
+        return None;
+    }
+
+    Some(payload)
+}
+
+/// Walk every intact record from the start of `bytes`.
+///
+/// The second value is the length of the valid prefix; anything after it
+/// is a torn write and should be truncated by the caller.
+pub fn decode_all(bytes: &[u8]) -> (Vec<&[u8]>, usize) {
+    let mut records = Vec::new();
+    let mut offset = 0;
+
+    while let Some(payload) = decode_at(bytes, offset) {
+        records.push(payload);
+        offset += HEADER_LEN + payload.len();
+    }
+
+    (records, offset)
+}
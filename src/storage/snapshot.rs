@@ -0,0 +1,139 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::{record, StorageError};
+use crate::blockchain::transaction::TxOutput;
+use crate::blockchain::utxo::{OutPoint, UtxoSet};
+use crate::crypto::hash::Hash;
+
+pub const SNAPSHOT_MAGIC: &[u8; 6] = b"ANSNAP";
+pub const SNAPSHOT_VERSION: u8 = 2;
+
+/// Describes the chain position and contents of a UTXO snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    pub height: u64,
+    pub block_hash: Hash,
+    pub utxo_count: u64,
+    pub commitment: Hash,
+}
+
+/// Write `utxo_set` as the UTXO set at (`height`, `block_hash`).
+///
+/// File layout:
+/// - 6-byte magic `ANSNAP`, 1-byte version
+/// - header record (`SnapshotHeader`)
+/// - one record per UTXO, sorted by outpoint
+///
+/// Records use the same checksummed framing as the block files. The file is
+/// written next to `path` and renamed into place once complete.
+pub fn export_snapshot(
+    path: impl AsRef<Path>,
+    height: u64,
+    block_hash: Hash,
+    utxo_set: &UtxoSet,
+) -> Result<SnapshotHeader, StorageError> {
+    let path = path.as_ref();
+    let header = SnapshotHeader {
+        height,
+        block_hash,
+        utxo_count: utxo_set.len() as u64,
+        commitment: utxo_set.commitment(),
+    };
+
+    let mut entries: Vec<(&OutPoint, &TxOutput)> = utxo_set.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+
+    let tmp_path = path.with_extension("partial");
+    {
+        let mut out = BufWriter::new(File::create(&tmp_path)?);
+        out.write_all(SNAPSHOT_MAGIC)?;
+        out.write_all(&[SNAPSHOT_VERSION])?;
+        out.write_all(&record::encode(&bincode::serialize(&header)?))?;
+
+        for entry in entries {
+            out.write_all(&record::encode(&bincode::serialize(&entry)?))?;
+        }
+
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+
+    Ok(header)
+}
+
+/// Read a snapshot and check it is internally consistent: every record is
+/// intact, entries are sorted and unique, and the recomputed commitment and
+/// count match the header.
+pub fn read_snapshot(path: impl AsRef<Path>) -> Result<(SnapshotHeader, UtxoSet), StorageError> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+
+    let prefix_len = SNAPSHOT_MAGIC.len() + 1;
+    if bytes.len() < prefix_len || &bytes[..SNAPSHOT_MAGIC.len()] != SNAPSHOT_MAGIC {
+        return Err(StorageError::Corrupt("not a UTXO snapshot".into()));
+    }
+    if bytes[SNAPSHOT_MAGIC.len()] != SNAPSHOT_VERSION {
+        return Err(StorageError::UnsupportedVersion {
+            what: "snapshot",
+            version: bytes[SNAPSHOT_MAGIC.len()].into(),
+        });
+    }
+
+    let body = &bytes[prefix_len..];
+    let (records, valid_len) = record::decode_all(body);
+    if valid_len != body.len() || records.is_empty() {
+        return Err(StorageError::Corrupt("truncated or damaged snapshot".into()));
+    }
+
+    let header: SnapshotHeader = bincode::deserialize(records[0])?;
+    let mut utxo_set = UtxoSet::default();
+    let mut last: Option<OutPoint> = None;
+
+    for payload in &records[1..] {
+        let (outpoint, output): (OutPoint, TxOutput) = bincode::deserialize(payload)?;
+
+        if last.as_ref().is_some_and(|prev| *prev >= outpoint) {
+            return Err(StorageError::Corrupt("snapshot entries out of order".into()));
+        }
+        last = Some(outpoint.clone());
+        utxo_set.insert(outpoint, output);
+    }
+
+    if utxo_set.len() as u64 != header.utxo_count {
+        return Err(StorageError::Corrupt(format!(
+            "snapshot declares {} UTXOs but contains {}",
+            header.utxo_count,
+            utxo_set.len()
+        )));
+    }
+    if utxo_set.commitment() != header.commitment {
+        return Err(StorageError::CommitmentMismatch {
+            expected: hex::encode(header.commitment.0),
+            actual: hex::encode(utxo_set.commitment().0),
+        });
+    }
+
+    Ok((header, utxo_set))
+}
+
+/// Read a snapshot and accept it only if it matches `trusted_commitment`,
+/// e.g. one hard-coded in the node or obtained out of band.
+pub fn import_snapshot(
+    path: impl AsRef<Path>,
+    trusted_commitment: &Hash,
+) -> Result<(SnapshotHeader, UtxoSet), StorageError> {
+    let (header, utxo_set) = read_snapshot(path)?;
+
+    if header.commitment != *trusted_commitment {
+        return Err(StorageError::CommitmentMismatch {
+            expected: hex::encode(trusted_commitment.0),
+            actual: hex::encode(header.commitment.0),
+        });
+    }
+
+    Ok((header, utxo_set))
+}
@@ -0,0 +1,210 @@
+use super::{ChainStore, StorageError};
+use crate::blockchain::address::Address;
+use crate::blockchain::block::{Block, BlockHeader};
+use crate::blockchain::transaction::TxOutput;
+use crate::blockchain::utxo::{BlockUndo, OutPoint, UtxoSet};
+use crate::crypto::hash::Hash;
+
+/// Block and UTXO persistence used by `State` and `TxBuilder`.
+///
+/// `UtxoSet` keeps everything in memory and is what unit tests use;
+/// `ChainStore` is the on-disk backend for nodes.
+pub trait Store {
+    fn get_utxo(&self, outpoint: &OutPoint) -> Result<Option<TxOutput>, StorageError>;
+
+    fn find_unspent(&self, address: &Address) -> Result<Vec<(OutPoint, TxOutput)>, StorageError>;
+
+    /// Up to `limit` outputs of `address` in outpoint order, starting after `after`
+    fn find_unspent_page(
+        &self,
+        address: &Address,
+        after: Option<&OutPoint>,
+        limit: usize,
+    ) -> Result<Vec<(OutPoint, TxOutput)>, StorageError>;
+
+    fn available_balance(&self, address: &Address) -> Result<u64, StorageError> {
+        Ok(self.find_unspent(address)?.iter().map(|(_, out)| out.value).sum())
+    }
+
+    /// Order-independent commitment over the whole UTXO set
+    fn utxo_commitment(&self) -> Result<Hash, StorageError>;
+
+    /// Height and hash of the best block, if any block is stored
+    fn tip(&self) -> Result<Option<(u64, Hash)>, StorageError>;
+
+    fn get_block(&self, hash: &Hash) -> Result<Option<Block>, StorageError>;
+
+    fn get_block_at_height(&self, height: u64) -> Result<Option<Block>, StorageError>;
+
+    /// Header of the main-chain block at `height`
+    fn get_header_at_height(&self, height: u64) -> Result<Option<BlockHeader>, StorageError> {
+        Ok(self.get_block_at_height(height)?.map(|block| block.header()))
+    }
+
+    /// Height of `hash` if it is on the main chain
+    fn main_chain_height(&self, hash: &Hash) -> Result<Option<u64>, StorageError> {
+        let header = match self.get_block(hash)? {
+            Some(block) => block.header(),
+            None => return Ok(None),
+        };
+
+        match self.get_header_at_height(header.index)? {
+            Some(main) if main.hash == *hash => Ok(Some(header.index)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Append `block` on top of the tip, spending its inputs and adding its outputs
+    fn connect_block(&mut self, block: &Block) -> Result<BlockUndo, StorageError>;
+
+    /// Remove the tip block, restoring the outputs it spent
+    fn disconnect_tip(&mut self) -> Result<Option<Block>, StorageError>;
+}
+
+impl Store for UtxoSet {
+    fn get_utxo(&self, outpoint: &OutPoint) -> Result<Option<TxOutput>, StorageError> {
+        Ok(self.get(outpoint).cloned())
+    }
+
+    fn find_unspent(&self, address: &Address) -> Result<Vec<(OutPoint, TxOutput)>, StorageError> {
+        Ok(UtxoSet::find_unspent(self, address))
+    }
+
+    fn find_unspent_page(
+        &self,
+        address: &Address,
+        after: Option<&OutPoint>,
+        limit: usize,
+    ) -> Result<Vec<(OutPoint, TxOutput)>, StorageError> {
+        Ok(UtxoSet::find_unspent_page(self, address, after, limit))
+    }
+
+    fn utxo_commitment(&self) -> Result<Hash, StorageError> {
+        Ok(self.commitment())
+    }
+
+    fn tip(&self) -> Result<Option<(u64, Hash)>, StorageError> {
+        Ok(self
+            .chain
+            .last()
+            .map(|hash| (self.chain.len() as u64 - 1, hash.clone())))
+    }
+
+    fn get_block(&self, hash: &Hash) -> Result<Option<Block>, StorageError> {
+        Ok(self.blocks.get(hash).map(|(block, _)| block.clone()))
+    }
+
+    fn get_block_at_height(&self, height: u64) -> Result<Option<Block>, StorageError> {
+        match self.chain.get(height as usize) {
+            Some(hash) => Store::get_block(self, hash),
+            None => Ok(None),
+        }
+    }
+
+    fn connect_block(&mut self, block: &Block) -> Result<BlockUndo, StorageError> {
+        if self.chain.last().is_some_and(|tip| *tip != block.prev_hash) {
+            return Err(StorageError::Corrupt(format!(
+                "block {} does not extend the stored tip",
+                hex::encode(block.hash.0)
+            )));
+        }
+
+        // Reject blocks spending unknown outputs, same as the disk backend
+        let mut created: Vec<OutPoint> = Vec::new();
+        for tx in &block.transactions {
+            for input in &tx.inputs {
+                let outpoint = (input.txid.clone(), input.index);
+                if let Some(pos) = created.iter().position(|o| *o == outpoint) {
+                    created.remove(pos);
+                } else if !self.contains(&outpoint) {
+                    return Err(StorageError::MissingUtxo(hex::encode(input.txid.0), input.index));
+                }
+            }
+            created.extend((0..tx.outputs.len() as u32).map(|i| (tx.id.clone(), i)));
+        }
+
+        let undo = self.apply_block(block);
+        self.chain.push(block.hash.clone());
+        self.blocks
+            .insert(block.hash.clone(), (block.clone(), undo.clone()));
+        Ok(undo)
+    }
+
+    fn disconnect_tip(&mut self) -> Result<Option<Block>, StorageError> {
+        let hash = match self.chain.pop() {
+            Some(hash) => hash,
+            None => return Ok(None),
+        };
+
+        let (block, undo) = self
+            .blocks
+            .get(&hash)
+            .cloned()
+            .ok_or_else(|| StorageError::Corrupt("tip block missing".into()))?;
+        self.undo_block(&block, &undo);
+        Ok(Some(block))
+    }
+}
+
+impl Store for ChainStore {
+    fn get_utxo(&self, outpoint: &OutPoint) -> Result<Option<TxOutput>, StorageError> {
+        ChainStore::get_utxo(self, outpoint)
+    }
+
+    fn find_unspent(&self, address: &Address) -> Result<Vec<(OutPoint, TxOutput)>, StorageError> {
+        ChainStore::find_unspent(self, address)
+    }
+
+    fn find_unspent_page(
+        &self,
+        address: &Address,
+        after: Option<&OutPoint>,
+        limit: usize,
+    ) -> Result<Vec<(OutPoint, TxOutput)>, StorageError> {
+        ChainStore::find_unspent_page(self, address, after, limit)
+    }
+
+    fn utxo_commitment(&self) -> Result<Hash, StorageError> {
+        ChainStore::utxo_commitment(self)
+    }
+
+    fn tip(&self) -> Result<Option<(u64, Hash)>, StorageError> {
+        match ChainStore::tip(self)? {
+            Some(entry) => Ok(Some((entry.height, entry.hash))),
+            None => Ok(self.snapshot_base()?.map(|base| (base.height, base.block_hash))),
+        }
+    }
+
+    fn get_block(&self, hash: &Hash) -> Result<Option<Block>, StorageError> {
+        ChainStore::get_block(self, hash)
+    }
+
+    fn get_block_at_height(&self, height: u64) -> Result<Option<Block>, StorageError> {
+        ChainStore::get_block_at_height(self, height)
+    }
+
+    // Served from the index so pruned blocks keep their headers
+    fn get_header_at_height(&self, height: u64) -> Result<Option<BlockHeader>, StorageError> {
+        match self.hash_at_height(height)? {
+            Some(hash) => Ok(self.block_entry(&hash)?.map(|entry| entry.header())),
+            None => Ok(None),
+        }
+    }
+
+    fn main_chain_height(&self, hash: &Hash) -> Result<Option<u64>, StorageError> {
+        match self.block_entry(hash)? {
+            Some(entry) if self.hash_at_height(entry.height)?.as_ref() == Some(hash) => {
+                Ok(Some(entry.height))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn connect_block(&mut self, block: &Block) -> Result<BlockUndo, StorageError> {
+        ChainStore::connect_block(self, block)
+    }
+
+    fn disconnect_tip(&mut self) -> Result<Option<Block>, StorageError> {
+        ChainStore::disconnect_tip(self)
+    }
+}
@@ -0,0 +1,118 @@
+use arcnova_chain::blockchain::{
+    address::{Address, AddressError, ADDRESS_HASH_SIZE, ADDRESS_VERSION},
+    params::{MAINNET, REGTEST, TESTNET},
+    signer::Signer,
+    state::State,
+    transaction::TxOutput,
+    tx_builder::{TxBuildError, TxBuilder},
+    utxo::UtxoSet,
+    wallet::Wallet,
+};
+use arcnova_chain::crypto::hash::Hash;
+use bech32::{Bech32, Hrp};
+
+mod common;
+use common::{block, coinbase};
+
+// TEST 1 — Addresses round-trip through Bech32m on every network
+#[test]
+fn test_bech32m_round_trip() {
+    let wallet = Wallet::new();
+    let address = wallet.address();
+    assert_eq!(Address::from_public_key(&wallet.public_key_bytes()), Some(address));
+    assert_eq!(address.version(), ADDRESS_VERSION);
+    assert!(Address::from_public_key(&[0u8; 31]).is_none());
+
+    for params in [&MAINNET, &TESTNET, &REGTEST] {
+        let shown = address.encode(params);
+        assert!(shown.starts_with(&format!("{}1", params.address_prefix)));
+        assert_eq!(Address::decode(&shown, params), Ok(address));
+        // Bech32 strings may be all upper case, e.g. for QR codes
+        assert_eq!(Address::decode(&shown.to_uppercase(), params), Ok(address));
+    }
+
+    // Versions ride along, even ones that cannot be paid yet
+    let future = Address::new(ADDRESS_VERSION + 1, *address.hash());
+    assert_eq!(Address::decode(&future.encode(&MAINNET), &MAINNET), Ok(future));
+    assert_ne!(future.encode(&MAINNET), address.encode(&MAINNET));
+}
+
+// TEST 2 — Typos, other networks and other checksums are refused
+#[test]
+fn test_decode_rejects_bad_addresses() {
+    let address = Wallet::new().address();
+    let shown = address.encode(&MAINNET);
+
+    // One mistyped character breaks the checksum
+    let last = shown.chars().last().unwrap();
+    let typo = format!("{}{}", &shown[..shown.len() - 1], if last == 'q' { 'p' } else { 'q' });
+    match Address::decode(&typo, &MAINNET) {
+        Err(AddressError::Encoding(_)) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    match Address::decode(&shown, &TESTNET) {
+        Err(AddressError::WrongPrefix { expected, found }) => {
+            assert_eq!(expected, "tarc");
+            assert_eq!(found, "arc");
+        }
+        _ => panic!("wrong error variant"),
+    }
+
+    // Same payload under the original Bech32 checksum
+    let hrp = Hrp::parse(MAINNET.address_prefix).unwrap();
+    let bech32 = bech32::encode::<Bech32>(hrp, &address.to_bytes()).unwrap();
+    assert!(Address::decode(&bech32, &MAINNET).is_err());
+
+    let short = bech32::encode::<bech32::Bech32m>(hrp, &address.to_bytes()[..12]).unwrap();
+    match Address::decode(&short, &MAINNET) {
+        Err(AddressError::InvalidLength(12)) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 3 — Unsupported versions are refused by the builder and by validation
+#[test]
+fn test_unsupported_versions_rejected() {
+    let wallet = Wallet::new();
+    let mut utxo_set = UtxoSet::default();
+    utxo_set.insert((Hash([1u8; 32]), 0), TxOutput { value: 10_000, address: wallet.address() });
+    let state = State { utxo_set };
+
+    let future = Address::new(ADDRESS_VERSION + 1, [3u8; ADDRESS_HASH_SIZE]);
+    let builder = TxBuilder::new(&state);
+    match builder.build_payment_tx(&wallet, &future, 1_000, 10) {
+        Err(TxBuildError::InvalidAddress(address)) => assert_eq!(address, future),
+        _ => panic!("wrong error variant"),
+    }
+
+    // Built by hand, a payment to it is still invalid
+    let mut tx = builder.build_payment_tx(&wallet, &Wallet::new().address(), 1_000, 10).unwrap();
+    assert!(state.validate_transaction(&tx));
+    tx.outputs[0].address = future;
+    tx.id = Hash([0u8; 32]);
+    tx.inputs[0].signature = Vec::new();
+    tx.id = tx.calculate_hash();
+    tx.inputs[0].signature = wallet.sign_digest(&tx.id).unwrap();
+    assert!(!state.validate_transaction(&tx));
+
+    // So is a coinbase paying it
+    assert!(state.validate_block(&block(1, Hash([0u8; 32]), vec![coinbase(1, wallet.address(), 50)])));
+    assert!(!state.validate_block(&block(1, Hash([0u8; 32]), vec![coinbase(1, future, 50)])));
+}
+
+// TEST 4 — Compact on the wire, hex in JSON files such as keystores
+#[test]
+fn test_address_serialization() {
+    let wallet = Wallet::new();
+    let address = wallet.address();
+
+    let bytes = bincode::serialize(&address).unwrap();
+    assert_eq!(bytes.len(), 1 + ADDRESS_HASH_SIZE);
+    assert_eq!(bincode::deserialize::<Address>(&bytes).unwrap(), address);
+
+    let json = serde_json::to_value(address).unwrap();
+    assert_eq!(json, serde_json::Value::String(hex::encode(address.to_bytes())));
+    assert_eq!(serde_json::from_value::<Address>(json).unwrap(), address);
+    assert!(serde_json::from_str::<Address>("\"00ff\"").is_err());
+}
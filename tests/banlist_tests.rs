@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use arcnova_chain::p2p::admin::{handle_admin_command, AdminCommand, AdminHandle};
+#[cfg(unix)]
+use arcnova_chain::p2p::admin::{
+    admin_request, bind_admin_socket, serve_admin_socket, AdminRequest, AdminResponse,
+};
+use arcnova_chain::p2p::banlist::{BanList, Misbehavior, BAN_THRESHOLD, DEFAULT_BAN_DURATION};
+
+mod common;
+use common::temp_dir;
+
+// TEST 1 — Misbehavior adds up until the peer is banned
+#[test]
+fn test_misbehavior_score_bans_peer() {
+    let mut bans = BanList::in_memory();
+    let now = 1_000;
+
+    for _ in 0..(BAN_THRESHOLD / Misbehavior::InvalidTransaction.penalty() - 1) {
+        assert!(!bans.misbehaving("peer-a", Misbehavior::InvalidTransaction, now));
+    }
+    assert!(!bans.is_banned("peer-a", now));
+    assert!(bans.misbehaving("peer-a", Misbehavior::MalformedMessage, now));
+    assert!(bans.is_banned("peer-a", now));
+    assert_eq!(bans.score("peer-a"), 0);
+
+    // One invalid block is enough
+    assert!(bans.misbehaving("peer-b", Misbehavior::InvalidBlock, now));
+    assert_eq!(bans.list(now).len(), 2);
+}
+
+// TEST 2 — Bans expire
+#[test]
+fn test_ban_expiry() {
+    let mut bans = BanList::in_memory();
+    bans.misbehaving("peer-a", Misbehavior::InvalidBlock, 0);
+    bans.ban("peer-b", Duration::from_secs(60), "manual", 0);
+
+    assert!(bans.is_banned("peer-b", 59));
+    assert!(!bans.is_banned("peer-b", 60));
+
+    let end = DEFAULT_BAN_DURATION.as_secs();
+    assert!(bans.is_banned("peer-a", end - 1));
+    bans.expire(end);
+    assert!(bans.list(0).is_empty());
+}
+
+// TEST 3 — Bans are saved across restarts, scores are not
+#[test]
+fn test_ban_persistence() {
+    let dir = temp_dir("bans");
+    let path = dir.join("bans.json");
+
+    let mut bans = BanList::open(&path).unwrap();
+    bans.misbehaving("peer-a", Misbehavior::InvalidBlock, 10);
+    bans.misbehaving("peer-b", Misbehavior::InvalidTransaction, 10);
+    bans.save().unwrap();
+
+    let mut reopened = BanList::open(&path).unwrap();
+    assert!(reopened.is_banned("peer-a", 10));
+    assert_eq!(reopened.score("peer-b"), 0);
+
+    assert!(reopened.unban("peer-a"));
+    assert!(!reopened.unban("peer-a"));
+    reopened.save().unwrap();
+    assert!(BanList::open(&path).unwrap().list(10).is_empty());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+// TEST 4 — Admin requests list, ban and unban peers
+#[tokio::test]
+async fn test_admin_api() {
+    let (admin, mut commands) = AdminHandle::channel();
+    let network = tokio::spawn(async move {
+        let mut bans = BanList::in_memory();
+        let mut disconnected = Vec::new();
+        while let Some(command) = commands.recv().await {
+            let is_ban = matches!(command, AdminCommand::Ban { .. });
+            let peer = handle_admin_command(&mut bans, command);
+            assert_eq!(peer.is_some(), is_ban);
+            disconnected.extend(peer);
+        }
+        disconnected
+    });
+
+    admin.ban("peer-a", Duration::from_secs(3600)).await.unwrap();
+    let listed = admin.list_bans().await.unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].0, "peer-a");
+    assert_eq!(listed[0].1.reason, "banned by operator");
+
+    assert!(admin.unban("peer-a").await.unwrap());
+    assert!(!admin.unban("peer-a").await.unwrap());
+    assert!(admin.list_bans().await.unwrap().is_empty());
+
+    drop(admin);
+    assert_eq!(network.await.unwrap(), vec!["peer-a".to_string()]);
+}
+
+// TEST 5 — Operators reach the admin API through the control socket
+#[cfg(unix)]
+#[tokio::test]
+async fn test_admin_socket() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("admin");
+    let path = dir.join("admin").join("admin.sock");
+    let (admin, mut commands) = AdminHandle::channel();
+    tokio::spawn(async move {
+        let mut bans = BanList::in_memory();
+        while let Some(command) = commands.recv().await {
+            handle_admin_command(&mut bans, command);
+        }
+    });
+
+    // A socket left behind by an earlier run is replaced, and the directory
+    // it sits in is made private
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::set_permissions(path.parent().unwrap(), std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::fs::write(&path, b"stale").unwrap();
+    tokio::spawn(serve_admin_socket(bind_admin_socket(&path).unwrap(), admin));
+    let mode = std::fs::metadata(path.parent().unwrap()).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+
+    let ban = AdminRequest::Ban { peer: "peer-a".into(), duration: DEFAULT_BAN_DURATION };
+    assert_eq!(admin_request(&path, &ban).await.unwrap(), AdminResponse::Banned);
+    match admin_request(&path, &AdminRequest::ListBans).await.unwrap() {
+        AdminResponse::Bans(bans) => assert_eq!(bans[0].0, "peer-a"),
+        _ => panic!("wrong response"),
+    }
+    let unban = AdminRequest::Unban { peer: "peer-a".into() };
+    assert_eq!(admin_request(&path, &unban).await.unwrap(), AdminResponse::Unbanned(true));
+    assert_eq!(admin_request(&path, &unban).await.unwrap(), AdminResponse::Unbanned(false));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,150 @@
+use arcnova_chain::blockchain::{
+    address::{Address, ADDRESS_HASH_SIZE, ADDRESS_VERSION},
+    coin_select::{LargestFirst, SmallestFirst},
+    state::State,
+    transaction::{Transaction, TxOutput},
+    tx_builder::{TxBuildError, TxBuilder, DUST_LIMIT},
+    utxo::UtxoSet,
+    wallet::Wallet,
+};
+use arcnova_chain::crypto::hash::Hash;
+use arcnova_chain::storage::Store;
+
+fn funded(wallet: &Wallet, values: &[u64]) -> State {
+    let mut utxo_set = UtxoSet::default();
+    for (i, &value) in values.iter().enumerate() {
+        utxo_set.insert((Hash([i as u8 + 1; 32]), 0), TxOutput { value, address: wallet.address() });
+    }
+    State { utxo_set }
+}
+
+fn pay(value: u64) -> TxOutput {
+    TxOutput { value, address: Wallet::new().address() }
+}
+
+fn fee(state: &State, tx: &Transaction) -> u64 {
+    let input: u64 = tx
+        .inputs
+        .iter()
+        .map(|input| state.utxo_set.get_utxo(&(input.txid.clone(), input.index)).unwrap().unwrap().value)
+        .sum();
+    input - tx.outputs.iter().map(|output| output.value).sum::<u64>()
+}
+
+// TEST 1 — Hundreds of recipients in one transaction, fee from its size
+#[test]
+fn test_batch_pays_all_recipients() {
+    let wallet = Wallet::new();
+    let state = funded(&wallet, &[1_000_000, 500_000]);
+    let payments: Vec<TxOutput> = (0..200).map(|i| pay(1_000 + i)).collect();
+
+    let tx = TxBuilder::new(&state)
+        .build_batch_payment(&wallet, &payments, 2, &LargestFirst)
+        .unwrap();
+
+    assert_eq!(tx.outputs.len(), 201);
+    for (output, payment) in tx.outputs.iter().zip(&payments) {
+        assert_eq!(output.address, payment.address);
+        assert_eq!(output.value, payment.value);
+    }
+    assert_eq!(tx.outputs[200].address, wallet.address());
+    assert_eq!(fee(&state, &tx), tx.size() * 2);
+    assert!(state.validate_transaction(&tx));
+}
+
+// TEST 2 — Change below the dust limit goes to the fee
+#[test]
+fn test_dust_change_is_dropped() {
+    let wallet = Wallet::new();
+    let payment = pay(10_000);
+
+    // Find what the fee with a single output would be, then leave less than
+    // the dust limit on top of it
+    let probe = funded(&wallet, &[1_000_000]);
+    let sized = TxBuilder::new(&probe)
+        .build_batch_payment(&wallet, std::slice::from_ref(&payment), 1, &LargestFirst)
+        .unwrap();
+    // A change output is a version byte, a 20-byte hash and an 8-byte value
+    let one_output_size = sized.size() - (1 + ADDRESS_HASH_SIZE as u64 + 8);
+
+    let state = funded(&wallet, &[10_000 + one_output_size + DUST_LIMIT - 1]);
+    let tx = TxBuilder::new(&state)
+        .build_batch_payment(&wallet, &[payment], 1, &LargestFirst)
+        .unwrap();
+
+    assert_eq!(tx.outputs.len(), 1);
+    assert_eq!(tx.size(), one_output_size);
+    assert_eq!(fee(&state, &tx), one_output_size + DUST_LIMIT - 1);
+    assert!(state.validate_transaction(&tx));
+}
+
+// TEST 3 — Selection is repeated until the inputs also cover their own fee
+#[test]
+fn test_fee_grows_with_inputs() {
+    let wallet = Wallet::new();
+    let state = funded(&wallet, &[1_000; 40]);
+
+    let tx = TxBuilder::new(&state)
+        .build_batch_payment(&wallet, &[pay(20_000)], 1, &SmallestFirst)
+        .unwrap();
+
+    // 20 inputs pay the amount; the fee needs a few more
+    assert!(tx.inputs.len() > 20);
+    assert!(fee(&state, &tx) >= tx.size());
+    assert!(state.validate_transaction(&tx));
+
+    // 40k cannot pay 39.5k plus the fee for 40 inputs
+    match TxBuilder::new(&state).build_batch_payment(&wallet, &[pay(39_500)], 1, &SmallestFirst) {
+        Err(TxBuildError::InsufficientFunds { available, .. }) => assert_eq!(available, 40_000),
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 4 — Recipients are checked before anything is selected
+#[test]
+fn test_batch_rejects_bad_recipients() {
+    let wallet = Wallet::new();
+    let state = funded(&wallet, &[100_000]);
+    let builder = TxBuilder::new(&state);
+
+    // An address version no key can spend yet
+    let future = Address::new(ADDRESS_VERSION + 1, [7u8; ADDRESS_HASH_SIZE]);
+    let bad = TxOutput { value: 1_000, address: future };
+    match builder.build_batch_payment(&wallet, &[pay(1_000), bad], 1, &LargestFirst) {
+        Err(TxBuildError::InvalidAddress(address)) => assert_eq!(address, future),
+        _ => panic!("wrong error variant"),
+    }
+
+    match builder.build_batch_payment(&wallet, &[pay(DUST_LIMIT - 1)], 1, &LargestFirst) {
+        Err(TxBuildError::DustOutput { value, .. }) => assert_eq!(value, DUST_LIMIT - 1),
+        _ => panic!("wrong error variant"),
+    }
+
+    match builder.build_batch_payment(&wallet, &[], 1, &LargestFirst) {
+        Err(TxBuildError::NoRecipients) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 5 — Amounts and fees too large for a u64 are an error, not a panic
+#[test]
+fn test_batch_rejects_overflowing_amounts() {
+    let wallet = Wallet::new();
+    let state = funded(&wallet, &[u64::MAX]);
+    let builder = TxBuilder::new(&state);
+
+    match builder.build_batch_payment(&wallet, &[pay(u64::MAX), pay(u64::MAX)], 1, &LargestFirst) {
+        Err(TxBuildError::Overflow) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    match builder.build_batch_payment(&wallet, &[pay(1_000)], u64::MAX, &LargestFirst) {
+        Err(TxBuildError::Overflow) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    match builder.build_batch_payment(&wallet, &[pay(u64::MAX)], 1, &LargestFirst) {
+        Err(TxBuildError::Overflow) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
@@ -0,0 +1,181 @@
+use arcnova_chain::blockchain::{
+    chain::{ChainManager, Verdict},
+    state::BLOCK_REWARD,
+    tx_builder::TxBuilder,
+    utxo::UtxoSet,
+    wallet::Wallet,
+};
+use arcnova_chain::crypto::hash::Hash;
+use arcnova_chain::p2p::messages::{
+    decode_block, encode_block, encode_transaction, message_id, GossipItem, BLOCK_TOPIC_STR,
+    TX_TOPIC_STR,
+};
+use arcnova_chain::p2p::pipeline::{run_chain_manager, ChainCommand, ChainEvent, Inbound};
+use arcnova_chain::storage::Store;
+use tokio::sync::mpsc;
+
+mod common;
+use common::{addr, block, coinbase};
+
+// Chain with a funded wallet at height 0
+fn funded_manager(wallet: &Wallet) -> ChainManager {
+    let mut manager = ChainManager::new(UtxoSet::default());
+    let genesis = block(0, Hash([0u8; 32]), vec![coinbase(0, wallet.address(), 100_000)]);
+    assert_eq!(manager.process_block(genesis), Verdict::Accept);
+    manager
+}
+
+// TEST 1 — Valid transactions enter the mempool, duplicates are ignored
+#[test]
+fn test_process_transaction_verdicts() {
+    let wallet = Wallet::new();
+    let mut manager = funded_manager(&wallet);
+
+    let tx = TxBuilder::new(&manager.state)
+        .build_payment_tx(&wallet, &addr("bob"), 10_000, 100)
+        .unwrap();
+
+    assert_eq!(manager.process_transaction(tx.clone()), Verdict::Accept);
+    assert_eq!(manager.process_transaction(tx.clone()), Verdict::Ignore);
+    assert_eq!(manager.mempool.len(), 1);
+
+    // Spends the same output as a pooled transaction
+    let conflicting = TxBuilder::new(&manager.state)
+        .build_payment_tx(&wallet, &addr("carol"), 10_000, 100)
+        .unwrap();
+    assert_eq!(manager.process_transaction(conflicting), Verdict::Ignore);
+
+    // Outputs rewritten under the original id
+    let mut tampered = tx;
+    tampered.outputs[0].address = addr("mallory");
+    assert_eq!(manager.process_transaction(tampered), Verdict::Reject);
+
+    let mut bad_sig = TxBuilder::new(&manager.state)
+        .build_payment_tx(&wallet, &addr("carol"), 5_000, 100)
+        .unwrap();
+    bad_sig.inputs[0].signature = vec![1, 2, 3];
+    manager.mempool = Default::default();
+    assert_eq!(manager.process_transaction(bad_sig), Verdict::Reject);
+}
+
+// TEST 2 — Blocks must extend the tip and contain valid transactions
+#[test]
+fn test_process_block_verdicts() {
+    let wallet = Wallet::new();
+    let mut manager = funded_manager(&wallet);
+    let (_, genesis_hash) = manager.state.utxo_set.tip().unwrap().unwrap();
+
+    let tx = TxBuilder::new(&manager.state)
+        .build_payment_tx(&wallet, &addr("bob"), 10_000, 100)
+        .unwrap();
+    manager.process_transaction(tx.clone());
+
+    // Orphan: unknown parent
+    let orphan = block(5, Hash([9u8; 32]), vec![coinbase(2, addr("miner"), 50)]);
+    assert_eq!(manager.process_block(orphan), Verdict::Ignore);
+
+    // Invalid: no coinbase
+    let no_coinbase = block(1, genesis_hash.clone(), vec![tx.clone()]);
+    assert_eq!(manager.process_block(no_coinbase), Verdict::Reject);
+
+    // Invalid: inflated output
+    let mut inflated = tx.clone();
+    inflated.outputs[0].value = 1_000_000;
+    let bad = block(1, genesis_hash.clone(), vec![coinbase(1, addr("miner"), 50), inflated]);
+    assert_eq!(manager.process_block(bad), Verdict::Reject);
+
+    // Invalid: hash does not cover the transactions
+    let mut forged = block(1, genesis_hash.clone(), vec![coinbase(1, addr("miner"), 50), tx.clone()]);
+    forged.transactions.pop();
+    assert_eq!(manager.process_block(forged), Verdict::Reject);
+
+    // Invalid: coinbase claims more than the reward plus the 100 in fees
+    let greedy = vec![coinbase(1, addr("miner"), BLOCK_REWARD + 101), tx.clone()];
+    assert_eq!(manager.process_block(block(1, genesis_hash.clone(), greedy)), Verdict::Reject);
+
+    // Invalid: the same transaction twice
+    let twice = vec![coinbase(1, addr("miner"), 50), tx.clone(), tx.clone()];
+    assert_eq!(manager.process_block(block(1, genesis_hash.clone(), twice)), Verdict::Reject);
+
+    let good = block(1, genesis_hash, vec![coinbase(1, addr("miner"), BLOCK_REWARD + 100), tx.clone()]);
+    assert_eq!(manager.process_block(good.clone()), Verdict::Accept);
+    assert!(manager.mempool.is_empty());
+    assert_eq!(manager.process_block(good.clone()), Verdict::Ignore);
+    assert_eq!(manager.state.utxo_set.available_balance(&addr("bob")), 10_000);
+
+    // Invalid: an already confirmed transaction again
+    let again = block(2, good.hash, vec![coinbase(2, addr("miner"), 50), tx]);
+    assert_eq!(manager.process_block(again), Verdict::Reject);
+}
+
+// TEST 3 — Malformed and trailing bytes are rejected by the decoder
+#[test]
+fn test_decode_block_rejects_garbage() {
+    let b = block(0, Hash([0u8; 32]), vec![coinbase(1, addr("miner"), 50)]);
+    let mut bytes = encode_block(&b);
+    assert_eq!(decode_block(&bytes).unwrap().hash, b.hash);
+
+    bytes.push(0);
+    assert!(decode_block(&bytes).is_err());
+    assert!(decode_block(&[1, 2, 3]).is_err());
+}
+
+// TEST 4 — The validation task reports a verdict for each inbound item
+#[tokio::test]
+async fn test_chain_manager_task_reports_verdicts() {
+    let wallet = Wallet::new();
+    let manager = funded_manager(&wallet);
+    let tx = TxBuilder::new(&manager.state)
+        .build_payment_tx(&wallet, &addr("bob"), 1_000, 10)
+        .unwrap();
+
+    let (command_tx, command_rx) = mpsc::channel::<ChainCommand<&str, ()>>(8);
+    let (event_tx, mut event_rx) = mpsc::channel(8);
+    let task = tokio::spawn(run_chain_manager(manager, command_rx, event_tx));
+
+    command_tx
+        .send(ChainCommand::Gossip(Inbound {
+            source: "peer-a",
+            id: vec![1],
+            item: GossipItem::Transaction(tx),
+        }))
+        .await
+        .unwrap();
+    let validated = match event_rx.recv().await.unwrap() {
+        ChainEvent::Validated(validated) => validated,
+        _ => panic!("wrong event"),
+    };
+    assert_eq!(validated.source, "peer-a");
+    assert_eq!(validated.id, vec![1]);
+    assert_eq!(validated.verdict, Verdict::Accept);
+
+    drop(command_tx);
+    let manager = task.await.unwrap();
+    assert_eq!(manager.mempool.len(), 1);
+}
+
+// TEST 5 — Gossip message IDs hash the payload, not the hash it claims
+#[test]
+fn test_gossip_message_ids() {
+    let b = block(0, Hash([0u8; 32]), vec![coinbase(0, addr("miner"), 50)]);
+    let id = message_id(BLOCK_TOPIC_STR, &encode_block(&b));
+    assert!(id.starts_with(BLOCK_TOPIC_STR.as_bytes()));
+
+    // Same block published twice gets the same ID; another block does not
+    assert_eq!(message_id(BLOCK_TOPIC_STR, &encode_block(&b)), id);
+    let other = block(1, b.hash.clone(), vec![coinbase(1, addr("miner"), 50)]);
+    assert_ne!(message_id(BLOCK_TOPIC_STR, &encode_block(&other)), id);
+
+    // A forgery claiming the block's hash cannot take its ID
+    let mut forged = other.clone();
+    forged.hash = b.hash.clone();
+    assert_ne!(message_id(BLOCK_TOPIC_STR, &encode_block(&forged)), id);
+
+    let tx = coinbase(1, addr("miner"), 50);
+    let tx_id = message_id(TX_TOPIC_STR, &encode_transaction(&tx));
+    assert_ne!(tx_id, message_id(BLOCK_TOPIC_STR, &encode_transaction(&tx)));
+
+    // Undecodable payloads still get a stable ID
+    assert_eq!(message_id(TX_TOPIC_STR, &[1, 2, 3]), message_id(TX_TOPIC_STR, &[1, 2, 3]));
+    assert_ne!(message_id(TX_TOPIC_STR, &[1, 2, 3]), message_id(TX_TOPIC_STR, &[4]));
+}
@@ -0,0 +1,129 @@
+use arcnova_chain::blockchain::{
+    coin_select::{BranchAndBound, CoinSelector, LargestFirst, RandomImprove, SmallestFirst},
+    state::State,
+    transaction::TxOutput,
+    tx_builder::{TxBuildError, TxBuilder},
+    utxo::{OutPoint, UtxoSet},
+    wallet::Wallet,
+};
+use arcnova_chain::crypto::hash::Hash;
+
+mod common;
+use common::addr;
+
+fn candidates(values: &[u64]) -> Vec<(OutPoint, TxOutput)> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, &value)| ((Hash([i as u8 + 1; 32]), 0), TxOutput { value, address: addr("me") }))
+        .collect()
+}
+
+fn values(selection: &[(OutPoint, TxOutput)]) -> Vec<u64> {
+    selection.iter().map(|(_, output)| output.value).collect()
+}
+
+// TEST 1 — Largest- and smallest-first ignore the order candidates come in
+#[test]
+fn test_ordered_selectors() {
+    let mut utxos = candidates(&[5_000, 40_000, 1_000, 20_000]);
+
+    let largest = LargestFirst.select(&utxos, 45_000).unwrap();
+    assert_eq!(values(&largest.inputs), vec![40_000, 20_000]);
+    assert_eq!(largest.change(45_000), 15_000);
+
+    let smallest = SmallestFirst.select(&utxos, 6_500).unwrap();
+    assert_eq!(values(&smallest.inputs), vec![1_000, 5_000, 20_000]);
+
+    utxos.reverse();
+    assert_eq!(values(&LargestFirst.select(&utxos, 45_000).unwrap().inputs), vec![40_000, 20_000]);
+    assert!(SmallestFirst.select(&utxos, 66_001).is_none());
+}
+
+// TEST 2 — Branch-and-bound finds a changeless combination or falls back
+#[test]
+fn test_branch_and_bound() {
+    let utxos = candidates(&[50_000, 30_000, 20_000, 7_000, 3_000]);
+
+    // 30k + 3k is exact; largest-first would have spent the 50k
+    let exact = BranchAndBound::default().select(&utxos, 33_000).unwrap();
+    assert_eq!(values(&exact.inputs), vec![30_000, 3_000]);
+    assert_eq!(exact.change(33_000), 0);
+
+    // Within the cost of change, the smallest excess wins
+    let close = BranchAndBound::new(1_000).select_changeless(&utxos, 26_500).unwrap();
+    assert_eq!(close.total, 27_000);
+
+    // Nothing fits: no changeless answer, but `select` still pays
+    assert!(BranchAndBound::default().select_changeless(&utxos, 1_500).is_none());
+    let fallback = BranchAndBound::default().select(&utxos, 1_500).unwrap();
+    assert_eq!(values(&fallback.inputs), vec![50_000]);
+}
+
+// TEST 3 — Random-improve is reproducible per seed and aims at twice the target
+#[test]
+fn test_random_improve() {
+    let utxos = candidates(&[1_000; 40]);
+
+    let first = RandomImprove::new(7).select(&utxos, 5_000).unwrap();
+    let again = RandomImprove::new(7).select(&utxos, 5_000).unwrap();
+    let outpoints = |selection: &[(OutPoint, TxOutput)]| -> Vec<OutPoint> {
+        selection.iter().map(|(outpoint, _)| outpoint.clone()).collect()
+    };
+    assert_eq!(outpoints(&first.inputs), outpoints(&again.inputs));
+    assert_eq!(first.total, 10_000);
+
+    let other = RandomImprove::new(8).select(&utxos, 5_000).unwrap();
+    assert_ne!(outpoints(&first.inputs), outpoints(&other.inputs));
+
+    assert!(RandomImprove::new(7).select(&utxos, 40_001).is_none());
+}
+
+// TEST 4 — The builder uses the selector it is given
+#[test]
+fn test_builder_with_selector() {
+    let wallet = Wallet::new();
+    let mut utxo_set = UtxoSet::default();
+    for (i, value) in [50_000u64, 30_000, 3_000].into_iter().enumerate() {
+        utxo_set.insert((Hash([i as u8 + 1; 32]), 0), TxOutput { value, address: wallet.address() });
+    }
+    let state = State { utxo_set };
+    let builder = TxBuilder::new(&state);
+
+    // Changeless: one output, the whole input paying amount + fee
+    let tx = builder
+        .build_payment_tx_with(&wallet, &addr("bob"), 32_000, 1_000, &BranchAndBound::default())
+        .unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    assert_eq!(tx.outputs.len(), 1);
+    assert!(state.validate_transaction(&tx));
+
+    // The default spends the largest output
+    let tx = builder.build_payment_tx(&wallet, &addr("bob"), 32_000, 1_000).unwrap();
+    assert_eq!(tx.inputs.len(), 1);
+    assert_eq!(tx.outputs[1].value, 17_000);
+
+    match builder.build_payment_tx_with(&wallet, &addr("bob"), 90_000, 0, &SmallestFirst) {
+        Err(TxBuildError::InsufficientFunds { required, available }) => {
+            assert_eq!(required, 90_000);
+            assert_eq!(available, 83_000);
+        }
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 5 — Outputs near u64::MAX do not overflow the selectors
+#[test]
+fn test_selectors_with_huge_values() {
+    let utxos = candidates(&[u64::MAX - 1, u64::MAX - 1, 10]);
+
+    let largest = LargestFirst.select(&utxos, u64::MAX).unwrap();
+    assert_eq!(largest.inputs.len(), 2);
+    assert_eq!(largest.total, u64::MAX);
+    assert_eq!(SmallestFirst.select(&utxos, u64::MAX).unwrap().inputs.len(), 2);
+    assert_eq!(RandomImprove::new(7).select(&utxos, u64::MAX).unwrap().total, u64::MAX);
+
+    let exact = BranchAndBound::default().select(&utxos, u64::MAX - 1).unwrap();
+    assert_eq!(values(&exact.inputs), vec![u64::MAX - 1]);
+    assert!(BranchAndBound::new(20).select(&utxos, u64::MAX).is_some());
+}
@@ -0,0 +1,48 @@
+// Fixtures shared by the integration tests; each test crate uses a subset
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use arcnova_chain::blockchain::{
+    address::{Address, ADDRESS_HASH_SIZE, ADDRESS_VERSION},
+    block::Block,
+    transaction::{Transaction, TxOutput},
+};
+use arcnova_chain::crypto::hash::Hash;
+
+// Address of a named party with no key behind it, for outputs nobody spends
+pub fn addr(name: &str) -> Address {
+    let mut hash = [0u8; ADDRESS_HASH_SIZE];
+    hash.copy_from_slice(&Hash::hash(&name).0[..ADDRESS_HASH_SIZE]);
+    Address::new(ADDRESS_VERSION, hash)
+}
+
+// Coinbase for the block at `height`
+pub fn coinbase(height: u64, address: Address, value: u64) -> Transaction {
+    Transaction::coinbase(height, vec![TxOutput { value, address }])
+}
+
+pub fn block(index: u64, prev_hash: Hash, transactions: Vec<Transaction>) -> Block {
+    let mut block = Block {
+        index,
+        prev_hash,
+        timestamp: index as u128,
+        transactions,
+        nonce: 0,
+        hash: Hash([0u8; 32]),
+    };
+    block.hash = block.calculate_hash();
+    block
+}
+
+// Fresh directory under the system temp dir
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("arcnova-{}-{}", name, rand::random::<u64>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Command line split the way a shell would
+pub fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(String::from).collect()
+}
@@ -0,0 +1,219 @@
+use arcnova_chain::blockchain::{
+    address::Address,
+    block::Block,
+    chain::{ChainManager, Verdict},
+    mempool::Mempool,
+    transaction::{Transaction, TxInput, TxOutput},
+    tx_builder::TxBuilder,
+    utxo::UtxoSet,
+    wallet::Wallet,
+};
+use arcnova_chain::crypto::hash::Hash;
+use arcnova_chain::p2p::compact::{CompactBlock, CompactError};
+use arcnova_chain::p2p::messages::GossipItem;
+use arcnova_chain::p2p::pipeline::{
+    run_chain_manager, ChainCommand, ChainEvent, Inbound, MAX_PENDING_COMPACT_BLOCKS,
+};
+use arcnova_chain::p2p::sync::{serve_request, SyncRequest, SyncResponse};
+use arcnova_chain::storage::Store;
+use tokio::sync::mpsc;
+
+mod common;
+use common::{addr, block};
+
+fn coinbase(height: u64, outputs: Vec<(Address, u64)>) -> Transaction {
+    let outputs = outputs.into_iter().map(|(address, value)| TxOutput { value, address }).collect();
+    Transaction::coinbase(height, outputs)
+}
+
+// A non-coinbase transaction; contents only matter for its ID here
+fn spend(tag: u8) -> Transaction {
+    Transaction {
+        id: Hash([tag; 32]),
+        inputs: vec![TxInput { txid: Hash([0u8; 32]), index: tag as u32, signature: vec![], public_key: vec![] }],
+        outputs: vec![TxOutput { value: 1, address: addr("bob") }],
+    }
+}
+
+fn tx_ids(block: &Block) -> Vec<Hash> {
+    block.transactions.iter().map(|tx| tx.id.clone()).collect()
+}
+
+// TEST 1 — A block whose transactions are all pooled is rebuilt without a round-trip
+#[test]
+fn test_reconstruct_from_mempool() {
+    let b = block(1, Hash([0u8; 32]), vec![coinbase(1, vec![(addr("miner"), 50)]), spend(2), spend(3)]);
+    let compact = CompactBlock::from_block(&b, 7);
+    assert_eq!(compact.prefilled.len(), 1);
+    assert_eq!(compact.short_ids.len(), 2);
+
+    let mut mempool = Mempool::default();
+    mempool.insert(spend(3));
+    mempool.insert(spend(2));
+    mempool.insert(spend(9));
+
+    let partial = compact.reconstruct(&mempool).unwrap();
+    assert!(partial.is_complete());
+    let rebuilt = partial.into_block().unwrap();
+    assert_eq!(rebuilt.hash, b.hash);
+    assert_eq!(tx_ids(&rebuilt), tx_ids(&b));
+}
+
+// TEST 2 — Missing transactions are reported by position and filled in
+#[test]
+fn test_fill_missing_transactions() {
+    let b = block(
+        1,
+        Hash([0u8; 32]),
+        vec![coinbase(1, vec![(addr("miner"), 50)]), spend(2), spend(3), spend(4)],
+    );
+    let compact = CompactBlock::from_block(&b, 42);
+
+    let mut mempool = Mempool::default();
+    mempool.insert(spend(3));
+    let mut partial = compact.reconstruct(&mempool).unwrap();
+    assert_eq!(partial.missing(), vec![1, 3]);
+
+    assert_eq!(
+        partial.clone().fill(vec![spend(2)]),
+        Err(CompactError::WrongTransactionCount { expected: 2, actual: 1 })
+    );
+    match partial.clone().fill(vec![spend(4), spend(2)]) {
+        Err(CompactError::ShortIdMismatch(_)) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    partial.fill(vec![spend(2), spend(4)]).unwrap();
+    assert_eq!(tx_ids(&partial.into_block().unwrap()), tx_ids(&b));
+}
+
+// TEST 3 — Malformed compact blocks are errors
+#[test]
+fn test_malformed_compact_block() {
+    let b = block(1, Hash([0u8; 32]), vec![coinbase(1, vec![(addr("miner"), 50)]), spend(2), spend(3)]);
+
+    let mut compact = CompactBlock::from_block(&b, 1);
+    compact.short_ids[1] = compact.short_ids[0];
+    match compact.reconstruct(&Mempool::default()) {
+        Err(CompactError::DuplicateShortIds) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    let mut compact = CompactBlock::from_block(&b, 1);
+    compact.prefilled[0].0 = 5;
+    match compact.reconstruct(&Mempool::default()) {
+        Err(CompactError::BadPrefilled(5)) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 4 — The chain manager asks for missing transactions, then connects the block
+#[tokio::test]
+async fn test_compact_block_round_trip() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let mut manager = ChainManager::new(UtxoSet::default());
+    let genesis = block(
+        0,
+        Hash([0u8; 32]),
+        vec![coinbase(0, vec![(alice.address(), 10_000), (bob.address(), 10_000)])],
+    );
+    assert_eq!(manager.process_block(genesis.clone()), Verdict::Accept);
+
+    let builder = TxBuilder::new(&manager.state);
+    let pooled = builder.build_payment_tx(&alice, &addr("carol"), 1_000, 10).unwrap();
+    let unseen = builder.build_payment_tx(&bob, &addr("carol"), 2_000, 10).unwrap();
+    assert_eq!(manager.process_transaction(pooled.clone()), Verdict::Accept);
+
+    let next = block(1, genesis.hash.clone(), vec![coinbase(1, vec![(addr("miner"), 50)]), pooled, unseen.clone()]);
+
+    // The peer that announced the block can serve the missing transactions
+    let mut sender = UtxoSet::default();
+    sender.connect_block(&genesis).unwrap();
+    sender.connect_block(&next).unwrap();
+
+    let (command_tx, command_rx) = mpsc::channel::<ChainCommand<&str, ()>>(8);
+    let (event_tx, mut event_rx) = mpsc::channel(8);
+    let task = tokio::spawn(run_chain_manager(manager, command_rx, event_tx));
+
+    let compact = CompactBlock::from_block(&next, 99);
+    command_tx
+        .send(ChainCommand::Gossip(Inbound { source: "peer-a", id: vec![1], item: GossipItem::CompactBlock(compact) }))
+        .await
+        .unwrap();
+    let (block_hash, indexes) = match event_rx.recv().await.unwrap() {
+        ChainEvent::MissingTransactions { source, block_hash, indexes } => {
+            assert_eq!(source, "peer-a");
+            (block_hash, indexes)
+        }
+        _ => panic!("wrong event"),
+    };
+    assert_eq!(indexes, vec![2]);
+
+    let transactions = match serve_request(&sender, &SyncRequest::GetBlockTxn { block_hash: block_hash.clone(), indexes }) {
+        SyncResponse::BlockTxn { transactions, .. } => transactions,
+        _ => panic!("wrong response"),
+    };
+    assert_eq!(transactions[0].id, unseen.id);
+
+    command_tx
+        .send(ChainCommand::BlockTxn { source: "peer-a", block_hash, transactions })
+        .await
+        .unwrap();
+    match event_rx.recv().await.unwrap() {
+        ChainEvent::Validated(validated) => {
+            assert_eq!(validated.id, vec![1]);
+            assert_eq!(validated.verdict, Verdict::Accept);
+        }
+        _ => panic!("wrong event"),
+    }
+
+    drop(command_tx);
+    let manager = task.await.unwrap();
+    assert!(manager.mempool.is_empty());
+}
+
+// TEST 5 — With too many blocks waiting, the oldest one is dropped
+#[tokio::test]
+async fn test_oldest_pending_block_is_evicted() {
+    let alice = Wallet::new();
+    let mut manager = ChainManager::new(UtxoSet::default());
+    let genesis = block(0, Hash([0u8; 32]), vec![coinbase(0, vec![(alice.address(), 10_000)])]);
+    assert_eq!(manager.process_block(genesis.clone()), Verdict::Accept);
+    let unseen = TxBuilder::new(&manager.state).build_payment_tx(&alice, &addr("carol"), 1_000, 10).unwrap();
+
+    let (command_tx, command_rx) = mpsc::channel::<ChainCommand<&str, ()>>(8);
+    let (event_tx, mut event_rx) = mpsc::channel(8);
+    let task = tokio::spawn(run_chain_manager(manager, command_rx, event_tx));
+
+    // Competing blocks that differ only in their coinbase, one more than fits
+    let mut hashes = Vec::new();
+    for i in 0..=MAX_PENDING_COMPACT_BLOCKS as u64 {
+        let next = block(1, genesis.hash.clone(), vec![coinbase(1, vec![(addr("miner"), 50 + i)]), unseen.clone()]);
+        let item = GossipItem::CompactBlock(CompactBlock::from_block(&next, i));
+        command_tx.send(ChainCommand::Gossip(Inbound { source: "peer-a", id: vec![i as u8], item })).await.unwrap();
+        match event_rx.recv().await.unwrap() {
+            ChainEvent::MissingTransactions { block_hash, .. } => hashes.push(block_hash),
+            _ => panic!("wrong event"),
+        }
+    }
+
+    // The first block is forgotten; the second is still waiting
+    for block_hash in hashes.into_iter().take(2) {
+        command_tx
+            .send(ChainCommand::BlockTxn { source: "peer-a", block_hash, transactions: vec![unseen.clone()] })
+            .await
+            .unwrap();
+    }
+    match event_rx.recv().await.unwrap() {
+        ChainEvent::Validated(validated) => {
+            assert_eq!(validated.id, vec![1]);
+            assert_eq!(validated.verdict, Verdict::Accept);
+        }
+        _ => panic!("wrong event"),
+    }
+
+    drop(command_tx);
+    task.await.unwrap();
+    assert!(event_rx.recv().await.is_none());
+}
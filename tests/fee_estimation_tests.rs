@@ -0,0 +1,141 @@
+use arcnova_chain::blockchain::{
+    address::{Address, ADDRESS_HASH_SIZE, ADDRESS_VERSION},
+    chain::{ChainManager, Verdict},
+    coin_select::LargestFirst,
+    fees::{block_fee_rates, FeeEstimator, MIN_FEE_RATE},
+    transaction::{Transaction, TxInput, TxOutput},
+    tx_builder::{TxBuildError, TxBuilder},
+    utxo::{BlockUndo, UtxoSet},
+    wallet::Wallet,
+};
+use arcnova_chain::crypto::hash::Hash;
+
+mod common;
+use common::{addr, block, coinbase};
+
+fn spend(tag: u8, txid: Hash, value: u64) -> Transaction {
+    Transaction {
+        id: Hash([tag; 32]),
+        inputs: vec![TxInput { txid, index: 0, signature: vec![0u8; 64], public_key: vec![0u8; 32] }],
+        outputs: vec![TxOutput { value, address: addr("someone") }],
+    }
+}
+
+// TEST 1 — Fee rates come from undo data and outputs created in the block
+#[test]
+fn test_block_fee_rates() {
+    let first = spend(2, Hash([9u8; 32]), 9_000);
+    let second = spend(3, first.id.clone(), 7_000);
+    let undo = BlockUndo {
+        spent: vec![((Hash([9u8; 32]), 0), TxOutput { value: 10_000, address: addr("someone") })],
+    };
+    let b = block(1, Hash([0u8; 32]), vec![coinbase(1, addr("miner"), 50), first.clone(), second.clone()]);
+
+    let rates = block_fee_rates(&b, &undo);
+    assert_eq!(rates, vec![1_000 / first.size(), 2_000 / second.size()]);
+}
+
+// TEST 2 — Longer confirmation targets get cheaper estimates
+#[test]
+fn test_estimates_by_target() {
+    let mut fees = FeeEstimator::new(10);
+    assert_eq!(fees.estimate(1), None);
+
+    for (height, rate) in [20, 4, 9, 3, 12, 5, 30, 2, 8, 6].into_iter().enumerate() {
+        fees.add_rate(height as u64, rate);
+    }
+    assert_eq!(fees.len(), 10);
+
+    // The 85th percentile of per-block minimums, then of 3-block windows
+    assert_eq!(fees.estimate(1), Some(20));
+    assert_eq!(fees.estimate(3), Some(4));
+    assert_eq!(fees.estimate(10), Some(2));
+    assert_eq!(fees.estimate(11), None);
+
+    // Old blocks fall out of the window; a replaced height drops its successors
+    fees.add_rate(10, 1);
+    assert_eq!(fees.len(), 10);
+    fees.add_rate(9, 40);
+    assert_eq!(fees.len(), 9);
+    fees.remove_block(9);
+    assert_eq!(fees.len(), 8);
+}
+
+// TEST 3 — A payment at a fee rate pays that rate for its signed size
+#[test]
+fn test_payment_at_rate() {
+    let wallet = Wallet::new();
+    let mut utxo_set = UtxoSet::default();
+    utxo_set.insert((Hash([1u8; 32]), 0), TxOutput { value: 100_000, address: wallet.address() });
+    let manager = ChainManager::new(utxo_set);
+    let builder = TxBuilder::new(&manager.state);
+
+    let to = Wallet::new().address();
+    let tx = builder.build_payment_tx_at_rate(&wallet, &to, 10_000, 7, &LargestFirst).unwrap();
+    assert_eq!(tx.outputs[0].value, 10_000);
+    assert_eq!(100_000 - 10_000 - tx.outputs[1].value, tx.size() * 7);
+    assert!(manager.state.validate_transaction(&tx));
+
+    let future = Address::new(ADDRESS_VERSION + 1, [1u8; ADDRESS_HASH_SIZE]);
+    match builder.build_payment_tx_at_rate(&wallet, &future, 10_000, 7, &LargestFirst) {
+        Err(TxBuildError::InvalidAddress(_)) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 4 — The chain manager learns rates from the blocks it connects
+#[test]
+fn test_manager_learns_from_blocks() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let mut manager = ChainManager::new(UtxoSet::default());
+    let funding = Transaction::coinbase(
+        0,
+        vec![
+            TxOutput { value: 100_000, address: alice.address() },
+            TxOutput { value: 100_000, address: bob.address() },
+        ],
+    );
+    let genesis = block(0, Hash([0u8; 32]), vec![funding]);
+    assert_eq!(manager.process_block(genesis.clone()), Verdict::Accept);
+    assert_eq!(manager.fees.estimate(1), Some(MIN_FEE_RATE));
+
+    let to = Wallet::new().address();
+    let builder = TxBuilder::new(&manager.state);
+    let expensive = builder.build_payment_tx_at_rate(&alice, &to, 5_000, 12, &LargestFirst).unwrap();
+    let cheap = builder.build_payment_tx_at_rate(&bob, &to, 5_000, 4, &LargestFirst).unwrap();
+
+    let b1 = block(1, genesis.hash.clone(), vec![coinbase(1, addr("miner"), 50), expensive, cheap]);
+    assert_eq!(manager.process_block(b1), Verdict::Accept);
+
+    // The cheapest transaction in a block is what it took to get in
+    assert_eq!(manager.fees.len(), 2);
+    assert_eq!(manager.fees.estimate(1), Some(4));
+    assert_eq!(manager.fees.estimate(2), Some(MIN_FEE_RATE));
+}
+
+// TEST 5 — Amounts and fees too large for a u64 are an error, not a panic
+#[test]
+fn test_overflowing_fee_is_an_error() {
+    let wallet = Wallet::new();
+    let mut utxo_set = UtxoSet::default();
+    utxo_set.insert((Hash([1u8; 32]), 0), TxOutput { value: u64::MAX, address: wallet.address() });
+    let manager = ChainManager::new(utxo_set);
+    let builder = TxBuilder::new(&manager.state);
+    let to = Wallet::new().address();
+
+    match builder.build_payment_tx_with(&wallet, &to, u64::MAX, 1, &LargestFirst) {
+        Err(TxBuildError::Overflow) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    match builder.build_psbt(&wallet.public_key_bytes(), &to, 1_000, u64::MAX, &LargestFirst) {
+        Err(TxBuildError::Overflow) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    match builder.build_payment_tx_at_rate(&wallet, &to, 10_000, u64::MAX / 2, &LargestFirst) {
+        Err(TxBuildError::Overflow) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
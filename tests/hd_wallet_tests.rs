@@ -0,0 +1,120 @@
+use arcnova_chain::blockchain::hd::{
+    generate_mnemonic, parse_mnemonic, ExtendedKey, HdError, HdWallet, DEFAULT_GAP_LIMIT,
+};
+use arcnova_chain::blockchain::{
+    address::Address,
+    block::Block,
+    utxo::UtxoSet,
+};
+use arcnova_chain::crypto::hash::Hash;
+use arcnova_chain::storage::Store;
+
+mod common;
+use common::{addr, coinbase};
+
+const ABANDON: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+// TEST 1 — BIP39 mnemonics round-trip and produce the reference seed
+#[test]
+fn test_mnemonic_generation_and_recovery() {
+    let mnemonic = parse_mnemonic(ABANDON).unwrap();
+    assert_eq!(
+        hex::encode(mnemonic.to_seed("TREZOR")),
+        "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+    );
+
+    let generated = generate_mnemonic(24).unwrap();
+    assert_eq!(generated.word_count(), 24);
+    let restored = parse_mnemonic(&generated.to_string()).unwrap();
+    assert_eq!(restored, generated);
+    assert_ne!(generate_mnemonic(12).unwrap(), generate_mnemonic(12).unwrap());
+
+    // Bad checksum (last word changed) and unsupported lengths
+    assert!(parse_mnemonic(&ABANDON.replace("about", "abandon")).is_err());
+    match generate_mnemonic(13) {
+        Err(HdError::WordCount(13)) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 2 — SLIP-0010 ed25519 test vector 1
+#[test]
+fn test_slip10_ed25519_vectors() {
+    let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+    let master = ExtendedKey::master(&seed);
+    assert_eq!(
+        hex::encode(master.secret()),
+        "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+    );
+    assert_eq!(
+        hex::encode(master.chain_code()),
+        "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb"
+    );
+
+    let child = master.derive_path("m/0'").unwrap();
+    assert_eq!(
+        hex::encode(child.secret()),
+        "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3"
+    );
+    assert_eq!(child, master.child(0));
+
+    // Non-hardened steps do not exist for ed25519
+    for path in ["m/0", "0'/1'", "m/x'"] {
+        match master.derive_path(path) {
+            Err(HdError::InvalidPath(_)) => {}
+            _ => panic!("wrong error variant"),
+        }
+    }
+}
+
+// TEST 3 — The same mnemonic always derives the same addresses
+#[test]
+fn test_hd_wallet_derivation_is_deterministic() {
+    let mnemonic = parse_mnemonic(ABANDON).unwrap();
+    let wallet = HdWallet::from_mnemonic(&mnemonic, "", 0);
+    let again = HdWallet::from_mnemonic(&mnemonic, "", 0);
+
+    let addresses: Vec<Address> = (0..5).map(|i| wallet.address(i)).collect();
+    assert_eq!(addresses, (0..5).map(|i| again.address(i)).collect::<Vec<_>>());
+    assert_eq!(wallet.derive(3).address(), addresses[3]);
+
+    // All distinct, and different per passphrase and per account
+    let mut unique = addresses.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), 5);
+    assert_ne!(HdWallet::from_mnemonic(&mnemonic, "extra", 0).address(0), addresses[0]);
+    assert_ne!(HdWallet::from_mnemonic(&mnemonic, "", 1).address(0), addresses[0]);
+}
+
+// TEST 4 — Rescanning finds funds across addresses up to the gap limit
+#[test]
+fn test_hd_wallet_rescan_with_gap_limit() {
+    let wallet = HdWallet::from_mnemonic(&parse_mnemonic(ABANDON).unwrap(), "", 0);
+    let mut utxos = UtxoSet::default();
+
+    let block = Block {
+        index: 0,
+        prev_hash: Hash([0u8; 32]),
+        timestamp: 0,
+        transactions: vec![
+            coinbase(1, wallet.address(0), 100),
+            coinbase(2, wallet.address(7), 200),
+            coinbase(3, wallet.address(30), 400),
+            coinbase(4, addr("someone-else"), 800),
+        ],
+        nonce: 0,
+        hash: Hash([9u8; 32]),
+    };
+    utxos.connect_block(&block).unwrap();
+
+    let scan = wallet.rescan(&utxos, DEFAULT_GAP_LIMIT).unwrap();
+    assert_eq!(scan.used, vec![0, 7]);
+    assert_eq!(scan.next_index, 8);
+    assert_eq!(scan.balance(), 300);
+
+    // Index 30 is 22 addresses past index 7; a wider gap finds it
+    let scan = wallet.rescan(&utxos, 25).unwrap();
+    assert_eq!(scan.used, vec![0, 7, 30]);
+    assert_eq!(scan.balance(), 700);
+}
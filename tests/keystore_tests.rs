@@ -0,0 +1,114 @@
+use arcnova_chain::blockchain::keystore::{KdfParams, Keystore, KeystoreError};
+use arcnova_chain::blockchain::wallet::Wallet;
+
+mod common;
+use common::temp_dir;
+
+// Cheap settings so the tests run quickly
+const FAST: KdfParams = KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 };
+
+// TEST 1 — A saved keystore unlocks to the same wallet
+#[test]
+fn test_keystore_round_trip() {
+    let path = temp_dir("keystore").join("wallet.json");
+    let wallet = Wallet::new();
+
+    Keystore::create_with(&wallet, "correct horse", FAST).unwrap().save(&path).unwrap();
+
+    let keystore = Keystore::load(&path).unwrap();
+    assert_eq!(keystore.address, wallet.address());
+    assert_eq!(keystore.kdf_params(), FAST);
+    let unlocked = keystore.unlock("correct horse").unwrap();
+    assert_eq!(unlocked.secret_bytes(), wallet.secret_bytes());
+    assert_eq!(unlocked.address(), wallet.address());
+
+    // The secret is not in the file
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(!text.contains(&hex::encode(wallet.secret_bytes())));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+// TEST 2 — Wrong passphrases and edited headers fail to unlock
+#[test]
+fn test_keystore_rejects_wrong_passphrase_and_tampering() {
+    let wallet = Wallet::new();
+    let keystore = Keystore::create_with(&wallet, "secret", FAST).unwrap();
+
+    match keystore.unlock("guess") {
+        Err(KeystoreError::Decryption) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    // The address is authenticated along with the secret
+    let mut json: serde_json::Value = serde_json::to_value(&keystore).unwrap();
+    json["address"] = serde_json::to_value(Wallet::new().address()).unwrap();
+    let edited: Keystore = serde_json::from_value(json).unwrap();
+    match edited.unlock("secret") {
+        Err(KeystoreError::Decryption) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 3 — Changing the passphrase re-encrypts the same key
+#[test]
+fn test_keystore_change_passphrase() {
+    let wallet = Wallet::new();
+    let mut keystore = Keystore::create_with(&wallet, "old", FAST).unwrap();
+    let before = keystore.clone();
+
+    match keystore.change_passphrase("wrong", "new") {
+        Err(KeystoreError::Decryption) => {}
+        _ => panic!("wrong error variant"),
+    }
+    assert_eq!(keystore, before);
+
+    keystore.change_passphrase("old", "new").unwrap();
+    assert_ne!(keystore, before);
+    assert!(keystore.unlock("old").is_err());
+    assert_eq!(keystore.unlock("new").unwrap().address(), wallet.address());
+}
+
+// TEST 4 — Unknown versions and excessive KDF costs are refused on load
+#[test]
+fn test_keystore_header_checks() {
+    let dir = temp_dir("keystore-header");
+    let keystore = Keystore::create_with(&Wallet::new(), "pw", FAST).unwrap();
+    let json = serde_json::to_value(&keystore).unwrap();
+
+    let mut future = json.clone();
+    future["version"] = 3.into();
+    std::fs::write(dir.join("future.json"), future.to_string()).unwrap();
+    match Keystore::load(dir.join("future.json")) {
+        Err(KeystoreError::UnsupportedVersion(3)) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    // Version 1 files stored the address as bare hex
+    let mut legacy = json.clone();
+    legacy["version"] = 1.into();
+    legacy["address"] = hex::encode([7u8; 20]).into();
+    std::fs::write(dir.join("legacy.json"), legacy.to_string()).unwrap();
+    match Keystore::load(dir.join("legacy.json")) {
+        Err(KeystoreError::UnsupportedVersion(1)) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    let mut costly = json;
+    costly["kdf"]["memory_kib"] = u32::MAX.into();
+    std::fs::write(dir.join("costly.json"), costly.to_string()).unwrap();
+    match Keystore::load(dir.join("costly.json")) {
+        Err(KeystoreError::UnsupportedKdf(_)) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    std::fs::write(dir.join("garbage.json"), "not json").unwrap();
+    match Keystore::load(dir.join("garbage.json")) {
+        Err(KeystoreError::Malformed(_)) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
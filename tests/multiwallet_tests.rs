@@ -0,0 +1,159 @@
+use arcnova_chain::blockchain::{
+    address::Address,
+    block::Block,
+    multiwallet::{Balance, MultiWallet},
+    state::State,
+    transaction::{Transaction, TxInput, TxOutput},
+    tx_builder::{TxBuildError, TxBuilder},
+    utxo::UtxoSet,
+};
+use arcnova_chain::crypto::hash::Hash;
+use arcnova_chain::storage::Store;
+
+mod common;
+use common::{addr, block, coinbase};
+
+// A wallet with two keys funded in one block, and the chain state to match
+fn funded() -> (MultiWallet, Vec<Address>, State, Block) {
+    let mut wallet = MultiWallet::new();
+    let addresses = vec![wallet.generate_key(), wallet.generate_key()];
+
+    let genesis = block(
+        0,
+        Hash([0u8; 32]),
+        vec![
+            coinbase(1, addresses[0], 30_000),
+            coinbase(2, addresses[1], 50_000),
+            coinbase(3, addr("someone-else"), 99_000),
+        ],
+    );
+    let mut utxos = UtxoSet::default();
+    utxos.connect_block(&genesis).unwrap();
+    wallet.connect_block(&genesis);
+
+    (wallet, addresses, State { utxo_set: utxos }, genesis)
+}
+
+// TEST 1 — Connected blocks update balance and history across keys
+#[test]
+fn test_wallet_tracks_blocks_across_keys() {
+    let (wallet, addresses, _, _) = funded();
+
+    assert_eq!(wallet.balance(), Balance { confirmed: 80_000, pending: 0 });
+    assert_eq!(wallet.spendable().len(), 2);
+    assert_eq!(wallet.history().len(), 2);
+    assert!(wallet.history().iter().all(|entry| entry.height == Some(0)));
+    assert!(wallet.is_mine(&addresses[1]));
+    assert!(!wallet.is_mine(&addr("someone-else")));
+}
+
+// TEST 2 — Our unconfirmed spends are pending and never selected twice
+#[test]
+fn test_pending_spends_are_not_reselected() {
+    let (mut wallet, addresses, state, _) = funded();
+    let builder = TxBuilder::new(&state);
+
+    // Needs both keys' outputs
+    let tx = builder
+        .build_wallet_payment(&wallet, &addr("bob"), 70_000, 1_000, &addresses[0])
+        .unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    assert!(state.validate_transaction(&tx));
+
+    assert!(wallet.add_pending(tx.clone()));
+    assert_eq!(wallet.balance(), Balance { confirmed: 0, pending: 9_000 });
+    assert!(wallet.is_pending(&tx.id));
+    assert_eq!(wallet.history().last().unwrap().sent, 80_000);
+
+    // Nothing confirmed is left to spend
+    match builder.build_wallet_payment(&wallet, &addr("carol"), 1_000, 100, &addresses[0]) {
+        Err(TxBuildError::NoUtxos) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    // Abandoning the transaction frees its inputs again
+    wallet.abandon(&tx.id);
+    assert_eq!(wallet.balance(), Balance { confirmed: 80_000, pending: 0 });
+    assert_eq!(wallet.history().len(), 2);
+}
+
+// TEST 3 — Confirmation and disconnection move transactions in and out of blocks
+#[test]
+fn test_confirm_and_disconnect() {
+    let (mut wallet, addresses, state, _) = funded();
+    let tx = TxBuilder::new(&state)
+        .build_wallet_payment(&wallet, &addr("bob"), 10_000, 500, &addresses[1])
+        .unwrap();
+    wallet.add_pending(tx.clone());
+
+    let b1 = block(1, Hash([0u8; 32]), vec![coinbase(4, addresses[0], 5_000), tx.clone()]);
+    wallet.connect_block(&b1);
+    assert!(!wallet.is_pending(&tx.id));
+    assert_eq!(wallet.balance(), Balance { confirmed: 74_500, pending: 0 });
+    let entry = wallet.history().iter().find(|entry| entry.txid == tx.id).unwrap();
+    assert_eq!(entry.height, Some(1));
+
+    // Back out of the chain: the coinbase is gone, the payment pending again
+    wallet.disconnect_block(&b1);
+    assert!(wallet.is_pending(&tx.id));
+    assert_eq!(wallet.balance().confirmed, 80_000 - 50_000);
+    assert_eq!(wallet.balance().pending, 39_500);
+    assert!(wallet.history().iter().all(|entry| entry.txid != Hash([4u8; 32])));
+}
+
+// TEST 4 — A block spending our output elsewhere abandons our pending spend
+#[test]
+fn test_conflicting_block_abandons_pending() {
+    let (mut wallet, addresses, state, genesis) = funded();
+    let builder = TxBuilder::new(&state);
+    let ours = builder
+        .build_wallet_payment(&wallet, &addr("bob"), 20_000, 0, &addresses[0])
+        .unwrap();
+    wallet.add_pending(ours.clone());
+
+    // Same input, different outputs (e.g. the same key used elsewhere)
+    let mut conflict = ours.clone();
+    conflict.outputs = vec![TxOutput { value: 50_000, address: addr("mallory") }];
+    conflict.id = Hash([77u8; 32]);
+    wallet.connect_block(&block(1, genesis.hash.clone(), vec![coinbase(5, addr("miner"), 1), conflict]));
+
+    assert!(!wallet.is_pending(&ours.id));
+    assert_eq!(wallet.balance(), Balance { confirmed: 30_000, pending: 0 });
+    assert_eq!(wallet.spendable()[0].1.address, genesis.transactions[0].outputs[0].address);
+}
+
+// TEST 5 — Chained pending transactions: spent change is not counted, and
+//          abandoning the parent abandons the child
+#[test]
+fn test_abandon_removes_pending_descendants() {
+    let (mut wallet, addresses, state, _) = funded();
+    let parent = TxBuilder::new(&state)
+        .build_wallet_payment(&wallet, &addr("bob"), 10_000, 0, &addresses[0])
+        .unwrap();
+    wallet.add_pending(parent.clone());
+    assert_eq!(wallet.balance(), Balance { confirmed: 30_000, pending: 40_000 });
+
+    // Spends the parent's change before it confirms
+    let change = parent.outputs.iter().position(|output| output.address == addresses[0]).unwrap();
+    let child = Transaction {
+        id: Hash([9u8; 32]),
+        inputs: vec![TxInput {
+            txid: parent.id.clone(),
+            index: change as u32,
+            signature: vec![],
+            public_key: vec![],
+        }],
+        outputs: vec![
+            TxOutput { value: 15_000, address: addresses[1] },
+            TxOutput { value: 25_000, address: addr("carol") },
+        ],
+    };
+    assert!(wallet.add_pending(child.clone()));
+    assert_eq!(wallet.balance(), Balance { confirmed: 30_000, pending: 15_000 });
+
+    wallet.abandon(&parent.id);
+    assert!(!wallet.is_pending(&child.id));
+    assert_eq!(wallet.balance(), Balance { confirmed: 80_000, pending: 0 });
+    assert_eq!(wallet.spendable().len(), 2);
+    assert_eq!(wallet.history().len(), 2);
+}
@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+use arcnova_chain::blockchain::params::{Network, NetworkError, MAINNET, REGTEST, TESTNET};
+use arcnova_chain::blockchain::{
+    block::Block,
+    chain::{ChainManager, Verdict},
+    state::State,
+    utxo::UtxoSet,
+    wallet::Wallet,
+};
+use arcnova_chain::crypto::hash::Hash;
+use arcnova_chain::p2p::config::{ConfigError, NodeConfig};
+use arcnova_chain::p2p::messages::{encode_block, message_id, BLOCK_TOPIC_STR};
+use arcnova_chain::storage::{ChainStore, Store};
+
+mod common;
+use common::{args, temp_dir};
+
+// TEST 1 — Every network has its own genesis, magic, port and namespaces
+#[test]
+fn test_network_params_are_distinct() {
+    let all = [&MAINNET, &TESTNET, &REGTEST];
+    for (i, a) in all.iter().enumerate() {
+        for b in &all[i + 1..] {
+            assert_ne!(a.genesis_hash(), b.genesis_hash());
+            assert_ne!(a.magic, b.magic);
+            assert_ne!(a.default_port, b.default_port);
+            assert_ne!(a.address_prefix, b.address_prefix);
+            assert_ne!(a.identify_protocol(), b.identify_protocol());
+            assert_ne!(a.sync_protocol(), b.sync_protocol());
+            assert_ne!(a.topic(BLOCK_TOPIC_STR), b.topic(BLOCK_TOPIC_STR));
+        }
+    }
+
+    // The genesis block is fixed
+    assert_eq!(TESTNET.genesis_block().hash, TESTNET.genesis_hash());
+    assert_eq!("testnet".parse::<Network>().unwrap().params(), &TESTNET);
+    assert!("moon".parse::<Network>().is_err());
+
+    // The same payload gossiped on two networks gets two message IDs
+    let data = encode_block(&REGTEST.genesis_block());
+    assert_ne!(
+        message_id(&REGTEST.topic(BLOCK_TOPIC_STR), &data),
+        message_id(&TESTNET.topic(BLOCK_TOPIC_STR), &data)
+    );
+}
+
+// TEST 2 — Addresses carry their network's prefix and are refused elsewhere
+#[test]
+fn test_addresses_are_namespaced() {
+    let address = Wallet::new().address();
+    let shown = TESTNET.encode_address(&address);
+    assert!(shown.starts_with("tarc1"));
+    assert_eq!(TESTNET.decode_address(&shown).unwrap(), address);
+
+    match MAINNET.decode_address(&shown) {
+        Err(NetworkError::WrongNetwork(_)) => {}
+        _ => panic!("wrong error variant"),
+    }
+    match TESTNET.decode_address("tarc1nothex") {
+        Err(NetworkError::InvalidAddress(_)) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 3 — Empty stores start at genesis; another network's chain is refused
+#[test]
+fn test_init_store_checks_genesis() {
+    // Genesis passes the same checks as every later block
+    for params in [&MAINNET, &TESTNET, &REGTEST] {
+        let genesis = params.genesis_block();
+        assert!(State { utxo_set: UtxoSet::default() }.validate_block(&genesis));
+        let mut manager = ChainManager::new(UtxoSet::default());
+        assert_eq!(manager.process_block(genesis), Verdict::Accept);
+    }
+
+    let mut store = UtxoSet::default();
+    TESTNET.init_store(&mut store).unwrap();
+    assert_eq!(store.tip().unwrap(), Some((0, TESTNET.genesis_hash())));
+
+    // Reopening is fine, as long as it is the same network
+    let dir = temp_dir("network-genesis");
+    {
+        let mut store = ChainStore::open(&dir).unwrap();
+        REGTEST.init_store(&mut store).unwrap();
+        REGTEST.init_store(&mut store).unwrap();
+    }
+    let mut store = ChainStore::open(&dir).unwrap();
+    match MAINNET.init_store(&mut store) {
+        Err(NetworkError::GenesisMismatch(hash)) => assert_eq!(hash, REGTEST.genesis_hash()),
+        _ => panic!("wrong error variant"),
+    }
+
+    // A chain started from some other genesis is refused too
+    let mut store = UtxoSet::default();
+    let other = Block { hash: Hash([9u8; 32]), ..MAINNET.genesis_block() };
+    store.connect_block(&other).unwrap();
+    assert!(MAINNET.init_store(&mut store).is_err());
+}
+
+// TEST 4 — --network picks the data directory and default port
+#[test]
+fn test_config_network_option() {
+    let config = NodeConfig::from_args(args("--network testnet --data-dir /tmp/node")).unwrap();
+    assert_eq!(config.network, Network::Testnet);
+    assert_eq!(config.network_dir(), PathBuf::from("/tmp/node/testnet"));
+    assert_eq!(config.node_key_path(), PathBuf::from("/tmp/node/testnet/node_key"));
+    assert_eq!(config.listen, vec![format!("/ip4/0.0.0.0/tcp/{}", TESTNET.default_port)]);
+
+    // Mainnet keeps using the data directory itself; --port still wins
+    let config = NodeConfig::from_args(args("--port 3001")).unwrap();
+    assert_eq!(config.network_dir(), PathBuf::from("data"));
+    assert_eq!(config.listen, vec!["/ip4/0.0.0.0/tcp/3001".to_string()]);
+
+    match NodeConfig::from_args(args("--network moon")) {
+        Err(ConfigError::InvalidValue { flag, .. }) => assert_eq!(flag, "--network"),
+        _ => panic!("wrong error variant"),
+    }
+}
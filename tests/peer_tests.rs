@@ -0,0 +1,224 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use arcnova_chain::p2p::admin::AdminRequest;
+use arcnova_chain::p2p::banlist::DEFAULT_BAN_DURATION;
+use arcnova_chain::p2p::config::{peer_multiaddr, ConfigError, NodeCommand, NodeConfig};
+use arcnova_chain::p2p::node_key::NodeKey;
+use arcnova_chain::p2p::peers::{PeerBook, DIAL_TIMEOUT, INITIAL_BACKOFF, MAX_BACKOFF, MAX_LEARNED_FAILURES};
+
+mod common;
+use common::{args, temp_dir};
+
+// TEST 1 — The docker-compose command line parses into listen and peer addresses
+#[test]
+fn test_parse_node_args() {
+    let config = NodeConfig::from_args(args("--port 3001 --peers node2:3002,node3:3003")).unwrap();
+    assert_eq!(config.listen, vec!["/ip4/0.0.0.0/tcp/3001".to_string()]);
+    assert_eq!(
+        config.peers,
+        vec!["/dns4/node2/tcp/3002".to_string(), "/dns4/node3/tcp/3003".to_string()]
+    );
+
+    let config = NodeConfig::from_args(args(
+        "--listen /ip4/127.0.0.1/tcp/4000 --listen /ip6/::1/tcp/4000 --data-dir /tmp/node",
+    ))
+    .unwrap();
+    assert_eq!(config.listen.len(), 2);
+    assert!(config.peers.is_empty());
+    assert_eq!(config.data_dir, PathBuf::from("/tmp/node"));
+    assert_eq!(config.prune_depth, None);
+
+    let config = NodeConfig::from_args(args("--prune-depth 550")).unwrap();
+    assert_eq!(config.prune_depth, Some(550));
+
+    assert_eq!(NodeConfig::from_args(Vec::new()).unwrap(), NodeConfig::default());
+}
+
+// TEST 2 — Bad options are reported
+#[test]
+fn test_parse_node_args_errors() {
+    match NodeConfig::from_args(args("--port")) {
+        Err(ConfigError::MissingValue(flag)) => assert_eq!(flag, "--port"),
+        _ => panic!("wrong error variant"),
+    }
+    match NodeConfig::from_args(args("--port 70000")) {
+        Err(ConfigError::InvalidValue { .. }) => {}
+        _ => panic!("wrong error variant"),
+    }
+    match NodeConfig::from_args(args("--prune-depth 10")) {
+        Err(ConfigError::InvalidValue { flag, .. }) => assert_eq!(flag, "--prune-depth"),
+        _ => panic!("wrong error variant"),
+    }
+    match NodeConfig::from_args(args("--verbose")) {
+        Err(ConfigError::UnknownOption(_)) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    assert_eq!(peer_multiaddr("10.0.0.2:3002").unwrap(), "/ip4/10.0.0.2/tcp/3002");
+    assert_eq!(peer_multiaddr("[::1]:3002").unwrap(), "/ip6/::1/tcp/3002");
+    assert!(peer_multiaddr("node2").is_err());
+    assert!(peer_multiaddr(":3002").is_err());
+}
+
+// TEST 3 — Static peers are redialed with exponential backoff
+#[test]
+fn test_static_peer_backoff() {
+    let start = Instant::now();
+    let mut book = PeerBook::in_memory();
+    let addr = "/dns4/node2/tcp/3002".to_string();
+    book.add_static(addr.clone(), start);
+
+    assert_eq!(book.due(start), vec![addr.clone()]);
+    // Already dialing: not handed out again
+    assert!(book.due(start).is_empty());
+
+    book.failed(&addr, start);
+    assert!(book.due(start).is_empty());
+    assert_eq!(book.due(start + INITIAL_BACKOFF), vec![addr.clone()]);
+
+    let now = start + INITIAL_BACKOFF;
+    book.failed(&addr, now);
+    assert!(book.due(now + INITIAL_BACKOFF).is_empty());
+    assert_eq!(book.due(now + INITIAL_BACKOFF * 2), vec![addr.clone()]);
+
+    // Backoff is capped and static peers are never forgotten
+    let mut now = now + INITIAL_BACKOFF * 2;
+    for _ in 0..50 {
+        book.failed(&addr, now);
+        now += MAX_BACKOFF;
+        assert_eq!(book.due(now), vec![addr.clone()]);
+    }
+
+    // Connecting resets the backoff; a disconnect redials shortly after
+    book.connected(&addr);
+    assert!(book.is_connected(&addr));
+    assert!(book.due(now + Duration::from_secs(3600)).is_empty());
+    book.disconnected(&addr, now);
+    assert_eq!(book.due(now + INITIAL_BACKOFF), vec![addr]);
+}
+
+// TEST 4 — Learned addresses are dropped after repeated failures, and stalled
+//          dials time out
+#[test]
+fn test_learned_peer_forgotten() {
+    let mut now = Instant::now();
+    let mut book = PeerBook::in_memory();
+    let addr = "/ip4/10.0.0.9/tcp/3009".to_string();
+    assert!(book.learn(addr.clone(), now));
+    assert!(!book.learn(addr.clone(), now));
+
+    for _ in 0..MAX_LEARNED_FAILURES {
+        now += MAX_BACKOFF;
+        assert_eq!(book.due(now), vec![addr.clone()]);
+        // No outcome reported: the dial times out
+        now += DIAL_TIMEOUT;
+        book.due(now);
+    }
+    assert!(!book.contains(&addr));
+}
+
+// TEST 5 — Known addresses survive a restart
+#[test]
+fn test_peer_book_persistence() {
+    let dir = temp_dir("peers");
+    let path = dir.join("peers.json");
+    let now = Instant::now();
+
+    let mut book = PeerBook::open(&path, now).unwrap();
+    assert!(book.is_empty());
+    book.add_static("/dns4/node2/tcp/3002".into(), now);
+    book.learn("/ip4/172.18.0.4/tcp/3003".into(), now);
+    book.save().unwrap();
+
+    let reopened = PeerBook::open(&path, now).unwrap();
+    assert_eq!(reopened.len(), 2);
+    assert!(reopened.contains("/ip4/172.18.0.4/tcp/3003"));
+
+    std::fs::write(&path, b"not json").unwrap();
+    assert!(PeerBook::open(&path, now).is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+// TEST 6 — The node key is created once and reused; rotation keeps the old one
+#[test]
+fn test_node_key_persistence() {
+    let dir = temp_dir("node-key");
+    let path = dir.join("node_key");
+
+    assert!(NodeKey::load(&path).unwrap().is_none());
+    let key = NodeKey::load_or_generate(&path).unwrap();
+    assert_eq!(NodeKey::load_or_generate(&path).unwrap(), key);
+
+    let rotated = NodeKey::rotate(&path).unwrap();
+    assert_ne!(rotated, key);
+    assert_eq!(NodeKey::load(&path).unwrap(), Some(rotated));
+    assert_eq!(NodeKey::load(path.with_extension("old")).unwrap(), Some(key));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o077, 0);
+    }
+
+    std::fs::write(&path, "abcd\n").unwrap();
+    assert!(NodeKey::load(&path).is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+// TEST 7 — Key management and admin subcommands
+#[test]
+fn test_parse_node_commands() {
+    match NodeCommand::from_args(args("peer-id --data-dir /tmp/n1")).unwrap() {
+        NodeCommand::ShowPeerId(config) => {
+            assert_eq!(config.node_key_path(), PathBuf::from("/tmp/n1/node_key"))
+        }
+        _ => panic!("wrong command"),
+    }
+    assert!(matches!(
+        NodeCommand::from_args(args("rotate-key")).unwrap(),
+        NodeCommand::RotateKey(_)
+    ));
+    assert!(matches!(
+        NodeCommand::from_args(args("--port 3001")).unwrap(),
+        NodeCommand::Run(_)
+    ));
+    match NodeCommand::from_args(args("mine")) {
+        Err(ConfigError::UnknownOption(cmd)) => assert_eq!(cmd, "mine"),
+        _ => panic!("wrong error variant"),
+    }
+
+    match NodeCommand::from_args(args("ban peer-a 60 --data-dir /tmp/n1")).unwrap() {
+        NodeCommand::Admin(config, request) => {
+            assert_eq!(config.admin_socket_path(), PathBuf::from("/tmp/n1/admin/admin.sock"));
+            assert_eq!(request, AdminRequest::Ban { peer: "peer-a".into(), duration: Duration::from_secs(60) });
+        }
+        _ => panic!("wrong command"),
+    }
+    assert_eq!(
+        NodeCommand::from_args(args("ban peer-a --network regtest")).unwrap(),
+        NodeCommand::Admin(
+            NodeConfig::from_args(args("--network regtest")).unwrap(),
+            AdminRequest::Ban { peer: "peer-a".into(), duration: DEFAULT_BAN_DURATION },
+        )
+    );
+    assert!(matches!(
+        NodeCommand::from_args(args("unban peer-a")).unwrap(),
+        NodeCommand::Admin(_, AdminRequest::Unban { .. })
+    ));
+    assert!(matches!(
+        NodeCommand::from_args(args("bans")).unwrap(),
+        NodeCommand::Admin(_, AdminRequest::ListBans)
+    ));
+    match NodeCommand::from_args(args("unban --data-dir /tmp/n1")) {
+        Err(ConfigError::MissingValue(cmd)) => assert_eq!(cmd, "unban"),
+        _ => panic!("wrong error variant"),
+    }
+    match NodeCommand::from_args(args("ban peer-a soon")) {
+        Err(ConfigError::InvalidValue { value, .. }) => assert_eq!(value, "soon"),
+        _ => panic!("wrong error variant"),
+    }
+}
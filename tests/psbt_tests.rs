@@ -0,0 +1,185 @@
+use arcnova_chain::blockchain::{
+    address::Address,
+    coin_select::LargestFirst,
+    hd::HdWallet,
+    psbt::{Psbt, PsbtError},
+    state::State,
+    transaction::TxOutput,
+    tx_builder::TxBuilder,
+    utxo::UtxoSet,
+    wallet::Wallet,
+};
+use arcnova_chain::crypto::hash::Hash;
+
+mod common;
+use common::addr;
+
+fn funded(outputs: &[(Address, u64)]) -> State {
+    let mut utxo_set = UtxoSet::default();
+    for (i, (address, value)) in outputs.iter().enumerate() {
+        utxo_set.insert((Hash([i as u8 + 1; 32]), 0), TxOutput { value: *value, address: *address });
+    }
+    State { utxo_set }
+}
+
+// TEST 1 — A coordinator with only the public key, an offline signer with the secret
+#[test]
+fn test_build_sign_finalize() {
+    let wallet = Wallet::new();
+    let state = funded(&[(wallet.address(), 50_000)]);
+
+    let psbt = TxBuilder::new(&state)
+        .build_psbt(&wallet.public_key_bytes(), &addr("bob"), 20_000, 1_000, &LargestFirst)
+        .unwrap();
+    assert_eq!(psbt.fee(), Some(1_000));
+    assert_eq!(psbt.inputs[0].previous_output.as_ref().unwrap().value, 50_000);
+
+    // Over the wire to the signer and back
+    let mut offline = Psbt::from_bytes(&psbt.to_bytes()).unwrap();
+    assert_eq!(offline, psbt);
+    assert_eq!(offline.sign(&wallet).unwrap(), 1);
+    assert_eq!(offline.sign(&Wallet::new()).unwrap(), 0);
+
+    let tx = Psbt::from_bytes(&offline.to_bytes()).unwrap().finalize().unwrap();
+    assert_eq!(tx.id, psbt.signing_digest().unwrap());
+    assert!(state.validate_transaction(&tx));
+}
+
+// TEST 2 — Inputs from two keys signed separately and combined
+#[test]
+fn test_combine_signatures() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let state = funded(&[(alice.address(), 30_000), (bob.address(), 40_000)]);
+
+    let mut psbt = Psbt::create(
+        vec![(Hash([1u8; 32]), 0), (Hash([2u8; 32]), 0)],
+        vec![TxOutput { value: 69_000, address: addr("carol") }],
+    );
+    psbt.update(&state.utxo_set).unwrap();
+    assert_eq!(psbt.fee(), Some(1_000));
+    psbt.set_key(0, &alice.public_key_bytes(), None).unwrap();
+    psbt.set_key(1, &bob.public_key_bytes(), None).unwrap();
+
+    let mut for_alice = psbt.clone();
+    let mut for_bob = psbt.clone();
+    for_alice.sign(&alice).unwrap();
+    for_bob.sign(&bob).unwrap();
+
+    match for_alice.finalize() {
+        Err(PsbtError::NotSigned(1)) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    for_alice.combine(&for_bob).unwrap();
+    let tx = for_alice.finalize().unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    assert!(state.validate_transaction(&tx));
+}
+
+// TEST 3 — Keys, transactions and signatures that do not fit are refused
+#[test]
+fn test_psbt_errors() {
+    let wallet = Wallet::new();
+    let state = funded(&[(wallet.address(), 50_000)]);
+    let outputs = vec![TxOutput { value: 49_000, address: addr("bob") }];
+
+    let mut psbt = Psbt::create(vec![(Hash([1u8; 32]), 0)], outputs);
+    match psbt.sign(&wallet) {
+        Err(PsbtError::MissingPublicKey(0)) => {}
+        _ => panic!("wrong error variant"),
+    }
+    match psbt.set_key(0, &wallet.public_key_bytes(), None) {
+        Err(PsbtError::MissingPreviousOutput(0)) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    psbt.update(&state.utxo_set).unwrap();
+    match psbt.set_key(0, &Wallet::new().public_key_bytes(), None) {
+        Err(PsbtError::KeyMismatch(0)) => {}
+        _ => panic!("wrong error variant"),
+    }
+    psbt.set_key(0, &wallet.public_key_bytes(), None).unwrap();
+    psbt.sign(&wallet).unwrap();
+
+    let other = Psbt::create(vec![(Hash([1u8; 32]), 0)], vec![]);
+    match psbt.clone().combine(&other) {
+        Err(PsbtError::DifferentTransaction) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    let mut tampered = psbt.clone();
+    tampered.inputs[0].signature.as_mut().unwrap()[0] ^= 1;
+    match tampered.finalize() {
+        Err(PsbtError::InvalidSignature(0)) => {}
+        _ => panic!("wrong error variant"),
+    }
+    match psbt.clone().combine(&tampered) {
+        Err(PsbtError::Conflict(0)) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    let mut future = psbt.clone();
+    future.version = 2;
+    match Psbt::from_bytes(&future.to_bytes()) {
+        Err(PsbtError::UnsupportedVersion(2)) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 4 — Inputs tagged with an HD index are signed from the seed
+#[test]
+fn test_sign_with_hd_wallet() {
+    let hd = HdWallet::from_seed(&[7u8; 64], 0);
+    let key = hd.derive(3);
+    let state = funded(&[(key.address(), 10_000)]);
+
+    let mut psbt = Psbt::create(
+        vec![(Hash([1u8; 32]), 0)],
+        vec![TxOutput { value: 9_500, address: addr("bob") }],
+    );
+    psbt.update(&state.utxo_set).unwrap();
+    psbt.set_key(0, &key.public_key_bytes(), Some(3)).unwrap();
+
+    assert_eq!(psbt.sign_hd(&hd).unwrap(), 1);
+    let tx = psbt.finalize().unwrap();
+    assert!(state.validate_transaction(&tx));
+}
+
+// TEST 5 — Malformed PSBTs are refused instead of panicking
+#[test]
+fn test_malformed_psbt() {
+    let wallet = Wallet::new();
+    let state = funded(&[(wallet.address(), 50_000)]);
+
+    let mut psbt = Psbt::create(
+        vec![(Hash([1u8; 32]), 0)],
+        vec![TxOutput { value: 49_000, address: addr("bob") }],
+    );
+    psbt.update(&state.utxo_set).unwrap();
+
+    let mut truncated = psbt.clone();
+    truncated.inputs.clear();
+    match Psbt::from_bytes(&truncated.to_bytes()) {
+        Err(PsbtError::InputCountMismatch { outpoints: 1, inputs: 0 }) => {}
+        _ => panic!("wrong error variant"),
+    }
+    assert!(truncated.clone().update(&state.utxo_set).is_err());
+    assert!(truncated.finalize().is_err());
+    assert!(psbt.clone().combine(&truncated).is_err());
+
+    let mut trailing = psbt.to_bytes();
+    trailing.push(0);
+    match Psbt::from_bytes(&trailing) {
+        Err(PsbtError::Malformed(_)) => {}
+        _ => panic!("wrong error variant"),
+    }
+    match Psbt::from_bytes(&[0xff; 16]) {
+        Err(PsbtError::Malformed(_)) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    let mut huge = psbt.clone();
+    huge.outputs = vec![TxOutput { value: u64::MAX, address: addr("bob") }; 2];
+    assert_eq!(huge.fee(), None);
+}
@@ -0,0 +1,185 @@
+#![cfg(unix)]
+
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use arcnova_chain::blockchain::{
+    address::Address,
+    block::Block,
+    coin_select::LargestFirst,
+    multiwallet::MultiWallet,
+    signer::{serve_signer, RemoteSigner, Signer, SignerError},
+    state::State,
+    transaction::{Transaction, TxOutput},
+    tx_builder::{TxBuildError, TxBuilder},
+    utxo::UtxoSet,
+    wallet::Wallet,
+};
+use arcnova_chain::crypto::hash::Hash;
+
+mod common;
+use common::{addr, temp_dir};
+
+fn funded(address: Address, values: &[u64]) -> State {
+    let mut utxo_set = UtxoSet::default();
+    for (i, &value) in values.iter().enumerate() {
+        utxo_set.insert((Hash([i as u8 + 1; 32]), 0), TxOutput { value, address });
+    }
+    State { utxo_set }
+}
+
+// A signer that counts what it signs, or refuses everything
+struct Hsm {
+    key: Wallet,
+    refuse: bool,
+    signed: Arc<AtomicUsize>,
+}
+
+impl Hsm {
+    fn new(refuse: bool) -> Self {
+        Hsm { key: Wallet::new(), refuse, signed: Arc::new(AtomicUsize::new(0)) }
+    }
+}
+
+impl Signer for Hsm {
+    fn public_key(&self) -> Vec<u8> {
+        self.key.public_key_bytes()
+    }
+
+    fn sign_digest(&self, digest: &Hash) -> Result<Vec<u8>, SignerError> {
+        if self.refuse {
+            return Err(SignerError::Refused("policy".into()));
+        }
+        self.signed.fetch_add(1, Ordering::SeqCst);
+        self.key.sign_digest(digest)
+    }
+}
+
+// Serve `signer` on one end of a socket pair; returns the other end
+fn spawn_remote(signer: impl Signer + 'static) -> RemoteSigner<UnixStream> {
+    let (client, mut server) = UnixStream::pair().unwrap();
+    thread::spawn(move || serve_signer(&mut server, &signer));
+    RemoteSigner::new(client).unwrap()
+}
+
+// TEST 1 — Any Signer can fund a payment; the digest is signed once
+#[test]
+fn test_custom_signer_in_builder() {
+    let hsm = Hsm::new(false);
+    let state = funded(hsm.key.address(), &[10_000, 20_000, 30_000]);
+
+    let tx = TxBuilder::new(&state).build_payment_tx(&hsm, &addr("bob"), 45_000, 1_000).unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    assert_eq!(hsm.signed.load(Ordering::SeqCst), 1);
+    assert!(state.validate_transaction(&tx));
+
+    // A plain wallet is a signer too
+    let wallet = Wallet::new();
+    let state = funded(wallet.address(), &[10_000]);
+    let tx = TxBuilder::new(&state).build_payment_tx(&wallet, &addr("bob"), 5_000, 100).unwrap();
+    assert!(state.validate_transaction(&tx));
+}
+
+// TEST 2 — Signing in another thread over a Unix socket
+#[test]
+fn test_remote_signer_over_socket() {
+    let dir = temp_dir("socket");
+    let path = dir.join("signer.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let key = Wallet::new();
+    let address = key.address();
+    thread::spawn(move || {
+        for stream in listener.incoming().take(2) {
+            serve_signer(&mut stream.unwrap(), &key).unwrap();
+        }
+    });
+
+    let remote = RemoteSigner::connect(&path).unwrap();
+    assert_eq!(remote.address(), Some(address));
+    let state = funded(address, &[50_000]);
+
+    let tx = TxBuilder::new(&state)
+        .build_payment_tx_at_rate(&remote, &Wallet::new().address(), 20_000, 3, &LargestFirst)
+        .unwrap();
+    assert!(state.validate_transaction(&tx));
+
+    // PSBTs are signed the same way, over a fresh connection
+    let mut psbt = TxBuilder::new(&state)
+        .build_psbt(&remote.public_key(), &addr("bob"), 1_000, 100, &LargestFirst)
+        .unwrap();
+    drop(remote);
+    assert_eq!(psbt.sign(&RemoteSigner::connect(&path).unwrap()).unwrap(), 1);
+    assert!(state.validate_transaction(&psbt.finalize().unwrap()));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// TEST 3 — Refusals and bad signatures from the other side surface as errors
+#[test]
+fn test_remote_signer_failures() {
+    let remote = spawn_remote(Hsm::new(true));
+    let state = funded(remote.address().unwrap(), &[10_000]);
+
+    match TxBuilder::new(&state).build_payment_tx(&remote, &addr("bob"), 1_000, 10) {
+        Err(TxBuildError::Signer(SignerError::Refused(reason))) => assert!(reason.contains("policy")),
+        _ => panic!("wrong error variant"),
+    }
+
+    // A signer answering with some other key's signatures
+    struct Liar(Wallet, Wallet);
+    impl Signer for Liar {
+        fn public_key(&self) -> Vec<u8> {
+            self.0.public_key_bytes()
+        }
+        fn sign_digest(&self, digest: &Hash) -> Result<Vec<u8>, SignerError> {
+            self.1.sign_digest(digest)
+        }
+    }
+    let remote = spawn_remote(Liar(Wallet::new(), Wallet::new()));
+    match remote.sign_digest(&Hash([5u8; 32])) {
+        Err(SignerError::Protocol(_)) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 4 — External signers as keys of a multi-address wallet
+#[test]
+fn test_multiwallet_with_external_signer() {
+    let hsm = Hsm::new(false);
+    let signed = hsm.signed.clone();
+    let remote = spawn_remote(hsm);
+    let mut wallet = MultiWallet::new();
+    let address = wallet.add_signer(Box::new(remote)).unwrap();
+    assert!(!wallet.is_watch_only(&address));
+    assert!(wallet.key(&address).is_some());
+
+    let genesis = Block {
+        index: 0,
+        prev_hash: Hash([0u8; 32]),
+        timestamp: 0,
+        transactions: (1..=2)
+            .map(|i| Transaction {
+                id: Hash([i; 32]),
+                inputs: vec![],
+                outputs: vec![TxOutput { value: 20_000, address }],
+            })
+            .collect(),
+        nonce: 0,
+        hash: Hash([100u8; 32]),
+    };
+    wallet.connect_block(&genesis);
+    let state = funded(address, &[20_000, 20_000]);
+
+    // Both inputs share the key, so the remote signs one digest
+    let tx = TxBuilder::new(&state)
+        .build_wallet_payment(&wallet, &addr("bob"), 30_000, 100, &address)
+        .unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    assert_eq!(signed.load(Ordering::SeqCst), 1);
+    assert!(state.validate_transaction(&tx));
+    assert!(wallet.add_pending(tx));
+    assert_eq!(wallet.balance().pending, 9_900);
+}
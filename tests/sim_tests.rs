@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use arcnova_chain::blockchain::params::REGTEST;
+use arcnova_chain::p2p::sim::{SimConfig, Simulator};
+
+const LIMIT: Duration = Duration::from_secs(600);
+
+// Tips of all nodes, for comparing runs
+fn tips(sim: &Simulator) -> Vec<Option<u64>> {
+    (0..sim.len()).map(|id| sim.node(id).tip().map(|(height, _)| height)).collect()
+}
+
+// TEST 1 — Mined blocks reach every node of a line topology
+#[test]
+fn test_blocks_propagate_across_hops() {
+    let mut sim = Simulator::new(4, &REGTEST, SimConfig::default());
+    for id in 0..3 {
+        sim.connect(id, id + 1);
+    }
+    assert!(sim.run_until_converged(LIMIT));
+
+    for _ in 0..5 {
+        sim.mine(0, Vec::new());
+        sim.run_for(Duration::from_secs(1));
+    }
+    assert!(sim.run_until_converged(LIMIT));
+    assert_eq!(sim.node(3).tip().unwrap().0, 5);
+
+    // Three hops of 50ms each
+    let mut sim2 = Simulator::new(4, &REGTEST, SimConfig::default());
+    for id in 0..3 {
+        sim2.connect(id, id + 1);
+    }
+    sim2.run_until_idle(LIMIT);
+    let start = sim2.now();
+    sim2.mine(0, Vec::new());
+    sim2.run_until_converged(LIMIT);
+    assert_eq!(sim2.now() - start, Duration::from_millis(150));
+}
+
+// TEST 2 — A partitioned minority catches up through sync after healing
+#[test]
+fn test_partition_heals_through_sync() {
+    let mut sim = Simulator::new(5, &REGTEST, SimConfig::default());
+    sim.connect_all();
+    sim.run_until_idle(LIMIT);
+
+    sim.partition(&[&[0, 1, 2], &[3, 4]]);
+    for _ in 0..20 {
+        sim.mine(0, Vec::new());
+    }
+    sim.run_until_idle(LIMIT);
+    assert!(!sim.converged());
+    assert_eq!(sim.node(2).tip().unwrap().0, 20);
+    assert_eq!(sim.node(4).tip().unwrap().0, 0);
+
+    sim.heal();
+    assert!(sim.run_until_converged(LIMIT));
+    assert_eq!(sim.node(4).tip(), sim.node(0).tip());
+}
+
+// TEST 3 — Lost requests time out and sync moves to another peer
+#[test]
+fn test_lossy_network_still_syncs() {
+    let config = SimConfig {
+        latency: Duration::from_millis(20),
+        jitter: Duration::from_millis(80),
+        drop_rate: 0.0,
+        seed: 7,
+    };
+    let mut sim = Simulator::new(4, &REGTEST, config);
+    sim.connect(0, 1);
+    sim.connect(0, 2);
+    for _ in 0..40 {
+        sim.mine(0, Vec::new());
+    }
+    sim.run_until_idle(LIMIT);
+
+    // Node 3 joins while every message it sends to node 1 is lost
+    sim.set_conditions(Duration::from_millis(20), Duration::from_millis(80), 1.0);
+    sim.connect(3, 1);
+    sim.run_for(Duration::from_secs(1));
+    sim.set_conditions(Duration::from_millis(20), Duration::from_millis(80), 0.0);
+    sim.connect(3, 2);
+
+    assert!(sim.run_until_converged(LIMIT));
+    assert!(sim.stats().dropped > 0);
+    assert!(sim.stats().failed_requests > 0);
+}
+
+// TEST 4 — The same seed gives the same run
+#[test]
+fn test_runs_are_deterministic() {
+    let run = |seed: u64| {
+        let config = SimConfig { jitter: Duration::from_millis(200), drop_rate: 0.2, seed, ..SimConfig::default() };
+        let mut sim = Simulator::new(6, &REGTEST, config);
+        sim.connect_all();
+        for round in 0..10 {
+            sim.mine(round % 6, Vec::new());
+            sim.run_for(Duration::from_millis(300));
+        }
+        sim.run_until_idle(LIMIT);
+        (tips(&sim), sim.stats(), sim.now())
+    };
+
+    assert_eq!(run(1), run(1));
+    assert_ne!(run(1), run(2));
+}
@@ -0,0 +1,168 @@
+use arcnova_chain::blockchain::{
+    block::Block,
+    transaction::{Transaction, TxInput, TxOutput},
+    utxo::UtxoSet,
+};
+use arcnova_chain::crypto::hash::Hash;
+use arcnova_chain::storage::{
+    export_snapshot, import_snapshot, snapshot::SNAPSHOT_MAGIC, ChainStore, StorageError, Store,
+};
+
+mod common;
+use common::{addr, temp_dir};
+
+fn sample_blocks() -> Vec<Block> {
+    let coinbase = Transaction {
+        id: Hash([1u8; 32]),
+        inputs: vec![],
+        outputs: vec![
+            TxOutput { value: 30, address: addr("alice") },
+            TxOutput { value: 20, address: addr("bob") },
+        ],
+    };
+    let spend = Transaction {
+        id: Hash([2u8; 32]),
+        inputs: vec![TxInput {
+            txid: Hash([1u8; 32]),
+            index: 0,
+            signature: vec![],
+            public_key: vec![],
+        }],
+        outputs: vec![TxOutput { value: 30, address: addr("carol") }],
+    };
+
+    let genesis = Block {
+        index: 0,
+        prev_hash: Hash([0u8; 32]),
+        timestamp: 0,
+        transactions: vec![coinbase],
+        nonce: 0,
+        hash: Hash([10u8; 32]),
+    };
+    let b1 = Block {
+        index: 1,
+        prev_hash: genesis.hash.clone(),
+        timestamp: 1,
+        transactions: vec![spend],
+        nonce: 0,
+        hash: Hash([11u8; 32]),
+    };
+    vec![genesis, b1]
+}
+
+// TEST 1 — Commitment is order independent and tracks undo
+#[test]
+fn test_commitment_incremental() {
+    let a = ((Hash([1u8; 32]), 0), TxOutput { value: 1, address: addr("a") });
+    let b = ((Hash([2u8; 32]), 0), TxOutput { value: 2, address: addr("b") });
+
+    let mut first = UtxoSet::default();
+    first.insert(a.0.clone(), a.1.clone());
+    first.insert(b.0.clone(), b.1.clone());
+
+    let mut second = UtxoSet::default();
+    second.insert(b.0.clone(), b.1.clone());
+    second.insert(a.0.clone(), a.1.clone());
+    assert_eq!(first.commitment(), second.commitment());
+
+    let empty = UtxoSet::default().commitment();
+    first.remove(&a.0);
+    first.remove(&b.0);
+    assert_eq!(first.commitment(), empty);
+}
+
+// TEST 2 — Memory and disk backends agree on the commitment
+#[test]
+fn test_commitment_matches_across_backends() {
+    let mut memory = UtxoSet::default();
+    let mut disk = ChainStore::open(temp_dir("commitment")).unwrap();
+
+    for block in sample_blocks() {
+        Store::connect_block(&mut memory, &block).unwrap();
+        disk.connect_block(&block).unwrap();
+    }
+    assert_eq!(memory.commitment(), disk.utxo_commitment().unwrap());
+
+    disk.disconnect_tip().unwrap();
+    Store::disconnect_tip(&mut memory).unwrap();
+    assert_eq!(memory.commitment(), disk.utxo_commitment().unwrap());
+}
+
+// TEST 3 — Export, verify against a trusted commitment and sync on top
+#[test]
+fn test_snapshot_roundtrip_and_continue() {
+    let dir = temp_dir("snapshot");
+    let blocks = sample_blocks();
+
+    let mut utxo_set = UtxoSet::default();
+    for block in &blocks {
+        Store::connect_block(&mut utxo_set, block).unwrap();
+    }
+    let trusted = utxo_set.commitment();
+
+    let path = dir.join("utxo.snapshot");
+    export_snapshot(&path, 1, blocks[1].hash.clone(), &utxo_set).unwrap();
+
+    let (header, imported) = import_snapshot(&path, &trusted).unwrap();
+    assert_eq!(header.height, 1);
+    assert_eq!(imported.len(), utxo_set.len());
+    assert_eq!(imported.available_balance(&addr("carol")), 30);
+
+    let mut store = ChainStore::open(dir.join("node")).unwrap();
+    store.load_snapshot(&header, &imported).unwrap();
+    assert_eq!(store.utxo_commitment().unwrap(), trusted);
+
+    let next = Block {
+        index: 2,
+        prev_hash: blocks[1].hash.clone(),
+        timestamp: 2,
+        transactions: vec![Transaction {
+            id: Hash([3u8; 32]),
+            inputs: vec![TxInput {
+                txid: Hash([2u8; 32]),
+                index: 0,
+                signature: vec![],
+                public_key: vec![],
+            }],
+            outputs: vec![TxOutput { value: 30, address: addr("dave") }],
+        }],
+        nonce: 0,
+        hash: Hash([12u8; 32]),
+    };
+    store.connect_block(&next).unwrap();
+    assert_eq!(store.height().unwrap(), Some(2));
+    assert_eq!(store.find_unspent(&addr("dave")).unwrap().len(), 1);
+}
+
+// TEST 4 — Tampered snapshots and untrusted commitments are rejected
+#[test]
+fn test_snapshot_rejects_tampering() {
+    let dir = temp_dir("snapshot-bad");
+    let mut utxo_set = UtxoSet::default();
+    for block in sample_blocks() {
+        Store::connect_block(&mut utxo_set, &block).unwrap();
+    }
+
+    let path = dir.join("utxo.snapshot");
+    export_snapshot(&path, 1, Hash([11u8; 32]), &utxo_set).unwrap();
+
+    match import_snapshot(&path, &Hash([0u8; 32])) {
+        Err(StorageError::CommitmentMismatch { .. }) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    let mut bytes = std::fs::read(&path).unwrap();
+    let mut old = bytes.clone();
+    old[SNAPSHOT_MAGIC.len()] = 1;
+    std::fs::write(dir.join("old.snapshot"), old).unwrap();
+    match import_snapshot(dir.join("old.snapshot"), &utxo_set.commitment()) {
+        Err(StorageError::UnsupportedVersion { version: 1, .. }) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    std::fs::write(&path, bytes).unwrap();
+
+    assert!(import_snapshot(&path, &utxo_set.commitment()).is_err());
+}
@@ -0,0 +1,232 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use arcnova_chain::blockchain::{
+    state::State,
+    transaction::{Transaction, TxInput, TxOutput},
+    tx_builder::TxBuilder,
+    utxo::UtxoSet,
+    wallet::Wallet,
+};
+use arcnova_chain::crypto::hash::Hash;
+use arcnova_chain::storage::kv::{KvLog, WriteBatch};
+use arcnova_chain::storage::{ChainStore, StorageError, Store, StoreConfig, MIN_PRUNE_DEPTH};
+
+mod common;
+use common::{addr, block, coinbase, temp_dir};
+
+// TEST 1 — Blocks and UTXOs survive a reopen
+#[test]
+fn test_chain_store_persists_across_reopen() {
+    let dir = temp_dir("persist");
+
+    let genesis = block(0, Hash([0u8; 32]), vec![coinbase(1, addr("miner"), 50)]);
+    let spend = Transaction {
+        id: Hash([2u8; 32]),
+        inputs: vec![TxInput {
+            txid: genesis.transactions[0].id.clone(),
+            index: 0,
+            signature: vec![],
+            public_key: vec![],
+        }],
+        outputs: vec![TxOutput { value: 50, address: addr("bob") }],
+    };
+    let b1 = block(1, genesis.hash.clone(), vec![spend]);
+
+    {
+        let mut store = ChainStore::open(&dir).unwrap();
+        store.connect_block(&genesis).unwrap();
+        store.connect_block(&b1).unwrap();
+    }
+
+    let store = ChainStore::open(&dir).unwrap();
+    assert_eq!(store.height().unwrap(), Some(1));
+    assert_eq!(store.hash_at_height(0).unwrap(), Some(genesis.hash.clone()));
+    assert_eq!(store.get_block_at_height(1).unwrap().unwrap().hash, b1.hash);
+
+    let utxos = store.load_utxo_set().unwrap();
+    assert_eq!(utxos.len(), 1);
+    assert_eq!(utxos.available_balance(&addr("bob")), 50);
+    assert_eq!(utxos.available_balance(&addr("miner")), 0);
+}
+
+// TEST 2 — Disconnecting the tip restores spent outputs
+#[test]
+fn test_chain_store_disconnect_tip() {
+    let dir = temp_dir("disconnect");
+    let mut store = ChainStore::open(&dir).unwrap();
+
+    let genesis = block(0, Hash([0u8; 32]), vec![coinbase(1, addr("miner"), 50)]);
+    let spend = Transaction {
+        id: Hash([2u8; 32]),
+        inputs: vec![TxInput {
+            txid: genesis.transactions[0].id.clone(),
+            index: 0,
+            signature: vec![],
+            public_key: vec![],
+        }],
+        outputs: vec![TxOutput { value: 50, address: addr("bob") }],
+    };
+    let b1 = block(1, genesis.hash.clone(), vec![spend]);
+
+    store.connect_block(&genesis).unwrap();
+    store.connect_block(&b1).unwrap();
+    store.disconnect_tip().unwrap();
+
+    assert_eq!(store.height().unwrap(), Some(0));
+    let utxos = store.load_utxo_set().unwrap();
+    assert_eq!(utxos.available_balance(&addr("miner")), 50);
+    assert_eq!(utxos.available_balance(&addr("bob")), 0);
+}
+
+// TEST 3 — A torn write at the end of the files is discarded on open
+#[test]
+fn test_chain_store_recovers_from_torn_write() {
+    let dir = temp_dir("torn");
+
+    let genesis = block(0, Hash([0u8; 32]), vec![coinbase(1, addr("miner"), 50)]);
+    {
+        let mut store = ChainStore::open(&dir).unwrap();
+        store.connect_block(&genesis).unwrap();
+    }
+
+    // Simulate a crash halfway through the next block
+    for file in ["chainstate.log", "blocks/blk00000.dat"] {
+        let mut f = OpenOptions::new().append(true).open(dir.join(file)).unwrap();
+        f.write_all(&[0xff, 0x00, 0x00, 0x00, 1, 2, 3]).unwrap();
+    }
+
+    let mut store = ChainStore::open(&dir).unwrap();
+    assert_eq!(store.height().unwrap(), Some(0));
+
+    let b1 = block(1, genesis.hash.clone(), vec![coinbase(3, addr("miner"), 50)]);
+    store.connect_block(&b1).unwrap();
+
+    let store = ChainStore::open(&dir).unwrap();
+    assert_eq!(store.get_block_at_height(1).unwrap().unwrap().hash, b1.hash);
+    assert_eq!(store.load_utxo_set().unwrap().available_balance(&addr("miner")), 100);
+}
+
+// Shared flow for TEST 4: fund a wallet, then build and validate a payment
+fn build_payment_against<S: Store>(mut store: S) {
+    let wallet = Wallet::new();
+    let genesis = block(0, Hash([0u8; 32]), vec![coinbase(1, wallet.address(), 80_000)]);
+    store.connect_block(&genesis).unwrap();
+
+    let state = State { utxo_set: store };
+    let tx = TxBuilder::new(&state)
+        .build_payment_tx(&wallet, &addr("bob"), 30_000, 1_000)
+        .unwrap();
+
+    assert!(state.validate_transaction(&tx));
+    assert_eq!(state.utxo_set.available_balance(&wallet.address()).unwrap(), 80_000);
+}
+
+// TEST 4 — State and TxBuilder work against both backends
+#[test]
+fn test_state_works_with_memory_and_disk_stores() {
+    build_payment_against(UtxoSet::default());
+    build_payment_against(ChainStore::open(temp_dir("state")).unwrap());
+}
+
+// TEST 5 — The on-disk address index pages through outputs in order
+#[test]
+fn test_chain_store_address_index_pagination() {
+    let dir = temp_dir("address-index");
+    let mut store = ChainStore::open(&dir).unwrap();
+
+    let txs = (1..=7).map(|value| coinbase(0, addr("miner"), value)).collect();
+    store.connect_block(&block(0, Hash([0u8; 32]), txs)).unwrap();
+
+    let first = store.find_unspent_page(&addr("miner"), None, 4).unwrap();
+    let rest = store.find_unspent_page(&addr("miner"), first.last().map(|(k, _)| k), 4).unwrap();
+
+    assert_eq!(first.len(), 4);
+    assert_eq!(rest.len(), 3);
+    assert_eq!(store.find_unspent(&addr("miner")).unwrap().len(), 7);
+    assert!(store.find_unspent(&addr("nobody")).unwrap().is_empty());
+
+    store.disconnect_tip().unwrap();
+    assert!(store.find_unspent(&addr("miner")).unwrap().is_empty());
+}
+
+// TEST 6 — Pruning drops old block data but keeps headers and UTXOs
+#[test]
+fn test_chain_store_pruning() {
+    let dir = temp_dir("prune");
+    let config = StoreConfig {
+        prune_depth: Some(MIN_PRUNE_DEPTH),
+        max_segment_size: 1024,
+    };
+    let mut store = ChainStore::open_with_config(&dir, config.clone()).unwrap();
+
+    let mut prev = Hash([0u8; 32]);
+    let mut hashes = Vec::new();
+    for height in 0..MIN_PRUNE_DEPTH + 50 {
+        let mut b = block(height, prev.clone(), vec![coinbase(0, addr("miner"), 1)]);
+        b.hash = Hash::hash(&height);
+        b.transactions[0].id = Hash::hash(&(height, "cb"));
+        store.connect_block(&b).unwrap();
+        prev = b.hash.clone();
+        hashes.push(b.hash);
+    }
+
+    assert!(store.is_pruned().unwrap());
+    let state = store.prune_state().unwrap().unwrap();
+    assert!(state.pruned_height > 0);
+
+    // Old blocks are gone, their headers are not
+    match store.get_block(&hashes[0]) {
+        Err(StorageError::BlockPruned(0)) => {}
+        _ => panic!("expected pruned block"),
+    }
+    assert_eq!(store.block_entry(&hashes[0]).unwrap().unwrap().height, 0);
+
+    // Recent blocks are still served and the tip can be disconnected
+    let tip_height = MIN_PRUNE_DEPTH + 49;
+    assert!(store.get_block_at_height(tip_height - MIN_PRUNE_DEPTH + 1).unwrap().is_some());
+    assert_eq!(
+        store.load_utxo_set().unwrap().available_balance(&addr("miner")),
+        MIN_PRUNE_DEPTH + 50
+    );
+    store.disconnect_tip().unwrap();
+
+    // Prune state survives a restart
+    drop(store);
+    let store = ChainStore::open_with_config(&dir, config).unwrap();
+    assert_eq!(store.prune_state().unwrap(), Some(state));
+}
+
+// TEST 7 — Prune depths inside the reorg window are rejected
+#[test]
+fn test_chain_store_rejects_shallow_prune_depth() {
+    let config = StoreConfig {
+        prune_depth: Some(MIN_PRUNE_DEPTH - 1),
+        ..StoreConfig::default()
+    };
+
+    match ChainStore::open_with_config(temp_dir("prune-config"), config) {
+        Err(StorageError::Config(_)) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 8 — Stores written before the format was versioned are refused
+#[test]
+fn test_chain_store_rejects_unversioned_store() {
+    let dir = temp_dir("unversioned");
+    ChainStore::open(&dir).unwrap();
+    ChainStore::open(&dir).unwrap();
+
+    let legacy = temp_dir("legacy-store");
+    let mut log = KvLog::open(legacy.join("chainstate.log")).unwrap();
+    let mut batch = WriteBatch::new();
+    batch.put(b"t".to_vec(), vec![0u8; 32]);
+    log.commit(batch).unwrap();
+    drop(log);
+
+    match ChainStore::open(&legacy) {
+        Err(StorageError::UnsupportedVersion { version: 1, .. }) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
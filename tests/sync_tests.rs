@@ -0,0 +1,294 @@
+use arcnova_chain::blockchain::{
+    block::Block,
+    chain::ChainManager,
+    utxo::UtxoSet,
+};
+use arcnova_chain::crypto::hash::Hash;
+use arcnova_chain::p2p::pipeline::{run_chain_manager, ChainCommand, ChainEvent};
+use arcnova_chain::p2p::sync::{
+    block_locator, serve_request, SyncError, SyncManager, SyncRequest, SyncResponse,
+    MAX_BLOCKS_PER_REQUEST,
+};
+use arcnova_chain::storage::Store;
+use tokio::sync::mpsc;
+
+mod common;
+use common::{addr, block, coinbase};
+
+// `len` blocks on top of genesis, each paying a fresh coinbase to "miner"
+fn build_chain(len: u64) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    for index in 0..len {
+        let prev_hash = blocks.last().map_or(Hash([0u8; 32]), |b| b.hash.clone());
+        blocks.push(block(index, prev_hash, vec![coinbase(index, addr("miner"), 50)]));
+    }
+    blocks
+}
+
+fn serving_node(blocks: &[Block]) -> UtxoSet {
+    let mut utxo_set = UtxoSet::default();
+    for b in blocks {
+        utxo_set.connect_block(b).unwrap();
+    }
+    utxo_set
+}
+
+fn requested_hashes(request: &SyncRequest) -> Vec<Hash> {
+    match request {
+        SyncRequest::GetBlocks { hashes } => hashes.clone(),
+        _ => panic!("wrong request"),
+    }
+}
+
+fn blocks_for(server: &UtxoSet, request: &SyncRequest) -> Vec<Block> {
+    match serve_request(server, request) {
+        SyncResponse::Blocks(blocks) => blocks,
+        _ => panic!("wrong response"),
+    }
+}
+
+// TEST 1 — Headers are fetched first, then blocks, released in chain order
+#[test]
+fn test_headers_first_sync() {
+    let chain = build_chain(5);
+    let server = serving_node(&chain);
+    let mut sync = SyncManager::new(None);
+
+    let requests = sync.add_peer("a", true);
+    assert_eq!(requests, vec![("a", SyncRequest::GetHeaders { locator: vec![] })]);
+
+    let headers = match serve_request(&server, &requests[0].1) {
+        SyncResponse::Headers(headers) => headers,
+        _ => panic!("wrong response"),
+    };
+    assert_eq!(headers.len(), 5);
+
+    let requests = sync.on_headers(&"a", headers).unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].0, "a");
+    assert_eq!(requests[0].1, SyncRequest::GetBlocks { hashes: chain.iter().map(|b| b.hash.clone()).collect() });
+    assert_eq!(sync.best_header_height(), Some(4));
+
+    let blocks = blocks_for(&server, &requests[0].1);
+    assert!(sync.on_blocks(&"a", blocks).unwrap().is_empty());
+
+    let ready = sync.drain_ready();
+    let heights: Vec<u64> = ready.iter().map(|b| b.index).collect();
+    assert_eq!(heights, vec![0, 1, 2, 3, 4]);
+    assert!(sync.is_synced());
+    assert_eq!(sync.tip(), Some(&(4, chain[4].hash.clone())));
+}
+
+// TEST 2 — Block batches are spread over all sync peers in parallel
+#[test]
+fn test_parallel_download_across_peers() {
+    let chain = build_chain(40);
+    let server = serving_node(&chain);
+    let mut sync = SyncManager::new(None);
+
+    let header_request = sync.add_peer("a", true).remove(0);
+    assert!(sync.add_peer("b", true).is_empty());
+
+    let headers = match serve_request(&server, &header_request.1) {
+        SyncResponse::Headers(headers) => headers,
+        _ => panic!("wrong response"),
+    };
+    let requests = sync.on_headers(&"a", headers).unwrap();
+    assert_eq!(requests.len(), 2);
+
+    let a = requests.iter().find(|(peer, _)| *peer == "a").unwrap();
+    let b = requests.iter().find(|(peer, _)| *peer == "b").unwrap();
+    let a_hashes = requested_hashes(&a.1);
+    let b_hashes = requested_hashes(&b.1);
+    assert_eq!(a_hashes.len(), MAX_BLOCKS_PER_REQUEST);
+    assert_eq!(b_hashes.len(), MAX_BLOCKS_PER_REQUEST);
+    assert!(a_hashes.iter().all(|h| !b_hashes.contains(h)));
+
+    // Blocks arriving out of order are held back until the gap is filled
+    let later = if a_hashes.contains(&chain[0].hash) { b } else { a };
+    let earlier = if later.0 == "a" { b } else { a };
+    sync.on_blocks(&later.0, blocks_for(&server, &later.1)).unwrap();
+    assert!(sync.drain_ready().is_empty());
+
+    sync.on_blocks(&earlier.0, blocks_for(&server, &earlier.1)).unwrap();
+    assert_eq!(sync.drain_ready().len(), 2 * MAX_BLOCKS_PER_REQUEST);
+}
+
+// TEST 3 — A disconnected peer's blocks are requested again, and a restarted
+//          node resumes from its stored tip
+#[test]
+fn test_sync_resumes_after_disconnect() {
+    let chain = build_chain(20);
+    let server = serving_node(&chain);
+    let mut sync = SyncManager::new(None);
+
+    sync.add_peer("a", true);
+    let headers: Vec<_> = chain.iter().map(|b| b.header()).collect();
+    let first = sync.on_headers(&"a", headers).unwrap().remove(0);
+    let lost = requested_hashes(&first.1);
+
+    // The only peer drops: nothing can be requested until another arrives
+    assert!(sync.remove_peer(&"a").is_empty());
+    let requests = sync.add_peer("b", true);
+    let batch = requests
+        .iter()
+        .find(|(_, r)| matches!(r, SyncRequest::GetBlocks { .. }))
+        .unwrap();
+    assert_eq!(requested_hashes(&batch.1), lost);
+
+    sync.on_blocks(&"b", blocks_for(&server, &batch.1)).unwrap();
+    assert_eq!(sync.drain_ready().len(), MAX_BLOCKS_PER_REQUEST);
+
+    // After a restart the locator starts from the stored tip
+    let tip = (15, chain[15].hash.clone());
+    let mut restarted = SyncManager::new(Some(tip.clone()));
+    let request = restarted.add_peer("b", true).remove(0).1;
+    assert_eq!(request, SyncRequest::GetHeaders { locator: vec![tip.1] });
+    match serve_request(&server, &request) {
+        SyncResponse::Headers(headers) => {
+            let heights: Vec<u64> = headers.iter().map(|h| h.index).collect();
+            assert_eq!(heights, vec![16, 17, 18, 19]);
+        }
+        _ => panic!("wrong response"),
+    }
+}
+
+// TEST 4 — Headers that do not chain and unrequested blocks are errors
+#[test]
+fn test_sync_rejects_bad_responses() {
+    let chain = build_chain(4);
+    let mut sync = SyncManager::new(None);
+    sync.add_peer("a", true);
+
+    let mut headers: Vec<_> = chain.iter().map(|b| b.header()).collect();
+    headers.swap(1, 2);
+    assert_eq!(sync.on_headers(&"a", headers), Err(SyncError::InvalidHeaders));
+
+    let headers: Vec<_> = chain[..2].iter().map(|b| b.header()).collect();
+    sync.on_headers(&"a", headers).unwrap();
+    match sync.on_blocks(&"a", vec![chain[3].clone()]) {
+        Err(SyncError::UnsolicitedBlock) => {}
+        _ => panic!("wrong error variant"),
+    }
+    match sync.on_headers(&"a", vec![chain[3].header()]) {
+        Err(SyncError::UnconnectedHeaders) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
+
+// TEST 5 — The chain manager connects synced batches and reports its tip
+#[tokio::test]
+async fn test_chain_manager_connects_synced_blocks() {
+    let chain = build_chain(3);
+    let (command_tx, command_rx) = mpsc::channel::<ChainCommand<&str, ()>>(8);
+    let (event_tx, mut event_rx) = mpsc::channel(8);
+    let task = tokio::spawn(run_chain_manager(
+        ChainManager::new(UtxoSet::default()),
+        command_rx,
+        event_tx,
+    ));
+
+    command_tx.send(ChainCommand::Synced { blocks: chain.clone() }).await.unwrap();
+    match event_rx.recv().await.unwrap() {
+        ChainEvent::SyncProcessed { rejected, tip, locator } => {
+            assert_eq!(rejected, None);
+            assert_eq!(tip, Some((2, chain[2].hash.clone())));
+            assert_eq!(locator, vec![chain[2].hash.clone(), chain[1].hash.clone(), chain[0].hash.clone()]);
+        }
+        _ => panic!("wrong event"),
+    }
+
+    command_tx
+        .send(ChainCommand::Serve {
+            request: SyncRequest::GetHeaders { locator: vec![chain[0].hash.clone()] },
+            channel: (),
+        })
+        .await
+        .unwrap();
+    match event_rx.recv().await.unwrap() {
+        ChainEvent::Served { response: SyncResponse::Headers(headers), .. } => {
+            assert_eq!(headers.len(), 2);
+        }
+        _ => panic!("wrong event"),
+    }
+
+    drop(command_tx);
+    let manager = task.await.unwrap();
+    assert_eq!(manager.state.utxo_set.len(), 3);
+}
+
+// TEST 6 — Tampered blocks and empty responses fail the peer, and its batch
+//          goes to the next one
+#[test]
+fn test_sync_rejects_tampered_and_empty_blocks() {
+    let chain = build_chain(2);
+    let hashes: Vec<Hash> = chain.iter().map(|b| b.hash.clone()).collect();
+    let mut sync = SyncManager::new(None);
+    sync.add_peer("a", true);
+    let headers: Vec<_> = chain.iter().map(|b| b.header()).collect();
+    sync.on_headers(&"a", headers).unwrap();
+
+    // Same header and transaction ids, different payout
+    let mut tampered = chain[0].clone();
+    tampered.transactions[0].outputs[0].value += 1;
+    assert_eq!(tampered.header(), chain[0].header());
+    match sync.on_blocks(&"a", vec![tampered]) {
+        Err(SyncError::HashMismatch) => {}
+        _ => panic!("wrong error variant"),
+    }
+    sync.remove_peer(&"a");
+
+    let batch = |requests: Vec<(&'static str, SyncRequest)>| {
+        let (_, request) = requests
+            .into_iter()
+            .find(|(_, r)| matches!(r, SyncRequest::GetBlocks { .. }))
+            .unwrap();
+        requested_hashes(&request)
+    };
+    assert_eq!(batch(sync.add_peer("b", true)), hashes);
+    match sync.on_blocks(&"b", Vec::new()) {
+        Err(SyncError::NoBlocks) => {}
+        _ => panic!("wrong error variant"),
+    }
+    sync.remove_peer(&"b");
+
+    assert_eq!(batch(sync.add_peer("c", true)), hashes);
+    sync.on_blocks(&"c", chain.clone()).unwrap();
+    assert_eq!(sync.drain_ready().len(), 2);
+}
+
+// TEST 7 — Locators step back exponentially to genesis, so a peer on another
+// branch answers from near the fork instead of from genesis
+#[test]
+fn test_locator_reaches_fork_point() {
+    let chain = build_chain(20);
+    let ours = serving_node(&chain);
+    let heights: Vec<u64> = block_locator(&ours)
+        .iter()
+        .map(|hash| ours.main_chain_height(hash).unwrap().unwrap())
+        .collect();
+    assert_eq!(heights, vec![19, 18, 16, 12, 4, 0]);
+
+    // The peer shares our first 10 blocks, then follows its own branch
+    let mut theirs = chain[..10].to_vec();
+    for index in 10..15 {
+        let prev_hash = theirs.last().unwrap().hash.clone();
+        theirs.push(block(index, prev_hash, vec![coinbase(index, addr("rival"), 50)]));
+    }
+    let server = serving_node(&theirs);
+
+    let mut sync = SyncManager::new(Some((19, chain[19].hash.clone())));
+    sync.set_chain_locator(block_locator(&ours));
+    let requests = sync.add_peer("a", true);
+    let headers = match serve_request(&server, &requests[0].1) {
+        SyncResponse::Headers(headers) => headers,
+        _ => panic!("wrong response"),
+    };
+    assert_eq!(headers.first().map(|h| h.index), Some(5));
+    assert_eq!(headers.len(), 10);
+
+    match sync.on_headers(&"a", headers) {
+        Err(SyncError::UnconnectedHeaders) => {}
+        _ => panic!("wrong error variant"),
+    }
+}
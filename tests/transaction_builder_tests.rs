@@ -1,12 +1,15 @@
 use arcnova_chain::blockchain::{
-    state::State,
+    state::{State, BLOCK_REWARD},
     tx_builder::TxBuilder,
-    transaction::{TxOutput, Transaction},
+    transaction::TxOutput,
     utxo::UtxoSet,
     wallet::Wallet,
 };
 use arcnova_chain::crypto::hash::Hash;
 
+mod common;
+use common::{addr, block, coinbase};
+
 // TEST 1 — UTXO selection + change logic
 #[test]
 fn test_tx_builder_basic_send() {
@@ -16,11 +19,11 @@ fn test_tx_builder_basic_send() {
     let fake_txid = Hash([9u8; 32]);
 
     let mut utxo_set = UtxoSet::default();
-    utxo_set.utxos.insert(
+    utxo_set.insert(
         (fake_txid.clone(), 0),
         TxOutput {
             value: 100_000,
-            address: from.clone(),
+            address: from,
         },
     );
 
@@ -28,13 +31,13 @@ fn test_tx_builder_basic_send() {
     let builder = TxBuilder::new(&state);
 
     let tx = builder
-        .build_payment_tx(&wallet, "bob", 50_000, 1_000)
+        .build_payment_tx(&wallet, &addr("bob"), 50_000, 1_000)
         .unwrap();
 
     assert_eq!(tx.outputs.len(), 2);
     assert_eq!(tx.outputs[0].value, 50_000);
     assert_eq!(tx.outputs[1].value, 49_000);
-    assert_eq!(tx.outputs[0].address, "bob");
+    assert_eq!(tx.outputs[0].address, addr("bob"));
     assert_eq!(tx.outputs[1].address, from);
 
     assert!(state.validate_transaction(&tx));
@@ -49,11 +52,11 @@ fn test_tx_builder_insufficient_funds() {
     let fake_txid = Hash([2u8; 32]);
 
     let mut utxo_set = UtxoSet::default();
-    utxo_set.utxos.insert(
+    utxo_set.insert(
         (fake_txid.clone(), 0),
         TxOutput {
             value: 10_000,
-            address: from.clone(),
+            address: from,
         },
     );
 
@@ -61,7 +64,7 @@ fn test_tx_builder_insufficient_funds() {
     let builder = TxBuilder::new(&state);
 
     let err = builder
-        .build_payment_tx(&wallet, "alice", 50_000, 1_000)
+        .build_payment_tx(&wallet, &addr("alice"), 50_000, 1_000)
         .unwrap_err();
 
     match err {
@@ -81,7 +84,7 @@ fn test_tx_builder_no_utxos() {
     let builder = TxBuilder::new(&state);
 
     let err = builder
-        .build_payment_tx(&wallet, "bob", 10_000, 100)
+        .build_payment_tx(&wallet, &addr("bob"), 10_000, 100)
         .unwrap_err();
 
     match err {
@@ -98,11 +101,11 @@ fn test_txid_not_zero() {
     let fake_txid = Hash([7u8; 32]);
 
     let mut utxo_set = UtxoSet::default();
-    utxo_set.utxos.insert(
+    utxo_set.insert(
         (fake_txid.clone(), 0),
         TxOutput {
             value: 42_000,
-            address: from.clone(),
+            address: from,
         },
     );
 
@@ -110,7 +113,7 @@ fn test_txid_not_zero() {
     let builder = TxBuilder::new(&state);
 
     let tx = builder
-        .build_payment_tx(&wallet, "test", 20_000, 1_000)
+        .build_payment_tx(&wallet, &addr("test"), 20_000, 1_000)
         .unwrap();
 
     assert_ne!(tx.id.0, [0u8; 32]);
@@ -125,11 +128,11 @@ fn test_transaction_signature_valid() {
     let fake_txid = Hash([3u8; 32]);
 
     let mut utxo_set = UtxoSet::default();
-    utxo_set.utxos.insert(
+    utxo_set.insert(
         (fake_txid.clone(), 0),
         TxOutput {
             value: 60_000,
-            address: from.clone(),
+            address: from,
         },
     );
 
@@ -137,7 +140,7 @@ fn test_transaction_signature_valid() {
     let builder = TxBuilder::new(&state);
 
     let tx = builder
-        .build_payment_tx(&wallet, "bob", 10_000, 1_000)
+        .build_payment_tx(&wallet, &addr("bob"), 10_000, 1_000)
         .unwrap();
 
     assert!(state.validate_transaction(&tx));
@@ -152,11 +155,11 @@ fn test_invalid_signature_fails_validation() {
     let fake_txid = Hash([5u8; 32]);
 
     let mut utxo_set = UtxoSet::default();
-    utxo_set.utxos.insert(
+    utxo_set.insert(
         (fake_txid.clone(), 0),
         TxOutput {
             value: 80_000,
-            address: from.clone(),
+            address: from,
         },
     );
 
@@ -164,7 +167,7 @@ fn test_invalid_signature_fails_validation() {
     let builder = TxBuilder::new(&state);
 
     let mut tx = builder
-        .build_payment_tx(&wallet, "bob", 20_000, 1_000)
+        .build_payment_tx(&wallet, &addr("bob"), 20_000, 1_000)
         .unwrap();
 
     // Corrupt signature to force invalid
@@ -182,11 +185,11 @@ fn test_double_spend_detected() {
 
     let mut utxo_set = UtxoSet::default();
 
-    utxo_set.utxos.insert(
+    utxo_set.insert(
         (fake_txid.clone(), 0),
         TxOutput {
             value: 100_000,
-            address: from.clone(),
+            address: from,
         },
     );
 
@@ -195,7 +198,7 @@ fn test_double_spend_detected() {
 
     // Build first tx
     let builder = TxBuilder::new(&state);
-    let tx1 = builder.build_payment_tx(&wallet, "bob", 30_000, 1_000).unwrap();
+    let tx1 = builder.build_payment_tx(&wallet, &addr("bob"), 30_000, 1_000).unwrap();
     assert!(state.validate_transaction(&tx1));
 
     // Apply first tx (spends the UTXO)
@@ -203,29 +206,30 @@ fn test_double_spend_detected() {
 
     // Build second tx which attempts to spend same UTXO again
     let builder2 = TxBuilder::new(&state);
-    let tx2 = builder2.build_payment_tx(&wallet, "alice", 20_000, 1_000);
+    let tx2 = builder2.build_payment_tx(&wallet, &addr("alice"), 20_000, 1_000);
 
     // This should fail (no UTXOs left)
     assert!(tx2.is_err());
 }
 
-/// TEST 8 — Coinbase transaction is always valid
+/// TEST 8 — Coinbase is only valid at the head of a block, up to the reward
 #[test]
 fn test_coinbase_is_valid() {
-    let coinbase = Transaction {
-        id: Hash([1u8; 32]),
-        inputs: vec![],   // coinbase has no inputs
-        outputs: vec![TxOutput {
-            value: 50_000,
-            address: "miner1".into(),
-        }],
-    };
-
     let state = State {
         utxo_set: UtxoSet::default(),
     };
 
-    assert!(state.validate_transaction(&coinbase));
+    // On its own, a coinbase could print money
+    let reward = coinbase(1, addr("miner1"), BLOCK_REWARD);
+    assert!(!state.validate_transaction(&reward));
+
+    assert!(state.validate_block(&block(1, Hash([0u8; 32]), vec![reward])));
+    let greedy = coinbase(1, addr("miner1"), BLOCK_REWARD + 1);
+    assert!(!state.validate_block(&block(1, Hash([0u8; 32]), vec![greedy])));
+
+    // Its id commits to the height of its block
+    let misplaced = coinbase(2, addr("miner1"), BLOCK_REWARD);
+    assert!(!state.validate_block(&block(1, Hash([0u8; 32]), vec![misplaced])));
 }
 
 
@@ -240,11 +244,11 @@ fn test_invalid_inflation_transaction() {
     let fake_txid = Hash([4u8; 32]);
 
     let mut utxo_set = UtxoSet::default();
-    utxo_set.utxos.insert(
+    utxo_set.insert(
         (fake_txid.clone(), 0),
         TxOutput {
             value: 20_000,
-            address: from.clone(),
+            address: from,
         },
     );
 
@@ -253,7 +257,7 @@ fn test_invalid_inflation_transaction() {
 
     // Force an invalid tx with too-large output
     let mut tx = builder
-        .build_payment_tx(&wallet, "bob", 19_000, 1_000)
+        .build_payment_tx(&wallet, &addr("bob"), 19_000, 1_000)
         .unwrap();
 
     // Modify outputs to exceed total inputs
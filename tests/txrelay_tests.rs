@@ -0,0 +1,143 @@
+use std::time::{Duration, Instant};
+
+use arcnova_chain::blockchain::{
+    chain::{ChainManager, Verdict},
+    tx_builder::TxBuilder,
+    utxo::UtxoSet,
+    wallet::Wallet,
+};
+use arcnova_chain::crypto::hash::Hash;
+use arcnova_chain::p2p::pipeline::{run_chain_manager, ChainCommand, ChainEvent};
+use arcnova_chain::p2p::sync::{SyncRequest, SyncResponse};
+use arcnova_chain::p2p::txrelay::{TxRelay, MAX_INV_SIZE, RATE_PER_SECOND, TRICKLE_INTERVAL, TX_REQUEST_TIMEOUT};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use tokio::sync::mpsc;
+
+mod common;
+use common::{addr, block, coinbase};
+
+fn txid(n: u32) -> Hash {
+    let mut bytes = [0u8; 32];
+    bytes[..4].copy_from_slice(&n.to_le_bytes());
+    Hash(bytes)
+}
+
+// Long enough for every peer's random trickle delay to have passed
+fn much_later(now: Instant) -> Instant {
+    now + TRICKLE_INTERVAL * 100
+}
+
+// TEST 1 — Accepted transactions are trickled to peers that do not know them
+#[test]
+fn test_announce_trickles_to_unaware_peers() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let start = Instant::now();
+    let mut relay = TxRelay::default();
+    relay.add_peer("a", start, &mut rng);
+    relay.add_peer("b", start, &mut rng);
+
+    // "a" announced the transaction to us, so only "b" hears about it
+    assert_eq!(relay.on_inv(&"a", vec![txid(1)], start), vec![txid(1)]);
+    assert!(relay.on_tx(&"a", &txid(1)));
+    relay.announce(txid(1));
+
+    assert!(relay.trickle(start, &mut rng).is_empty());
+    let due = relay.trickle(much_later(start), &mut rng);
+    assert_eq!(due, vec![("b", vec![txid(1)])]);
+
+    // Nothing left to send
+    assert!(relay.trickle(much_later(much_later(start)), &mut rng).is_empty());
+}
+
+// TEST 2 — Only unseen, not yet requested txids are fetched; timeouts retry elsewhere
+#[test]
+fn test_inv_requests_and_retries() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let now = Instant::now();
+    let mut relay = TxRelay::default();
+    for peer in ["a", "b"] {
+        relay.add_peer(peer, now, &mut rng);
+    }
+    relay.announce(txid(9));
+
+    assert_eq!(relay.on_inv(&"a", vec![txid(1), txid(9)], now), vec![txid(1)]);
+    assert!(relay.on_inv(&"b", vec![txid(1)], now).is_empty());
+    assert!(relay.timed_out(now).is_empty());
+
+    // "a" never answers: ask "b", then give up
+    let retries = relay.timed_out(now + TX_REQUEST_TIMEOUT);
+    assert_eq!(retries, vec![("b", vec![txid(1)])]);
+    assert!(!relay.on_tx(&"a", &txid(1)));
+    assert!(relay.timed_out(now + TX_REQUEST_TIMEOUT * 3).is_empty());
+    assert!(!relay.has_seen(&txid(1)));
+}
+
+// TEST 3 — Peers may only fetch what was announced to them
+#[test]
+fn test_getdata_limited_to_announced() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let now = Instant::now();
+    let mut relay = TxRelay::default();
+    relay.add_peer("a", now, &mut rng);
+    relay.announce(txid(1));
+    relay.announce(txid(2));
+
+    // Not yet trickled out
+    assert!(relay.on_getdata(&"a", vec![txid(1)], now).is_empty());
+
+    relay.trickle(much_later(now), &mut rng);
+    let allowed = relay.on_getdata(&"a", vec![txid(1), txid(2), txid(3)], much_later(now));
+    assert_eq!(allowed, vec![txid(1), txid(2)]);
+    assert!(relay.on_getdata(&"unknown", vec![txid(1)], now).is_empty());
+}
+
+// TEST 4 — Announcements beyond the per-peer rate are dropped
+#[test]
+fn test_inv_rate_limit() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let now = Instant::now();
+    let mut relay = TxRelay::default();
+    relay.add_peer("a", now, &mut rng);
+
+    let mut next = 0;
+    let mut batch = |count: usize| {
+        let ids: Vec<Hash> = (next..next + count as u32).map(txid).collect();
+        next += count as u32;
+        ids
+    };
+
+    // The burst allowance covers two full announcements
+    assert_eq!(relay.on_inv(&"a", batch(MAX_INV_SIZE + 500), now).len(), MAX_INV_SIZE);
+    assert_eq!(relay.on_inv(&"a", batch(MAX_INV_SIZE), now).len(), MAX_INV_SIZE);
+    assert!(relay.on_inv(&"a", batch(10), now).is_empty());
+
+    let later = now + Duration::from_secs(1);
+    assert_eq!(relay.on_inv(&"a", batch(MAX_INV_SIZE), later).len(), RATE_PER_SECOND as usize);
+}
+
+// TEST 5 — Requested transactions are served from the mempool
+#[tokio::test]
+async fn test_get_txs_served_from_mempool() {
+    let wallet = Wallet::new();
+    let mut manager = ChainManager::new(UtxoSet::default());
+    let genesis = block(0, Hash([0u8; 32]), vec![coinbase(0, wallet.address(), 10_000)]);
+    assert_eq!(manager.process_block(genesis), Verdict::Accept);
+    let tx = TxBuilder::new(&manager.state).build_payment_tx(&wallet, &addr("bob"), 100, 10).unwrap();
+    manager.process_transaction(tx.clone());
+
+    let (command_tx, command_rx) = mpsc::channel::<ChainCommand<&str, u8>>(8);
+    let (event_tx, mut event_rx) = mpsc::channel(8);
+    tokio::spawn(run_chain_manager(manager, command_rx, event_tx));
+
+    let request = SyncRequest::GetTxs { txids: vec![tx.id.clone(), txid(7)] };
+    command_tx.send(ChainCommand::Serve { request, channel: 5 }).await.unwrap();
+    match event_rx.recv().await.unwrap() {
+        ChainEvent::Served { channel, response: SyncResponse::Txs(txs) } => {
+            assert_eq!(channel, 5);
+            assert_eq!(txs.len(), 1);
+            assert_eq!(txs[0].id, tx.id);
+        }
+        _ => panic!("wrong event"),
+    }
+}
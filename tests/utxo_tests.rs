@@ -0,0 +1,96 @@
+use arcnova_chain::blockchain::{
+    address::Address,
+    block::Block,
+    transaction::{Transaction, TxInput, TxOutput},
+    utxo::UtxoSet,
+};
+use arcnova_chain::crypto::hash::Hash;
+
+mod common;
+use common::addr;
+
+fn output(address: Address, value: u64) -> TxOutput {
+    TxOutput { value, address }
+}
+
+// TEST 1 — Address index follows apply_transaction
+#[test]
+fn test_address_index_tracks_spends() {
+    let mut utxo_set = UtxoSet::default();
+    utxo_set.insert((Hash([1u8; 32]), 0), output(addr("alice"), 40));
+    utxo_set.insert((Hash([1u8; 32]), 1), output(addr("bob"), 10));
+
+    let tx = Transaction {
+        id: Hash([2u8; 32]),
+        inputs: vec![TxInput {
+            txid: Hash([1u8; 32]),
+            index: 0,
+            signature: vec![],
+            public_key: vec![],
+        }],
+        outputs: vec![output(addr("bob"), 25), output(addr("alice"), 15)],
+    };
+    utxo_set.apply_transaction(&tx);
+
+    assert_eq!(utxo_set.available_balance(&addr("alice")), 15);
+    assert_eq!(utxo_set.available_balance(&addr("bob")), 35);
+    assert_eq!(utxo_set.find_unspent(&addr("bob")).len(), 2);
+    assert!(utxo_set.find_unspent(&addr("carol")).is_empty());
+}
+
+// TEST 2 — Undoing a block restores the index
+#[test]
+fn test_address_index_after_undo() {
+    let mut utxo_set = UtxoSet::default();
+    utxo_set.insert((Hash([1u8; 32]), 0), output(addr("alice"), 40));
+
+    let block = Block {
+        index: 1,
+        prev_hash: Hash([0u8; 32]),
+        timestamp: 0,
+        transactions: vec![Transaction {
+            id: Hash([2u8; 32]),
+            inputs: vec![TxInput {
+                txid: Hash([1u8; 32]),
+                index: 0,
+                signature: vec![],
+                public_key: vec![],
+            }],
+            outputs: vec![output(addr("bob"), 40)],
+        }],
+        nonce: 0,
+        hash: Hash([3u8; 32]),
+    };
+
+    let undo = utxo_set.apply_block(&block);
+    assert_eq!(utxo_set.available_balance(&addr("bob")), 40);
+
+    utxo_set.undo_block(&block, &undo);
+    assert_eq!(utxo_set.available_balance(&addr("alice")), 40);
+    assert_eq!(utxo_set.available_balance(&addr("bob")), 0);
+    assert_eq!(utxo_set.len(), 1);
+}
+
+// TEST 3 — Paginated iteration visits every output exactly once
+#[test]
+fn test_find_unspent_pagination() {
+    let mut utxo_set = UtxoSet::default();
+    for i in 0..25u32 {
+        utxo_set.insert((Hash([i as u8; 32]), i), output(addr("alice"), 1));
+    }
+    utxo_set.insert((Hash([9u8; 32]), 100), output(addr("bob"), 1));
+
+    let mut seen = Vec::new();
+    let mut after = None;
+    loop {
+        let page = utxo_set.find_unspent_page(&addr("alice"), after.as_ref(), 10);
+        if page.is_empty() {
+            break;
+        }
+        after = page.last().map(|(k, _)| k.clone());
+        seen.extend(page.into_iter().map(|(k, _)| k));
+    }
+
+    assert_eq!(seen.len(), 25);
+    assert!(seen.windows(2).all(|w| w[0] < w[1]));
+}
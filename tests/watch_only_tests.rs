@@ -0,0 +1,128 @@
+use arcnova_chain::blockchain::{
+    address::Address,
+    block::Block,
+    coin_select::LargestFirst,
+    multiwallet::{Balance, MultiWallet},
+    psbt::Psbt,
+    state::State,
+    transaction::{Transaction, TxOutput},
+    tx_builder::{TxBuildError, TxBuilder},
+    utxo::UtxoSet,
+    wallet::Wallet,
+};
+use arcnova_chain::crypto::hash::Hash;
+use arcnova_chain::storage::Store;
+
+mod common;
+use common::addr;
+
+// One block paying each of `payees`, with the chain state to match
+fn genesis(payees: &[(Address, u64)]) -> (Block, State) {
+    let coinbase = Transaction {
+        id: Hash([1u8; 32]),
+        inputs: vec![],
+        outputs: payees
+            .iter()
+            .map(|(address, value)| TxOutput { value: *value, address: *address })
+            .collect(),
+    };
+    let block = Block {
+        index: 0,
+        prev_hash: Hash([0u8; 32]),
+        timestamp: 0,
+        transactions: vec![coinbase],
+        nonce: 0,
+        hash: Hash([100u8; 32]),
+    };
+
+    let mut utxos = UtxoSet::default();
+    utxos.connect_block(&block).unwrap();
+    (block, State { utxo_set: utxos })
+}
+
+// TEST 1 — Public keys alone are enough to follow balances
+#[test]
+fn test_watch_only_tracks_balance() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let (block, _) = genesis(&[(alice.address(), 30_000), (bob.address(), 12_000), (addr("other"), 5)]);
+
+    let alice_key = alice.public_key_bytes();
+    let bob_key = bob.public_key_bytes();
+    let mut watcher = MultiWallet::watch_only([&alice_key[..], &bob_key[..]]).unwrap();
+    watcher.connect_block(&block);
+
+    assert_eq!(watcher.balance(), Balance { confirmed: 42_000, pending: 0 });
+    assert!(watcher.is_watch_only(&alice.address()));
+    assert!(watcher.key(&alice.address()).is_none());
+    assert_eq!(watcher.public_key(&bob.address()), Some(bob_key));
+
+    assert!(MultiWallet::watch_only([&[1u8, 2, 3][..]]).is_none());
+    assert_eq!(watcher.add_public_key(&[0u8; 31]), None);
+}
+
+// TEST 2 — A watcher builds, an offline signer signs
+#[test]
+fn test_watch_only_spend_through_psbt() {
+    let signer = Wallet::new();
+    let (block, state) = genesis(&[(signer.address(), 50_000)]);
+    let mut watcher = MultiWallet::new();
+    let address = watcher.add_public_key(&signer.public_key_bytes()).unwrap();
+    watcher.connect_block(&block);
+
+    let builder = TxBuilder::new(&state);
+    // Nothing it could sign itself
+    match builder.build_wallet_payment(&watcher, &addr("bob"), 20_000, 500, &address) {
+        Err(TxBuildError::NoUtxos) => {}
+        _ => panic!("wrong error variant"),
+    }
+
+    let psbt = builder
+        .build_wallet_psbt(&watcher, &addr("bob"), 20_000, 500, &address, &LargestFirst)
+        .unwrap();
+    let mut offline = Psbt::from_bytes(&psbt.to_bytes()).unwrap();
+    assert_eq!(offline.sign(&signer).unwrap(), 1);
+    let tx = offline.finalize().unwrap();
+    assert!(state.validate_transaction(&tx));
+
+    assert!(watcher.add_pending(tx));
+    assert_eq!(watcher.balance(), Balance { confirmed: 0, pending: 29_500 });
+}
+
+// TEST 3 — Full and watch-only keys side by side
+#[test]
+fn test_mixed_wallet() {
+    let hot = Wallet::new();
+    let cold = Wallet::new();
+    let hot_public = hot.public_key_bytes();
+    let (block, state) = genesis(&[(hot.address(), 10_000), (cold.address(), 40_000)]);
+
+    let mut wallet = MultiWallet::new();
+    let hot_address = wallet.add_key(hot);
+    wallet.add_public_key(&cold.public_key_bytes()).unwrap();
+    wallet.connect_block(&block);
+
+    // Watching a key we already hold does not downgrade it
+    wallet.add_public_key(&hot_public).unwrap();
+    assert!(!wallet.is_watch_only(&hot_address));
+
+    // Signing here only spends the hot key's outputs
+    let builder = TxBuilder::new(&state);
+    let tx = builder
+        .build_wallet_payment(&wallet, &addr("bob"), 9_000, 100, &hot_address)
+        .unwrap();
+    assert!(state.validate_transaction(&tx));
+    match builder.build_wallet_payment(&wallet, &addr("bob"), 20_000, 100, &hot_address) {
+        Err(TxBuildError::InsufficientFunds { available, .. }) => assert_eq!(available, 10_000),
+        _ => panic!("wrong error variant"),
+    }
+
+    // Larger ones need the cold key: sign what we can here, the rest there
+    let mut psbt = builder
+        .build_wallet_psbt(&wallet, &addr("bob"), 45_000, 100, &hot_address, &LargestFirst)
+        .unwrap();
+    assert_eq!(psbt.sign(wallet.key(&hot_address).unwrap()).unwrap(), 1);
+    assert!(psbt.finalize().is_err());
+    assert_eq!(psbt.sign(&cold).unwrap(), 1);
+    assert!(state.validate_transaction(&psbt.finalize().unwrap()));
+}